@@ -0,0 +1,7 @@
+-- Migration 004: Per-topic sequence numbers
+-- Chaque message reçoit un numéro de séquence monotone au sein de son sujet.
+-- Les lignes existantes gardent `seq = NULL` (antérieures à la numérotation).
+ALTER TABLE messages ADD COLUMN seq INTEGER;
+
+-- Index pour retrouver rapidement le dernier `seq` d'un sujet au démarrage.
+CREATE INDEX IF NOT EXISTS idx_messages_topic_seq ON messages (topic, seq);
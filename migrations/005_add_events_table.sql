@@ -0,0 +1,14 @@
+-- Migration 005: Audit log of broker events
+-- Persiste le flux `BroadcastEvent` (new_client, new_message, client_disconnected, ...)
+-- lorsque `PERSIST_EVENTS` est activé.
+CREATE TABLE IF NOT EXISTS events
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_type TEXT NOT NULL,
+    data       TEXT NOT NULL,
+    timestamp  REAL NOT NULL
+);
+
+-- Index pour les requêtes `GET /events?since=...&type=...`.
+CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events (timestamp);
+CREATE INDEX IF NOT EXISTS idx_events_type_timestamp ON events (event_type, timestamp);
@@ -0,0 +1,7 @@
+-- Migration 006: Per-producer sequence numbers
+-- Numéro de séquence monotone propre à chaque producteur, tous sujets confondus (`PRODUCER_SEQUENCES`).
+-- `NULL` pour les messages publiés sans cette option ou antérieurs à la migration.
+ALTER TABLE messages ADD COLUMN producer_seq INTEGER;
+
+-- Index pour retrouver rapidement le dernier `producer_seq` d'un producteur au démarrage.
+CREATE INDEX IF NOT EXISTS idx_messages_producer_seq ON messages (producer, producer_seq);
@@ -0,0 +1,6 @@
+-- Migration 007: Per-topic unique message keys
+-- Clé métier d'un message, renseignée uniquement sur les sujets à clé unique (`UNIQUE_KEY_TOPICS`).
+ALTER TABLE messages ADD COLUMN key TEXT;
+
+-- Au plus un message par clé et par sujet ; les messages sans clé ne sont pas concernés.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_topic_key ON messages (topic, key) WHERE key IS NOT NULL;
@@ -0,0 +1,13 @@
+-- Migration 008: Scheduled delivery
+-- Publications en attente de leur date de livraison (`deliver_at`), relues par le planificateur.
+CREATE TABLE IF NOT EXISTS scheduled_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id TEXT NOT NULL UNIQUE,
+    topic TEXT NOT NULL,
+    -- Publication complète (`PublishRequest` JSON), rejouée telle quelle à l'échéance.
+    payload TEXT NOT NULL,
+    deliver_at REAL NOT NULL,
+    created_at REAL NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_messages_deliver_at ON scheduled_messages (deliver_at);
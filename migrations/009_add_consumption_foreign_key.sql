@@ -0,0 +1,37 @@
+-- Migration 009: Referential integrity between consumptions and messages
+-- Chaque consommation référence désormais la ligne du message consommé (`message_ref`) :
+-- la suppression d'un message (purge, remplacement d'une clé unique) supprime ses consommations
+-- (`ON DELETE CASCADE`, actif grâce à `PRAGMA foreign_keys = ON`).
+-- SQLite ne sait pas ajouter une contrainte à une table existante : la table est reconstruite.
+-- Un même `message_id` pouvant être republié, la consommation est rattachée à la publication la plus récente.
+CREATE TABLE consumptions_new
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    consumer    TEXT,
+    topic       TEXT,
+    message_id  TEXT,
+    message     TEXT,
+    timestamp   REAL,
+    message_ref INTEGER NOT NULL REFERENCES messages (id) ON DELETE CASCADE
+);
+
+-- Les consommations orphelines (message déjà purgé) ne sont pas reprises.
+INSERT INTO consumptions_new (consumer, topic, message_id, message, timestamp, message_ref)
+SELECT c.consumer, c.topic, c.message_id, c.message, c.timestamp, m.ref
+FROM consumptions c
+JOIN (SELECT topic, message_id, MAX(id) AS ref FROM messages GROUP BY topic, message_id) m
+    ON m.topic = c.topic AND m.message_id = c.message_id
+ORDER BY c.rowid;
+
+DROP TABLE consumptions;
+ALTER TABLE consumptions_new RENAME TO consumptions;
+
+-- Index supprimés avec l'ancienne table (migration 002).
+CREATE INDEX IF NOT EXISTS idx_consumptions_consumer_timestamp ON consumptions (consumer, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_consumptions_topic_timestamp ON consumptions (topic, timestamp DESC);
+
+-- Index de la clé étrangère : sans lui, chaque suppression de message parcourt toute la table.
+CREATE INDEX IF NOT EXISTS idx_consumptions_message_ref ON consumptions (message_ref);
+
+-- Résolution de `message_ref` à l'insertion d'une consommation.
+CREATE INDEX IF NOT EXISTS idx_messages_topic_message_id ON messages (topic, message_id);
@@ -0,0 +1,17 @@
+-- Migration 010: Persistent QoS 1 delivery state
+-- État de livraison d'un message à un consommateur (`PERSIST_DELIVERY_ACKS`) :
+-- `delivered` (envoyé, accusé attendu, `frame` conservée pour un renvoi après reconnexion)
+-- puis `acked` (trame `consumed` reçue, le message n'est plus jamais relivré à ce consommateur).
+CREATE TABLE IF NOT EXISTS deliveries
+(
+    consumer   TEXT NOT NULL,
+    topic      TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    state      TEXT NOT NULL CHECK (state IN ('delivered', 'acked')),
+    frame      TEXT,
+    updated_at REAL NOT NULL,
+    PRIMARY KEY (consumer, topic, message_id)
+);
+
+-- Index pour la purge par âge.
+CREATE INDEX IF NOT EXISTS idx_deliveries_updated_at ON deliveries (updated_at);
@@ -0,0 +1,13 @@
+-- Migration 011: Graph-state history
+-- Historique de l'état du graphe (`GRAPH_HISTORY_INTERVAL_SECS`) : un instantané complet (`full`)
+-- sert de point de reprise, les suivants (`delta`) ne contiennent que les nœuds et liens ajoutés ou retirés.
+CREATE TABLE IF NOT EXISTS graph_snapshots
+(
+    id       INTEGER PRIMARY KEY AUTOINCREMENT,
+    taken_at REAL NOT NULL,
+    kind     TEXT NOT NULL CHECK (kind IN ('full', 'delta')),
+    data     TEXT NOT NULL
+);
+
+-- Index pour la recherche du point de reprise le plus proche et pour la purge par âge.
+CREATE INDEX IF NOT EXISTS idx_graph_snapshots_kind_taken_at ON graph_snapshots (kind, taken_at);
@@ -0,0 +1,7 @@
+-- Migration 012: Message TTL
+-- Date d'expiration d'un message publié avec `ttl_seconds` (NULL = pas d'expiration).
+-- Un message expiré n'est plus diffusé ni listé, et il est supprimé à la purge suivante.
+ALTER TABLE messages ADD COLUMN expires_at REAL;
+
+-- Index pour la purge des messages expirés.
+CREATE INDEX IF NOT EXISTS idx_messages_expires_at ON messages (expires_at);
@@ -0,0 +1,4 @@
+-- Migration 013: Connection tags
+-- Étiquettes clé/valeur déclarées par le consommateur à l'abonnement (`tags`), en JSON,
+-- pour filtrer et regrouper les clients connectés (`GET /clients?tag.region=eu`).
+ALTER TABLE subscriptions ADD COLUMN tags TEXT;
@@ -0,0 +1,9 @@
+-- Migration 014: Per-topic JSON schemas
+-- Schéma JSON imposé aux messages publiés sur un sujet (`POST /topics/{topic}/schema`) :
+-- une publication non conforme est refusée avec `422`.
+CREATE TABLE IF NOT EXISTS topic_schemas
+(
+    topic      TEXT PRIMARY KEY,
+    schema     TEXT NOT NULL,
+    updated_at REAL NOT NULL
+);
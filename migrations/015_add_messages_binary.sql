@@ -0,0 +1,16 @@
+-- Migration 015: Binary message payloads
+-- Publications binaires (`POST /publish/binary`), stockées telles quelles : le contenu brut n'est ni
+-- encodé en base64 ni mêlé aux messages JSON (`messages`), et il n'a pas de numéro de séquence.
+CREATE TABLE IF NOT EXISTS messages_binary
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    topic      TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    producer   TEXT NOT NULL,
+    payload    BLOB NOT NULL,
+    timestamp  REAL NOT NULL
+);
+
+-- Index pour la purge par âge et la suppression d'un sujet.
+CREATE INDEX IF NOT EXISTS idx_messages_binary_topic_timestamp ON messages_binary (topic, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_messages_binary_timestamp ON messages_binary (timestamp);
@@ -0,0 +1,17 @@
+-- Migration 016: Dead letters
+-- Messages restés sans aucune consommation au-delà de `DEAD_LETTER_GRACE_SECS`, recopiés par le
+-- balayage périodique du broker. Un message n'y figure qu'une fois par sujet.
+CREATE TABLE IF NOT EXISTS dead_letters
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    topic        TEXT NOT NULL,
+    message_id   TEXT NOT NULL,
+    message      TEXT NOT NULL,
+    producer     TEXT NOT NULL,
+    published_at REAL NOT NULL,
+    detected_at  REAL NOT NULL,
+    UNIQUE (topic, message_id)
+);
+
+-- Index pour la consultation (`GET /dead-letters`) et la purge par âge.
+CREATE INDEX IF NOT EXISTS idx_dead_letters_detected_at ON dead_letters (detected_at);
@@ -0,0 +1,20 @@
+-- Migration 017: Unique message_id
+-- Un `message_id` n'est plus publié qu'une fois : les republications d'un producteur idempotent
+-- sont écartées. Les doublons existants sont fusionnés dans la publication la plus ancienne,
+-- à laquelle leurs consommations sont rattachées avant leur suppression.
+UPDATE consumptions
+SET message_ref = (SELECT MIN(o.id)
+                   FROM messages o
+                            JOIN messages m ON m.message_id = o.message_id
+                   WHERE m.id = consumptions.message_ref)
+WHERE message_ref IN (SELECT m.id
+                      FROM messages m
+                      WHERE EXISTS (SELECT 1 FROM messages o WHERE o.message_id = m.message_id AND o.id < m.id));
+
+DELETE
+FROM messages
+WHERE EXISTS (SELECT 1 FROM messages o WHERE o.message_id = messages.message_id AND o.id < messages.id);
+
+-- Remplace l'index simple de la migration 001.
+DROP INDEX IF EXISTS idx_messages_message_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_message_id ON messages (message_id);
@@ -0,0 +1,28 @@
+-- Migration 018: Consumptions without a stored message
+-- Une consommation est enregistrée même lorsque son message n'est pas dans `messages` (message binaire
+-- de `messages_binary`, message déjà purgé) : `message_ref` est alors NULL. Les consommations
+-- rattachées à un message restent supprimées avec lui (`ON DELETE CASCADE`, migration 009).
+-- SQLite ne sait pas modifier une contrainte existante : la table est reconstruite.
+CREATE TABLE consumptions_new
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    consumer    TEXT,
+    topic       TEXT,
+    message_id  TEXT,
+    message     TEXT,
+    timestamp   REAL,
+    message_ref INTEGER REFERENCES messages (id) ON DELETE CASCADE
+);
+
+INSERT INTO consumptions_new (id, consumer, topic, message_id, message, timestamp, message_ref)
+SELECT id, consumer, topic, message_id, message, timestamp, message_ref
+FROM consumptions
+ORDER BY id;
+
+DROP TABLE consumptions;
+ALTER TABLE consumptions_new RENAME TO consumptions;
+
+-- Index supprimés avec l'ancienne table (migrations 002 et 009).
+CREATE INDEX IF NOT EXISTS idx_consumptions_consumer_timestamp ON consumptions (consumer, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_consumptions_topic_timestamp ON consumptions (topic, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_consumptions_message_ref ON consumptions (message_ref);
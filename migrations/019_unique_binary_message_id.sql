@@ -0,0 +1,9 @@
+-- Migration 019: Unique binary message_id
+-- Comme pour `messages` (migration 017), un `message_id` binaire n'est stocké qu'une fois :
+-- la republication d'un producteur idempotent sur `POST /publish/binary` est écartée.
+-- Les doublons existants sont réduits à la publication la plus ancienne.
+DELETE
+FROM messages_binary
+WHERE EXISTS (SELECT 1 FROM messages_binary o WHERE o.message_id = messages_binary.message_id AND o.id < messages_binary.id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_binary_message_id ON messages_binary (message_id);
@@ -0,0 +1,116 @@
+-- Migration PostgreSQL 001: Initial schema
+-- Schéma complet de la fonctionnalité `postgres`, équivalent à l'état final des migrations SQLite 001 à 014.
+-- Les identifiants et séquences sont en `BIGINT` et les horodatages en `DOUBLE PRECISION`,
+-- pour correspondre aux types Rust `i64` et `f64` lus par le broker.
+
+CREATE TABLE IF NOT EXISTS messages
+(
+    id           BIGSERIAL PRIMARY KEY,
+    topic        TEXT,
+    message_id   TEXT,
+    message      TEXT,
+    producer     TEXT,
+    timestamp    DOUBLE PRECISION,
+    -- Numéro de séquence par sujet (migration 004).
+    seq          BIGINT,
+    -- Numéro de séquence par producteur (migration 006).
+    producer_seq BIGINT,
+    -- Clé métier des sujets à clé unique (migration 007).
+    key          TEXT,
+    -- Date d'expiration (migration 012).
+    expires_at   DOUBLE PRECISION
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_message_id ON messages (message_id);
+CREATE INDEX IF NOT EXISTS idx_messages_topic_timestamp ON messages (topic, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_messages_producer_topic ON messages (producer, topic);
+CREATE INDEX IF NOT EXISTS idx_messages_topic_seq ON messages (topic, seq);
+CREATE INDEX IF NOT EXISTS idx_messages_producer_seq ON messages (producer, producer_seq);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_topic_key ON messages (topic, key) WHERE key IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_messages_topic_message_id ON messages (topic, message_id);
+CREATE INDEX IF NOT EXISTS idx_messages_expires_at ON messages (expires_at);
+
+-- Chaque consommation référence la publication consommée ; la suppression d'un message
+-- supprime ses consommations (migration 009).
+CREATE TABLE IF NOT EXISTS consumptions
+(
+    id          BIGSERIAL PRIMARY KEY,
+    consumer    TEXT,
+    topic       TEXT,
+    message_id  TEXT,
+    message     TEXT,
+    timestamp   DOUBLE PRECISION,
+    message_ref BIGINT NOT NULL REFERENCES messages (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_consumptions_consumer_timestamp ON consumptions (consumer, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_consumptions_topic_timestamp ON consumptions (topic, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_consumptions_message_ref ON consumptions (message_ref);
+
+CREATE TABLE IF NOT EXISTS subscriptions
+(
+    sid          TEXT NOT NULL,
+    consumer     TEXT NOT NULL,
+    topic        TEXT NOT NULL,
+    connected_at DOUBLE PRECISION NOT NULL,
+    -- Étiquettes JSON de la connexion (migration 013).
+    tags         TEXT,
+    PRIMARY KEY (sid, topic)
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscriptions_consumer_topic ON subscriptions (consumer, topic);
+CREATE INDEX IF NOT EXISTS idx_subscriptions_consumer ON subscriptions (consumer);
+CREATE INDEX IF NOT EXISTS idx_subscriptions_topic ON subscriptions (topic);
+
+CREATE TABLE IF NOT EXISTS events
+(
+    id         BIGSERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    data       TEXT NOT NULL,
+    timestamp  DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events (timestamp);
+CREATE INDEX IF NOT EXISTS idx_events_type_timestamp ON events (event_type, timestamp);
+
+CREATE TABLE IF NOT EXISTS scheduled_messages
+(
+    id         BIGSERIAL PRIMARY KEY,
+    message_id TEXT NOT NULL UNIQUE,
+    topic      TEXT NOT NULL,
+    payload    TEXT NOT NULL,
+    deliver_at DOUBLE PRECISION NOT NULL,
+    created_at DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_messages_deliver_at ON scheduled_messages (deliver_at);
+
+CREATE TABLE IF NOT EXISTS deliveries
+(
+    consumer   TEXT NOT NULL,
+    topic      TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    state      TEXT NOT NULL CHECK (state IN ('delivered', 'acked')),
+    frame      TEXT,
+    updated_at DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (consumer, topic, message_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_deliveries_updated_at ON deliveries (updated_at);
+
+CREATE TABLE IF NOT EXISTS graph_snapshots
+(
+    id       BIGSERIAL PRIMARY KEY,
+    taken_at DOUBLE PRECISION NOT NULL,
+    kind     TEXT NOT NULL CHECK (kind IN ('full', 'delta')),
+    data     TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_graph_snapshots_kind_taken_at ON graph_snapshots (kind, taken_at);
+
+CREATE TABLE IF NOT EXISTS topic_schemas
+(
+    topic      TEXT PRIMARY KEY,
+    schema     TEXT NOT NULL,
+    updated_at DOUBLE PRECISION NOT NULL
+);
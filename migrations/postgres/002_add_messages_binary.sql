@@ -0,0 +1,13 @@
+-- Migration PostgreSQL 002: Binary message payloads (équivalent de la migration SQLite 015).
+CREATE TABLE IF NOT EXISTS messages_binary
+(
+    id         BIGSERIAL PRIMARY KEY,
+    topic      TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    producer   TEXT NOT NULL,
+    payload    BYTEA NOT NULL,
+    timestamp  DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_binary_topic_timestamp ON messages_binary (topic, timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_messages_binary_timestamp ON messages_binary (timestamp);
@@ -0,0 +1,14 @@
+-- Migration PostgreSQL 003: Dead letters (équivalent de la migration SQLite 016).
+CREATE TABLE IF NOT EXISTS dead_letters
+(
+    id           BIGSERIAL PRIMARY KEY,
+    topic        TEXT NOT NULL,
+    message_id   TEXT NOT NULL,
+    message      TEXT NOT NULL,
+    producer     TEXT NOT NULL,
+    published_at DOUBLE PRECISION NOT NULL,
+    detected_at  DOUBLE PRECISION NOT NULL,
+    UNIQUE (topic, message_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_dead_letters_detected_at ON dead_letters (detected_at);
@@ -0,0 +1,16 @@
+-- Migration PostgreSQL 004: Unique message_id (équivalent de la migration SQLite 017).
+UPDATE consumptions
+SET message_ref = (SELECT MIN(o.id)
+                   FROM messages o
+                            JOIN messages m ON m.message_id = o.message_id
+                   WHERE m.id = consumptions.message_ref)
+WHERE message_ref IN (SELECT m.id
+                      FROM messages m
+                      WHERE EXISTS (SELECT 1 FROM messages o WHERE o.message_id = m.message_id AND o.id < m.id));
+
+DELETE
+FROM messages
+WHERE EXISTS (SELECT 1 FROM messages o WHERE o.message_id = messages.message_id AND o.id < messages.id);
+
+DROP INDEX IF EXISTS idx_messages_message_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_message_id ON messages (message_id);
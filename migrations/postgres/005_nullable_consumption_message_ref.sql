@@ -0,0 +1,2 @@
+-- Migration PostgreSQL 005: Consumptions without a stored message (équivalent de la migration SQLite 018).
+ALTER TABLE consumptions ALTER COLUMN message_ref DROP NOT NULL;
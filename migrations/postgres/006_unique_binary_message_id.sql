@@ -0,0 +1,6 @@
+-- Migration PostgreSQL 006: Unique binary message_id (équivalent de la migration SQLite 019).
+DELETE
+FROM messages_binary
+WHERE EXISTS (SELECT 1 FROM messages_binary o WHERE o.message_id = messages_binary.message_id AND o.id < messages_binary.id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_binary_message_id ON messages_binary (message_id);
@@ -0,0 +1,78 @@
+// Journal d'accès HTTP, branché sur `tower_http::trace::TraceLayer`.
+//
+// Chaque requête ouvre un span `http_request{method, path}` au niveau `ACCESS_LOG_LEVEL` ;
+// la réponse y journalise son statut et sa latence, comme le ferait un reverse proxy.
+// Les chemins de `ACCESS_LOG_EXCLUDE` (et leurs sous-chemins) reçoivent un span vide et ne sont pas journalisés.
+use crate::config::Config;
+use axum::http::{Request, Response, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::trace::{MakeSpan, OnResponse};
+use tracing::{Level, Span};
+
+#[derive(Clone)]
+pub struct AccessLog {
+    config: Arc<Config>,
+}
+
+impl AccessLog {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+
+    fn excluded(&self, path: &str) -> bool {
+        // Les exclusions sont exprimées sans `BASE_PATH`.
+        let path = path
+            .strip_prefix(self.config.base_path.as_str())
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            .unwrap_or(path);
+        self.config.access_log_exclude.iter().any(|prefix| {
+            path == prefix
+                || path
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+impl<B> MakeSpan<B> for AccessLog {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let path = request.uri().path();
+        if !self.config.access_log || self.excluded(path) {
+            return Span::none();
+        }
+        let method = request.method();
+        // Les macros de `tracing` exigent un niveau constant.
+        match self.config.access_log_level {
+            Level::ERROR => tracing::error_span!("http_request", %method, path),
+            Level::WARN => tracing::warn_span!("http_request", %method, path),
+            Level::INFO => tracing::info_span!("http_request", %method, path),
+            Level::DEBUG => tracing::debug_span!("http_request", %method, path),
+            _ => tracing::trace_span!("http_request", %method, path),
+        }
+    }
+}
+
+impl<B> OnResponse<B> for AccessLog {
+    fn on_response(self, response: &Response<B>, latency: Duration, span: &Span) {
+        // Requête exclue (ou niveau filtré par le subscriber).
+        if span.is_disabled() {
+            return;
+        }
+        let status = response.status().as_u16();
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        // Un `101` (upgrade WebSocket) est journalisé à l'ouverture de la connexion, pas à sa fin.
+        let outcome = if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+            "connection upgraded"
+        } else {
+            "request completed"
+        };
+        match self.config.access_log_level {
+            Level::ERROR => tracing::error!(parent: span, status, latency_ms, "{}", outcome),
+            Level::WARN => tracing::warn!(parent: span, status, latency_ms, "{}", outcome),
+            Level::INFO => tracing::info!(parent: span, status, latency_ms, "{}", outcome),
+            Level::DEBUG => tracing::debug!(parent: span, status, latency_ms, "{}", outcome),
+            _ => tracing::trace!(parent: span, status, latency_ms, "{}", outcome),
+        }
+    }
+}
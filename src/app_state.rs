@@ -1,13 +1,40 @@
 // Utilise des modules pour le broker, le cache, et la synchronisation.
 use crate::broker::Broker;
 use crate::cache::QueryCache;
+use crate::config::{Config, EmitStrategy};
+use crate::federation::Federation;
+use crate::groups::ConsumerGroups;
+use crate::metrics::Metrics;
+use crate::models::{
+    DebugState, PublishRequest, StatsSnapshot, TopicChannelInfo, TopicDeliveryStats, TopicRate,
+};
+use crate::rate_limit::ProducerRateLimiter;
+use crate::registry::{ConnectionHandle, ConnectionRegistry};
+use crate::validator::ValidationWebhook;
 use std::{
     collections::HashMap,
-    // `Arc` pour partage thread-safe, `AtomicBool` pour booléen atomique.
-    sync::{atomic::AtomicBool, Arc},
+    // `Arc` pour partage thread-safe, `AtomicBool`/`AtomicU8` pour des valeurs atomiques.
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 // `RwLock` pour accès concurrent (lectures multiples/une écriture), `broadcast` pour diffusion.
+use axum::extract::ws::Message;
 use tokio::sync::{broadcast, RwLock};
+use tracing::{info, warn};
+
+// Compteurs de livraison d'un sujet, partagés par ses tâches de transfert.
+#[derive(Default)]
+pub struct DeliveryCounters {
+    // Messages transmis à une connexion abonnée.
+    pub delivered: AtomicU64,
+    // Messages sautés par un récepteur en retard (`RecvError::Lagged`).
+    pub dropped: AtomicU64,
+    // Émissions Socket.IO en échec (chaque tentative compte).
+    pub emit_failures: AtomicU64,
+}
 
 // `#[derive(Clone)]` permet de dupliquer l'état de l'application.
 #[derive(Clone)]
@@ -19,23 +46,359 @@ pub struct AppState {
     // `RwLock`: Optimise les accès concurrents (plusieurs lecteurs ou un seul rédacteur).
     // `HashMap`: Associe un nom de topic à un canal de diffusion (`broadcast::Sender`).
     pub topic_channels: Arc<RwLock<HashMap<String, broadcast::Sender<String>>>>,
+    // Canaux relais par topic, utilisés au-delà de `TOPIC_SUBSCRIBER_CAP` abonnés sur le canal primaire.
+    pub topic_relays: Arc<RwLock<HashMap<String, Vec<broadcast::Sender<String>>>>>,
     // `Arc<QueryCache>`: Partage thread-safe du cache de requêtes.
     pub cache: Arc<QueryCache>,
     // `Arc<AtomicBool>`: Un booléen thread-safe, plus performant qu'un Mutex pour les cas simples.
     pub dashboard_enabled: Arc<AtomicBool>,
+    // Configuration lue au démarrage.
+    pub config: Arc<Config>,
+    // Stratégie d'émission courante (`EmitStrategy` encodée en `u8`), relue à chaque publication.
+    pub emit_strategy: Arc<AtomicU8>,
+    // Registre des connexions WebSocket actives (sid -> canal interne), pour adresser un client précis.
+    pub connections: Arc<ConnectionRegistry>,
+    // Relais vers les brokers pairs (`FEDERATION_PEERS`), absent hors fédération.
+    pub federation: Option<Arc<Federation>>,
+    // Validateur externe des publications (`VALIDATION_WEBHOOK_URL`), absent s'il n'est pas configuré.
+    pub validator: Option<Arc<ValidationWebhook>>,
+    // Limite de débit par producteur (`PUBLISH_RATE_PER_SEC`), absente si elle est désactivée.
+    pub publish_limiter: Option<Arc<ProducerRateLimiter>>,
+    // Compteurs cumulés exposés par `GET /metrics` (les mêmes que ceux du broker).
+    pub metrics: Arc<Metrics>,
+    // Membres des groupes de consommateurs, par sujet (`SubscribeMessage::group`).
+    pub consumer_groups: Arc<ConsumerGroups>,
+    // Compteurs de livraison par topic. `std::sync::RwLock` : accès courts, sans `await`.
+    pub delivery_counters: Arc<std::sync::RwLock<HashMap<String, Arc<DeliveryCounters>>>>,
 }
 
 impl AppState {
     // `new` est le constructeur pour `AppState`.
-    pub fn new(broker: Arc<Broker>) -> Self {
+    pub fn new(
+        broker: Arc<Broker>,
+        config: Arc<Config>,
+        federation: Option<Arc<Federation>>,
+    ) -> Self {
+        let metrics = broker.metrics();
         Self {
             broker,
             // `with_capacity(100)`: Pré-alloue la mémoire, une optimisation de performance.
             topic_channels: Arc::new(RwLock::new(HashMap::with_capacity(100))),
+            topic_relays: Arc::new(RwLock::new(HashMap::new())),
             // Crée une nouvelle instance du cache.
             cache: Arc::new(QueryCache::new(2)),
             // Initialise le drapeau du dashboard à `false`.
             dashboard_enabled: Arc::new(AtomicBool::new(false)),
+            emit_strategy: Arc::new(AtomicU8::new(config.emit_strategy as u8)),
+            connections: Arc::new(ConnectionRegistry::new()),
+            federation,
+            validator: ValidationWebhook::from_config(&config),
+            metrics,
+            publish_limiter: ProducerRateLimiter::from_config(&config),
+            consumer_groups: Arc::new(ConsumerGroups::default()),
+            delivery_counters: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            config,
+        }
+    }
+
+    // Compteurs de livraison du topic, créés à la première demande.
+    pub fn delivery_counters(&self, topic: &str) -> Arc<DeliveryCounters> {
+        if let Some(counters) = self.delivery_counters.read().unwrap().get(topic) {
+            return counters.clone();
+        }
+        self.delivery_counters
+            .write()
+            .unwrap()
+            .entry(topic.to_string())
+            .or_default()
+            .clone()
+    }
+
+    // Livraisons du topic depuis le démarrage ; `None` si le sujet n'a jamais été publié ni suivi.
+    pub fn delivery_stats(&self, topic: &str) -> Option<TopicDeliveryStats> {
+        let head_seq = self.broker.topic_sequences().get(topic).copied();
+        let counters = self.delivery_counters.read().unwrap().get(topic).cloned();
+        if head_seq.is_none() && counters.is_none() {
+            return None;
+        }
+        let (delivered, dropped) = counters.map_or((0, 0), |c| {
+            (
+                c.delivered.load(Ordering::Relaxed),
+                c.dropped.load(Ordering::Relaxed),
+            )
+        });
+        Some(TopicDeliveryStats {
+            topic: topic.to_string(),
+            head_seq: head_seq.unwrap_or(0),
+            delivered,
+            dropped,
+        })
+    }
+
+    // Abonne un récepteur au canal de diffusion du topic, en le créant si besoin.
+    // Si `TOPIC_SUBSCRIBER_CAP` est défini et que le canal primaire l'a atteint, le récepteur est
+    // rattaché à un canal relais (second étage de fan-out) : chaque envoi sur le primaire ne clone
+    // alors le message que vers ses propres récepteurs, dont un seul par relais.
+    // Contrepartie : un saut de plus (tâche relais) pour les abonnés rattachés à un relais.
+    pub async fn subscribe_topic(&self, topic: &str) -> broadcast::Receiver<String> {
+        let primary = {
+            let mut channels = self.topic_channels.write().await;
+            channels
+                .entry(topic.to_string())
+                .or_insert_with(|| broadcast::channel(self.config.topic_channel_capacity).0)
+                .clone()
+        };
+
+        let cap = self.config.topic_subscriber_cap;
+        if cap == 0 || primary.receiver_count() < cap {
+            return primary.subscribe();
+        }
+
+        let mut relays = self.topic_relays.write().await;
+        let tier = relays.entry(topic.to_string()).or_default();
+        if let Some(relay) = tier.iter().find(|relay| relay.receiver_count() < cap) {
+            return relay.subscribe();
+        }
+
+        // Tous les relais existants sont pleins : on en crée un nouveau, alimenté par le primaire.
+        let (relay_tx, relay_rx) = broadcast::channel(self.config.topic_channel_capacity);
+        let mut upstream = primary.subscribe();
+        let forward_tx = relay_tx.clone();
+        let topic_name = topic.to_string();
+        let counters = self.delivery_counters(topic);
+        tokio::spawn(async move {
+            loop {
+                match upstream.recv().await {
+                    // Aucun récepteur sur le relais n'est pas une erreur : d'autres peuvent s'y rattacher.
+                    Ok(msg) => {
+                        let _ = forward_tx.send(msg);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Relay for topic {} lagged by {} messages", topic_name, n);
+                        counters.dropped.fetch_add(n, Ordering::Relaxed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        tier.push(relay_tx);
+        info!(
+            "Topic {} reached {} subscribers, relay #{} created",
+            topic,
+            cap,
+            tier.len()
+        );
+        relay_rx
+    }
+
+    // Livre une publication à un membre de chaque groupe de consommateurs du sujet, à tour de rôle ;
+    // un membre injoignable (connexion fermée entre-temps) cède sa place au suivant. Un membre Socket.IO
+    // n'est servi que dans le namespace de la publication ; un membre `/ws`, sans namespace, est servi
+    // comme ses abonnements par sujet.
+    pub async fn deliver_to_groups(&self, payload: &PublishRequest) {
+        let rotations = self.consumer_groups.rotation(&payload.topic);
+        if rotations.is_empty() {
+            return;
+        }
+        let Ok(frame) = serde_json::to_string(payload) else {
+            return;
+        };
+        let counters = self.delivery_counters(&payload.topic);
+        let namespace = payload.namespace.as_deref().unwrap_or("/");
+        for members in rotations {
+            for sid in members {
+                let delivered = match self.connections.handle(&sid).await {
+                    Some(ConnectionHandle::WebSocket(tx, _)) => {
+                        tx.send(Message::Text(frame.clone().into())).is_ok()
+                    }
+                    Some(ConnectionHandle::SocketIo(socket)) => {
+                        socket.ns() == namespace && socket.emit("message", payload).is_ok()
+                    }
+                    None => false,
+                };
+                if delivered {
+                    counters.delivered.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Désabonne la connexion `sid` d'un sujet sans la fermer : abonnement retiré du broker, puis
+    // livraison arrêtée sur son transport. Retourne `false` si elle n'y était pas abonnée.
+    pub async fn unsubscribe(&self, sid: &str, topic: &str) -> bool {
+        if !self.broker.unregister_subscription(sid, topic).await {
+            return false;
+        }
+        self.consumer_groups.leave(sid, topic);
+        if let Some(handle) = self.connections.handle(sid).await {
+            handle.leave(topic).await;
+        }
+        info!("SID {} unsubscribed from topic {}", sid, topic);
+        true
+    }
+
+    // Nettoyage des sujets morts (`TOPIC_CLEANUP`), déclenché après chaque purge :
+    // un sujet sans message restant ni abonné perd son canal de diffusion, ses relais et sa numérotation.
+    pub fn spawn_topic_cleanup(&self) {
+        let state = self.clone();
+        tokio::spawn(async move {
+            loop {
+                state.broker.purge_completed().await;
+                state.cleanup_dead_topics().await;
+            }
+        });
+    }
+
+    // Balayage de vivacité (`CLIENT_STALE_SECS`) : signale `stale` les clients WebSocket silencieux et
+    // retire du cache les abonnements dont la connexion n'est plus enregistrée. La déconnexion des
+    // clients silencieux (`CLIENT_EXPIRY_SECS`) est faite par leur propre boucle de réception.
+    pub fn spawn_liveness_sweeper(&self) {
+        let state = self.clone();
+        let every = Duration::from_secs((self.config.client_stale_secs / 4).clamp(1, 30));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            loop {
+                interval.tick().await;
+                state.sweep_liveness(every).await;
+            }
+        });
+    }
+
+    async fn sweep_liveness(&self, every: Duration) {
+        let stale = self
+            .connections
+            .idle_websockets(self.config.client_stale_secs * 1000)
+            .await;
+        self.broker.mark_stale(stale).await;
+
+        // Laisse une période de balayage aux connexions Socket.IO, enregistrées de façon asynchrone.
+        let live = self.connections.sids().await;
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64()
+            - every.as_secs_f64();
+        for sid in self.broker.orphaned_subscriptions(&live, before).await {
+            warn!("Removing subscriptions of vanished connection {}", sid);
+            self.broker.unregister_client(&sid).await;
+            self.consumer_groups.leave_all(&sid);
+        }
+    }
+
+    async fn cleanup_dead_topics(&self) {
+        let candidates: Vec<String> = self.topic_channels.read().await.keys().cloned().collect();
+        let dead = self.broker.dead_topics(candidates).await;
+        if dead.is_empty() {
+            return;
+        }
+
+        // Les verrous en écriture bloquent `subscribe_topic` pendant la vérification finale.
+        let mut channels = self.topic_channels.write().await;
+        let mut relays = self.topic_relays.write().await;
+        let mut removed = 0;
+        for (topic, observed_seq) in dead {
+            // Un abonné a pu arriver depuis la requête : on garde le sujet s'il a un récepteur
+            // (hors récepteurs internes des relais, un par relais).
+            let tier = relays.get(&topic);
+            let relay_count = tier.map_or(0, Vec::len);
+            let has_receivers = channels
+                .get(&topic)
+                .is_some_and(|tx| tx.receiver_count() > relay_count)
+                || tier.is_some_and(|tier| tier.iter().any(|relay| relay.receiver_count() > 0));
+            if has_receivers {
+                continue;
+            }
+            // Fermer le canal primaire arrête aussi les tâches relais qui l'écoutent.
+            channels.remove(&topic);
+            relays.remove(&topic);
+            self.delivery_counters.write().unwrap().remove(&topic);
+            self.broker.forget_topic(&topic, observed_seq);
+            removed += 1;
+        }
+        if removed > 0 {
+            info!("Topic cleanup: {} dead topic(s) removed", removed);
+        }
+    }
+
+    // Instantané de l'état en mémoire pour `GET /admin/debug/state`.
+    pub async fn debug_state(&self) -> DebugState {
+        let subscriptions = self.broker.subscriptions_snapshot().await;
+        let mut topic_channels: Vec<TopicChannelInfo> = {
+            let channels = self.topic_channels.read().await;
+            let relays = self.topic_relays.read().await;
+            channels
+                .iter()
+                .map(|(topic, tx)| TopicChannelInfo {
+                    topic: topic.clone(),
+                    receivers: tx.receiver_count(),
+                    relays: relays.get(topic).map_or(0, Vec::len),
+                })
+                .collect()
+        };
+        topic_channels.sort_by(|a, b| a.topic.cmp(&b.topic));
+
+        DebugState {
+            subscriptions,
+            topic_channels,
+            dashboard_enabled: self.dashboard_enabled.load(Ordering::Relaxed),
+        }
+    }
+
+    // Statistiques agrégées ; les débits sont calculés par rapport aux `seq` de l'instantané précédent.
+    pub async fn stats_snapshot(
+        &self,
+        seqs: &HashMap<String, i64>,
+        previous: &HashMap<String, i64>,
+        elapsed_secs: f64,
+    ) -> StatsSnapshot {
+        let mut topics: Vec<TopicRate> = seqs
+            .iter()
+            .filter_map(|(topic, &seq)| {
+                let published = seq - previous.get(topic).copied().unwrap_or(0);
+                (published > 0).then(|| TopicRate {
+                    topic: topic.clone(),
+                    seq,
+                    rate: published as f64 / elapsed_secs.max(f64::EPSILON),
+                })
+            })
+            .collect();
+        topics.sort_by(|a, b| b.rate.total_cmp(&a.rate));
+
+        StatsSnapshot {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            connections: self.connections.count().await,
+            subscribers: self.broker.subscriber_count().await,
+            db_queue_depth: self.broker.db_queue_depth(),
+            scheduled: self.broker.scheduled_count(),
+            topics,
         }
     }
+
+    // Trame de bienvenue envoyée à chaque nouvelle connexion : capacités et limites effectives
+    // du serveur, pour que les clients s'y adaptent sans configuration externe.
+    pub fn welcome_payload(&self, supported_events: &[&str]) -> serde_json::Value {
+        serde_json::json!({
+            "server_version": env!("CARGO_PKG_VERSION"),
+            "max_message_bytes": self.config.max_message_bytes,
+            "supported_events": supported_events,
+            "server_time": std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+        })
+    }
+
+    // Lit la stratégie d'émission courante.
+    pub fn emit_strategy(&self) -> EmitStrategy {
+        EmitStrategy::from_u8(self.emit_strategy.load(Ordering::Relaxed))
+    }
+
+    // Change la stratégie d'émission à chaud ; prise en compte dès la publication suivante.
+    pub fn set_emit_strategy(&self, strategy: EmitStrategy) {
+        self.emit_strategy.store(strategy as u8, Ordering::Relaxed);
+    }
 }
@@ -0,0 +1,145 @@
+// Archivage à froid des messages purgés (`ARCHIVE_SINK`).
+//
+// Au lieu d'être définitivement supprimés, les messages évincés par la purge périodique sont d'abord
+// écrits dans une destination secondaire ; la suppression n'est validée qu'une fois l'archive écrite
+// (voir `Broker::purge_old_data`). Destinations prises en charge :
+// - `sqlite:<fichier>` : table `archived_messages` d'une base SQLite séparée ;
+// - `ndjson:<fichier>` : un objet JSON par ligne, ajouté en fin de fichier ; compressé en gzip si le
+//   nom se termine par `.gz` (un membre gzip par purge, lisible tel quel par `zcat`).
+use serde::Serialize;
+use sqlx::sqlite::SqlitePool;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Message retiré de la base par la purge, tel qu'archivé.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ArchivedMessage {
+    pub topic: String,
+    pub message_id: String,
+    // Contenu JSON sérialisé, tel que stocké.
+    pub message: String,
+    pub producer: String,
+    pub timestamp: f64,
+    pub seq: Option<i64>,
+    pub producer_seq: Option<i64>,
+    pub key: Option<String>,
+}
+
+pub enum Archive {
+    Sqlite(SqlitePool),
+    Ndjson { path: PathBuf, gzip: bool },
+}
+
+impl Archive {
+    // Ouvre la destination configurée, ou retourne `None` si l'archivage est désactivé.
+    // Une destination invalide ou inaccessible empêche le démarrage : purger sans archiver
+    // perdrait précisément les données que l'archivage doit conserver.
+    pub async fn open(sink: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(sink) = sink else {
+            return Ok(None);
+        };
+        match sink.split_once(':') {
+            Some(("sqlite", file)) => {
+                let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", file))
+                    .await
+                    .map_err(|e| format!("archive {} inaccessible: {}", sink, e))?;
+                sqlx::query(
+                    "CREATE TABLE IF NOT EXISTS archived_messages (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        topic TEXT NOT NULL,
+                        message_id TEXT NOT NULL,
+                        message TEXT NOT NULL,
+                        producer TEXT NOT NULL,
+                        timestamp REAL NOT NULL,
+                        seq INTEGER,
+                        producer_seq INTEGER,
+                        key TEXT,
+                        archived_at REAL NOT NULL
+                    )",
+                )
+                .execute(&pool)
+                .await
+                .map_err(|e| format!("archive {} inaccessible: {}", sink, e))?;
+                Ok(Some(Self::Sqlite(pool)))
+            }
+            Some(("ndjson", file)) => {
+                let path = PathBuf::from(file);
+                // Vérifie dès le démarrage que le fichier est accessible en écriture.
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .map_err(|e| format!("archive {} inaccessible: {}", sink, e))?;
+                let gzip = file.ends_with(".gz");
+                Ok(Some(Self::Ndjson { path, gzip }))
+            }
+            _ => Err(format!(
+                "ARCHIVE_SINK invalide: {} (attendu sqlite:<fichier> ou ndjson:<fichier>)",
+                sink
+            )),
+        }
+    }
+
+    // Écrit les messages dans l'archive. Ne retourne `Ok` qu'une fois l'écriture durable.
+    pub async fn write(&self, messages: &[ArchivedMessage]) -> Result<(), String> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Self::Sqlite(pool) => write_sqlite(pool, messages)
+                .await
+                .map_err(|e| e.to_string()),
+            Self::Ndjson { path, gzip } => {
+                let mut buffer = Vec::new();
+                for message in messages {
+                    serde_json::to_writer(&mut buffer, message).map_err(|e| e.to_string())?;
+                    buffer.push(b'\n');
+                }
+                let (path, gzip) = (path.clone(), *gzip);
+                tokio::task::spawn_blocking(move || append_file(&path, &buffer, gzip))
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+async fn write_sqlite(pool: &SqlitePool, messages: &[ArchivedMessage]) -> Result<(), sqlx::Error> {
+    let archived_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+    let mut tx = pool.begin().await?;
+    for message in messages {
+        sqlx::query("INSERT INTO archived_messages (topic, message_id, message, producer, timestamp, seq, producer_seq, key, archived_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .bind(&message.topic)
+            .bind(&message.message_id)
+            .bind(&message.message)
+            .bind(&message.producer)
+            .bind(message.timestamp)
+            .bind(message.seq)
+            .bind(message.producer_seq)
+            .bind(&message.key)
+            .bind(archived_at)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await
+}
+
+// Ajoute `lines` en fin de fichier (éventuellement comme nouveau membre gzip) puis synchronise le disque.
+fn append_file(path: &Path, lines: &[u8], gzip: bool) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if gzip {
+        let mut encoder = flate2::write::GzEncoder::new(&mut file, flate2::Compression::default());
+        encoder.write_all(lines)?;
+        encoder.finish()?;
+    } else {
+        file.write_all(lines)?;
+    }
+    file.sync_all()
+}
@@ -0,0 +1,65 @@
+// Outils d'authentification partagés par les routes protégées (administration, etc.).
+use crate::app_state::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use socketioxide::SocketIo;
+use tracing::warn;
+
+// Compare deux secrets en temps constant (pour une longueur donnée).
+// Contrairement à `==`, la durée ne dépend pas de la position du premier octet différent,
+// ce qui empêche de deviner un jeton octet par octet en mesurant les temps de réponse.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Extrait le jeton d'un en-tête `Authorization: Bearer <token>`.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+// Middleware Axum protégeant les routes d'administration.
+// Si `ADMIN_TOKEN` est défini, la requête doit porter le même jeton en `Bearer`, sinon `401`.
+pub async fn require_admin(
+    State((state, _)): State<(AppState, SocketIo)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if let Some(expected) = state.config.admin_token.as_deref() {
+        let authorized = bearer_token(req.headers())
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()));
+        if !authorized {
+            warn!("Accès admin refusé: {} {}", req.method(), req.uri().path());
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    next.run(req).await
+}
+
+// Middleware Axum protégeant `/publish`.
+// Si `PUBLISH_API_KEY` est défini, la requête doit porter la même clé en `Bearer`, sinon `401`.
+pub async fn require_publish_key(
+    State((state, _)): State<(AppState, SocketIo)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if let Some(expected) = state.config.publish_api_key.as_deref() {
+        let authorized = bearer_token(req.headers())
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()));
+        if !authorized {
+            warn!("Publication refusée: clé d'API absente ou invalide");
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    next.run(req).await
+}
@@ -1,13 +1,36 @@
 // Importations de modèles et de bibliothèques nécessaires.
-use crate::models::{BroadcastEvent, ClientInfo, ConsumptionInfo, GraphState, Link, MessageInfo};
+use crate::archive::{Archive, ArchivedMessage};
+use crate::config::{
+    Config, ConsumptionRetention, MessageRetention, UniqueKeyPolicy, WildcardOrdering,
+};
+use crate::database::{Backend, Db, DbPool};
+use crate::dedup::{RecentMessageIds, DEDUP_WINDOW};
+use crate::metrics::Metrics;
+use crate::models::{
+    BinaryPublishQuery, BroadcastEvent, ClientInfo, ConsumedAck, ConsumptionInfo, DeadLetterInfo,
+    EventInfo, ExportTable, GraphState, ImportRecord, Link, MessageInfo, PublishRequest,
+    SubscriptionInfo, SubscriptionSnapshot, TopicBacklog, TopicStat,
+};
+use crate::pipeline::Pipeline;
+use crate::topic_pattern::{is_topic_pattern, topic_matches};
+use crate::wildcard::WildcardItem;
+use futures_util::StreamExt;
 // Pour l'interaction avec la base de données SQLite.
-use sqlx::sqlite::SqlitePool;
+use sqlx::FromRow;
 // Structures de données standard, partage thread-safe, et temps système.
-use std::{collections::HashMap, sync::Arc, time::SystemTime};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Instant, SystemTime},
+};
 // Outils de synchronisation asynchrone de Tokio.
-use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, Notify, RwLock};
 // Pour la journalisation des erreurs et des avertissements.
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 // Énumération représentant les commandes à envoyer au worker de base de données.
 // Ceci permet de centraliser les opérations DB et de les traiter de manière asynchrone.
@@ -22,6 +45,8 @@ pub enum DbCommand {
         topic: String,
         // Timestamp de la connexion.
         connected_at: f64,
+        // Étiquettes de la connexion (JSON), absentes si elle n'en déclare pas.
+        tags: Option<String>,
     },
     // Sauvegarde un message publié sur un sujet.
     SaveMessage {
@@ -33,6 +58,21 @@ pub enum DbCommand {
         // Nom du producteur.
         producer: String,
         timestamp: f64,
+        // Numéro de séquence du message au sein de son sujet.
+        seq: i64,
+        // Numéro de séquence du message pour son producteur (`PRODUCER_SEQUENCES`).
+        producer_seq: Option<i64>,
+        // Date d'expiration (`ttl_seconds`), `None` si le message n'expire pas.
+        expires_at: Option<f64>,
+    },
+    // Sauvegarde une publication binaire (`POST /publish/binary`).
+    SaveBinaryMessage {
+        topic: String,
+        message_id: String,
+        producer: String,
+        // Contenu brut, stocké tel quel.
+        payload: Vec<u8>,
+        timestamp: f64,
     },
     // Sauvegarde la confirmation de consommation d'un message.
     SaveConsumption {
@@ -42,10 +82,73 @@ pub enum DbCommand {
         message: String,
         timestamp: f64,
     },
+    // Supprime un seul abonnement d'une connexion toujours ouverte.
+    UnregisterSubscription {
+        sid: String,
+        topic: String,
+    },
     // Supprime un client lors de sa déconnexion.
     UnregisterClient {
         sid: String,
     },
+    // Enregistre la livraison QoS 1 d'un message à un consommateur (`PERSIST_DELIVERY_ACKS`).
+    RecordDelivery {
+        consumer: String,
+        topic: String,
+        message_id: String,
+        // Trame livrée, renvoyée telle quelle après une reconnexion.
+        frame: String,
+        timestamp: f64,
+    },
+    // Marque une livraison comme acquittée par le consommateur.
+    AckDelivery {
+        consumer: String,
+        topic: String,
+        message_id: String,
+        timestamp: f64,
+    },
+    // Enregistre un événement du broker dans le journal d'audit.
+    SaveEvent {
+        event_type: String,
+        // Données de l'événement (JSON sérialisé).
+        data: String,
+        timestamp: f64,
+    },
+    // Barrière d'écriture : le worker valide le batch courant (donc toutes les commandes envoyées
+    // avant elle) puis signale `done`, à `false` si un batch a échoué depuis la barrière précédente.
+    // Jamais ajoutée au batch.
+    Flush {
+        done: oneshot::Sender<bool>,
+    },
+}
+
+// Numéros de séquence attribués à une publication.
+pub struct AssignedSeq {
+    // Position du message dans son sujet.
+    pub seq: i64,
+    // Position du message parmi les publications de son producteur (`PRODUCER_SEQUENCES`).
+    pub producer_seq: Option<i64>,
+}
+
+// Issue de l'enregistrement d'une publication (`Broker::save_message`).
+pub enum Saved {
+    Accepted(AssignedSeq),
+    // Un message portant ce `message_id` a déjà été publié : ni stocké ni diffusé.
+    Duplicate,
+}
+
+// Publication refusée : la `key` existe déjà sur ce sujet à clé unique (`UNIQUE_KEY_TOPICS`).
+#[derive(Debug)]
+pub struct DuplicateKey;
+
+// Issue de la mise en attente d'une livraison différée (`Broker::schedule_message`).
+#[derive(Debug, PartialEq, Eq)]
+pub enum Scheduled {
+    Accepted,
+    // Un message différé porte déjà ce `message_id`.
+    Duplicate,
+    // `MAX_SCHEDULED_MESSAGES` livraisons sont déjà en attente.
+    Full,
 }
 
 // Configuration for automatic data purging
@@ -53,15 +156,21 @@ pub enum DbCommand {
 const MAX_MESSAGES: i64 = 10_000;
 // Nombre maximum de consommations à conserver.
 const MAX_CONSUMPTIONS: i64 = 10_000;
+// Nombre de lignes retournées par défaut par `get_messages` et `get_consumptions`.
+const DEFAULT_PAGE_SIZE: i64 = 100;
 // Âge maximum des données en heures.
 const MAX_AGE_HOURS: f64 = 24.0;
+// Attente maximale de la dernière écriture en base à l'arrêt du serveur.
+const SHUTDOWN_FLUSH_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(5);
 // Intervalle en minutes entre chaque purge.
 const PURGE_INTERVAL_MINUTES: u64 = 30;
+// Nouvelles tentatives d'une lecture bloquée par un verrou avant de remonter l'erreur.
+const READ_RETRIES: u64 = 3;
 
 // Le `Broker` est le cœur de l'application, gérant l'état, les messages et les clients.
 pub struct Broker {
     // Pool de connexions à la base de données pour les lectures.
-    db: SqlitePool,
+    db: DbPool,
     // Canal pour diffuser des événements à l'échelle de l'application (ex: dashboard).
     pub event_tx: broadcast::Sender<Arc<BroadcastEvent>>,
     // Cache en mémoire des abonnements: sid -> (consommateur, sujets, timestamp).
@@ -72,13 +181,70 @@ pub struct Broker {
     subscriptions: Arc<RwLock<HashMap<String, (String, Vec<String>, f64)>>>,
     // Canal pour envoyer des commandes d'écriture à la base de données.
     db_tx: mpsc::UnboundedSender<DbCommand>,
+    // Configuration partagée de l'application.
+    config: Arc<Config>,
+    // Dernier numéro de séquence attribué par sujet.
+    // `std::sync::Mutex` suffit : la section critique est courte et ne contient aucun `await`.
+    topic_seqs: std::sync::Mutex<HashMap<String, i64>>,
+    // Dernier `producer_seq` attribué par producteur (`PRODUCER_SEQUENCES`).
+    // Le verrou en écriture n'est pris qu'à la première publication d'un producteur ; ensuite,
+    // l'incrément est atomique sous simple verrou en lecture.
+    producer_seqs: std::sync::RwLock<HashMap<String, Arc<AtomicU64>>>,
+    // File vers le forwarder wildcard ordonné (présente seulement si `WILDCARD_ORDERING=per_topic`).
+    wildcard_tx: Option<mpsc::UnboundedSender<WildcardItem>>,
+    // Signalé à la fin de chaque purge, pour déclencher le nettoyage des sujets morts (`TOPIC_CLEANUP`).
+    purge_done: Arc<Notify>,
+    // Processeurs appliqués aux publications avant stockage et émission (`PIPELINE_CONFIG`).
+    pipeline: Pipeline,
+    // Nombre de messages vus par le tap, pour l'échantillonnage (`TAP_SAMPLE_EVERY`).
+    tap_counter: AtomicU64,
+    // Sérialise les écritures des sujets à clé unique (`UNIQUE_KEY_TOPICS`).
+    unique_keys: tokio::sync::Mutex<()>,
+    // Profondeur de la file d'écriture DB, mise à jour par le worker.
+    db_queue_depth: Arc<AtomicUsize>,
+    // Signalé à chaque nouvelle livraison différée, pour réveiller le planificateur.
+    scheduled_changed: Notify,
+    // Livraisons différées en attente, pour `MAX_SCHEDULED_MESSAGES` et les statistiques.
+    scheduled_count: AtomicUsize,
+    // Connexions silencieuses depuis `CLIENT_STALE_SECS`, tenues à jour par le balayage de vivacité.
+    stale_sids: std::sync::RwLock<HashSet<String>>,
+    // Étiquettes des connexions qui en ont déclaré (sid -> étiquettes).
+    client_tags: std::sync::RwLock<HashMap<String, BTreeMap<String, String>>>,
+    // Compteurs cumulés (`GET /metrics`), partagés avec `AppState`.
+    metrics: Arc<Metrics>,
+    // Dernière publication reçue (ms depuis l'epoch), tous chemins confondus (JSON, binaire,
+    // différée, import), pour l'arrêt sur inactivité (`AUTO_SHUTDOWN_IDLE_SECS`).
+    last_publish: AtomicU64,
+    // Motifs hiérarchiques abonnés (`orders.*`, `orders.#`) -> nombre de connexions abonnées,
+    // consultés à chaque publication pour trouver les motifs qui la reçoivent.
+    pattern_refs: std::sync::RwLock<HashMap<String, usize>>,
+    // Schémas JSON compilés par sujet (table `topic_schemas`), vérifiés à chaque publication.
+    topic_schemas: std::sync::RwLock<HashMap<String, Arc<jsonschema::Validator>>>,
+    // Dernier message retenu (`retain`) par sujet, remis à chaque nouvel abonné.
+    retained: Arc<RwLock<HashMap<String, MessageInfo>>>,
+    // `message_id` récemment publiés, pour écarter les republications d'un producteur.
+    recent_message_ids: RecentMessageIds,
 }
 
 impl Broker {
     // Constructeur pour le `Broker`.
-    pub fn new(db: SqlitePool, event_tx: broadcast::Sender<Arc<BroadcastEvent>>) -> Self {
+    pub fn new(
+        db: DbPool,
+        event_tx: broadcast::Sender<Arc<BroadcastEvent>>,
+        config: Arc<Config>,
+        wildcard_tx: Option<mpsc::UnboundedSender<WildcardItem>>,
+        pipeline: Pipeline,
+        archive: Option<Archive>,
+    ) -> Self {
         let (db_tx, mut db_rx) = mpsc::unbounded_channel::<DbCommand>();
         let db_clone = db.clone();
+        // Horodatage (ms) de la dernière commande d'écriture reçue, utilisé par le worker de vacuum
+        // pour ne travailler que pendant les périodes calmes.
+        let last_write_ms = Arc::new(AtomicU64::new(0));
+        let last_write_worker = last_write_ms.clone();
+        // Commandes en attente d'écriture (file + batch courant), exposées dans `stats_snapshot`.
+        let db_queue_depth = Arc::new(AtomicUsize::new(0));
+        let queue_depth_worker = db_queue_depth.clone();
 
         // Worker dédié pour les écritures DB en batch
         // `tokio::spawn` exécute cette tâche en arrière-plan, sans bloquer le reste de l'application.
@@ -88,33 +254,75 @@ impl Broker {
             let mut batch = Vec::with_capacity(500);
             // Intervalle de temps pour vider le batch.
             let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(20));
+            // Échec d'un batch depuis la dernière barrière, rapporté à celle-ci.
+            let mut failed_since_barrier = false;
 
             loop {
                 // `tokio::select!` attend sur plusieurs futurs en même temps.
                 tokio::select! {
                     // Si l'intervalle se déclenche, on vide le batch.
                     _ = interval.tick() => {
-                        if !batch.is_empty() {
-                            Self::flush_batch(&db_clone, &mut batch).await;
+                        if !batch.is_empty() && !Self::flush_batch(&db_clone, &mut batch).await {
+                            failed_since_barrier = true;
                         }
                     }
                     // Si une nouvelle commande arrive, on l'ajoute au batch.
                     Some(cmd) = db_rx.recv() => {
-                        batch.push(cmd);
-                        // Si le batch atteint sa capacité maximale, on le vide immédiatement.
-                        if batch.len() >= 500 {
-                            Self::flush_batch(&db_clone, &mut batch).await;
+                        if let DbCommand::Flush { done } = cmd {
+                            // Le canal est FIFO : le batch contient déjà tout ce qui précède la barrière.
+                            let committed = Self::flush_batch(&db_clone, &mut batch).await;
+                            let _ = done.send(committed && !failed_since_barrier);
+                            failed_since_barrier = false;
+                        } else {
+                            last_write_worker.store(current_millis(), Ordering::Relaxed);
+                            batch.push(cmd);
+                            // Si le batch atteint sa capacité maximale, on le vide immédiatement.
+                            if batch.len() >= 500 && !Self::flush_batch(&db_clone, &mut batch).await {
+                                failed_since_barrier = true;
+                            }
                         }
                     }
                     // Si le canal est fermé, on sort de la boucle.
                     else => break,
                 }
+                queue_depth_worker.store(db_rx.len() + batch.len(), Ordering::Relaxed);
             }
         });
 
+        // Worker d'audit : recopie chaque `BroadcastEvent` dans la table `events` via le worker DB,
+        // ce qui évite de toucher aux nombreux points d'émission (`event_tx.send`).
+        if config.persist_events {
+            let mut audit_rx = event_tx.subscribe();
+            let audit_db_tx = db_tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    match audit_rx.recv().await {
+                        Ok(event) => {
+                            let cmd = DbCommand::SaveEvent {
+                                event_type: event.event_type.clone(),
+                                data: event.data.to_string(),
+                                timestamp: current_timestamp(),
+                            };
+                            if audit_db_tx.send(cmd).is_err() {
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(n)) => {
+                            // Le journal d'audit est incomplet : on le signale clairement.
+                            warn!("Audit log lagged, {} events not persisted", n);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
+
         // Worker dédié pour la purge automatique des données
         // Une autre tâche de fond dédiée à la maintenance de la base de données.
         let purge_db = db.clone();
+        let purge_config = config.clone();
+        let purge_done = Arc::new(Notify::new());
+        let purge_notify = purge_done.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
                 PURGE_INTERVAL_MINUTES * 60,
@@ -127,23 +335,533 @@ impl Broker {
             loop {
                 // Attend le prochain intervalle.
                 interval.tick().await;
-                Self::purge_old_data(&purge_db).await;
+                Self::purge_old_data(&purge_db, &purge_config, archive.as_ref()).await;
+                // `notify_one` conserve le signal si personne n'attend encore.
+                purge_notify.notify_one();
             }
         });
 
+        // Balayage des lettres mortes : messages toujours sans consommation après `DEAD_LETTER_GRACE_SECS`.
+        if config.dead_letter_grace_secs > 0 {
+            let dead_letter_db = db.clone();
+            let dead_letter_tx = event_tx.clone();
+            let grace = config.dead_letter_grace_secs as f64;
+            let scan_interval = config.dead_letter_scan_interval_secs;
+            tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(tokio::time::Duration::from_secs(scan_interval));
+                loop {
+                    interval.tick().await;
+                    Self::scan_dead_letters(&dead_letter_db, &dead_letter_tx, grace).await;
+                }
+            });
+        }
+
+        // Worker de compactation : récupère l'espace libéré par les purges avec des
+        // `PRAGMA incremental_vacuum(N)` successifs et courts, au lieu d'un `VACUUM` complet bloquant.
+        // Sans objet sous PostgreSQL, dont l'autovacuum récupère l'espace.
+        if config.vacuum_interval_secs > 0 && <Backend as Db>::INCREMENTAL_VACUUM {
+            let vacuum_db = db.clone();
+            let vacuum_config = config.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+                    vacuum_config.vacuum_interval_secs,
+                ));
+                // Pas de compactation au démarrage.
+                interval.tick().await;
+
+                loop {
+                    interval.tick().await;
+                    if !Self::incremental_vacuum(&vacuum_db, &vacuum_config, &last_write_ms).await {
+                        // `auto_vacuum` n'est pas en mode INCREMENTAL : inutile de continuer.
+                        break;
+                    }
+                }
+            });
+        }
+
         Self {
             db,
             event_tx,
             subscriptions: Arc::new(RwLock::new(HashMap::with_capacity(1000))),
             db_tx,
+            config,
+            topic_seqs: std::sync::Mutex::new(HashMap::new()),
+            producer_seqs: std::sync::RwLock::new(HashMap::new()),
+            wildcard_tx,
+            purge_done,
+            pipeline,
+            tap_counter: AtomicU64::new(0),
+            unique_keys: tokio::sync::Mutex::new(()),
+            db_queue_depth,
+            scheduled_changed: Notify::new(),
+            scheduled_count: AtomicUsize::new(0),
+            stale_sids: std::sync::RwLock::new(HashSet::new()),
+            client_tags: std::sync::RwLock::new(HashMap::new()),
+            metrics: Arc::new(Metrics::default()),
+            last_publish: AtomicU64::new(current_millis()),
+            pattern_refs: std::sync::RwLock::new(HashMap::new()),
+            topic_schemas: std::sync::RwLock::new(HashMap::new()),
+            retained: Arc::new(RwLock::new(HashMap::new())),
+            recent_message_ids: RecentMessageIds::default(),
+        }
+    }
+
+    // Au démarrage, aucune connexion n'existe : les abonnements persistés viennent de sessions
+    // précédentes. Ils sont supprimés pour ne pas polluer le graphe (`PURGE_STALE_SUBSCRIPTIONS`).
+    pub async fn purge_stale_subscriptions(&self) {
+        match sqlx::query("DELETE FROM subscriptions")
+            .execute(&self.db)
+            .await
+        {
+            Ok(result) if result.rows_affected() > 0 => info!(
+                "{} abonnement(s) obsolète(s) d'une exécution précédente supprimé(s)",
+                result.rows_affected()
+            ),
+            Ok(_) => {}
+            Err(e) => error!("Erreur lors de la purge des abonnements obsolètes: {}", e),
+        }
+    }
+
+    // Recharge le nombre de livraisons différées en attente, persistées par une exécution précédente.
+    pub async fn load_scheduled_count(&self) {
+        match sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM scheduled_messages")
+            .fetch_one(&self.db)
+            .await
+        {
+            Ok((count,)) => self
+                .scheduled_count
+                .store(count.max(0) as usize, Ordering::Relaxed),
+            Err(e) => error!("Erreur lors du comptage des messages différés: {}", e),
+        }
+    }
+
+    // Charge les schémas JSON des sujets ; un schéma devenu invalide est ignoré avec une erreur.
+    pub async fn load_topic_schemas(&self) {
+        let rows =
+            match sqlx::query_as::<_, (String, String)>("SELECT topic, schema FROM topic_schemas")
+                .fetch_all(&self.db)
+                .await
+            {
+                Ok(rows) => rows,
+                Err(e) => {
+                    error!("Erreur lors du chargement des schémas de sujets: {}", e);
+                    return;
+                }
+            };
+        let mut schemas = self.topic_schemas.write().unwrap();
+        for (topic, schema) in rows {
+            let compiled = serde_json::from_str(&schema)
+                .map_err(|e| e.to_string())
+                .and_then(|schema| compile_schema(&schema));
+            match compiled {
+                Ok(validator) => {
+                    schemas.insert(topic, Arc::new(validator));
+                }
+                Err(e) => error!("Schéma invalide pour le sujet {}: {}", topic, e),
+            }
+        }
+        if !schemas.is_empty() {
+            info!("{} topic schemas loaded", schemas.len());
+        }
+    }
+
+    // Enregistre ou remplace le schéma JSON d'un sujet. Un schéma invalide est refusé sans toucher au précédent.
+    pub async fn set_topic_schema(
+        &self,
+        topic: &str,
+        schema: &serde_json::Value,
+    ) -> Result<(), SchemaError> {
+        let validator = compile_schema(schema).map_err(SchemaError::Invalid)?;
+        sqlx::query(
+            "INSERT INTO topic_schemas (topic, schema, updated_at) VALUES ($1, $2, $3) \
+             ON CONFLICT(topic) DO UPDATE SET schema = excluded.schema, updated_at = excluded.updated_at",
+        )
+        .bind(topic)
+        .bind(schema.to_string())
+        .bind(current_timestamp())
+        .execute(&self.db)
+        .await
+        .inspect_err(|e| error!("Erreur lors de l'enregistrement du schéma de {}: {}", topic, e))
+        .map_err(SchemaError::Database)?;
+        self.topic_schemas
+            .write()
+            .unwrap()
+            .insert(topic.to_string(), Arc::new(validator));
+        info!("Schema registered for topic {}", topic);
+        Ok(())
+    }
+
+    // Vérifie un message contre le schéma de son sujet, s'il en a un ; l'erreur décrit la première violation.
+    pub fn check_schema(&self, topic: &str, message: &serde_json::Value) -> Result<(), String> {
+        let Some(validator) = self.topic_schemas.read().unwrap().get(topic).cloned() else {
+            return Ok(());
+        };
+        validator.validate(message).map_err(|e| {
+            let path = e.instance_path.to_string();
+            if path.is_empty() {
+                e.to_string()
+            } else {
+                format!("{} (at {})", e, path)
+            }
+        })
+    }
+
+    // Recharge les `message_id` les plus récents, pour qu'une republication après un redémarrage
+    // soit encore reconnue comme doublon.
+    pub async fn load_recent_message_ids(&self) {
+        match sqlx::query_as::<_, (String,)>(
+            "SELECT message_id FROM (SELECT message_id, timestamp FROM messages WHERE message_id IS NOT NULL UNION ALL SELECT message_id, timestamp FROM messages_binary) AS published ORDER BY timestamp DESC LIMIT $1",
+        )
+        .bind(DEDUP_WINDOW as i64)
+        .fetch_all(&self.db)
+        .await
+        {
+            Ok(rows) => {
+                // Fenêtre pleine : des `message_id` plus anciens restent en base.
+                if rows.len() >= DEDUP_WINDOW {
+                    self.recent_message_ids.mark_incomplete();
+                }
+                // Du plus ancien au plus récent, pour que l'ordre d'éviction soit respecté.
+                for (message_id,) in rows.into_iter().rev() {
+                    self.recent_message_ids.insert(&message_id);
+                }
+            }
+            Err(e) => error!("Erreur lors du chargement des message_id récents: {}", e),
+        }
+    }
+
+    // Recharge le dernier `seq` de chaque sujet depuis la DB, pour que la numérotation
+    // reprenne là où elle s'était arrêtée après un redémarrage.
+    pub async fn load_topic_sequences(&self) {
+        match sqlx::query_as::<_, (String, i64)>(
+            "SELECT topic, MAX(seq) FROM messages WHERE seq IS NOT NULL GROUP BY topic",
+        )
+        .fetch_all(&self.db)
+        .await
+        {
+            Ok(rows) => {
+                let mut seqs = self.topic_seqs.lock().unwrap();
+                seqs.extend(rows);
+            }
+            Err(e) => error!("Erreur lors du chargement des séquences de sujets: {}", e),
+        }
+
+        if !self.config.producer_sequences {
+            return;
+        }
+        match sqlx::query_as::<_, (String, i64)>(
+            "SELECT producer, MAX(producer_seq) FROM messages WHERE producer_seq IS NOT NULL GROUP BY producer",
+        )
+        .fetch_all(&self.db)
+        .await
+        {
+            Ok(rows) => {
+                let mut seqs = self.producer_seqs.write().unwrap();
+                seqs.extend(
+                    rows.into_iter()
+                        .map(|(producer, seq)| (producer, Arc::new(AtomicU64::new(seq as u64)))),
+                );
+            }
+            Err(e) => error!("Erreur lors du chargement des séquences de producteurs: {}", e),
+        }
+    }
+
+    // Attribue le prochain `producer_seq` du producteur.
+    fn next_producer_seq(&self, producer: &str) -> i64 {
+        let counter = self.producer_seqs.read().unwrap().get(producer).cloned();
+        let counter = counter.unwrap_or_else(|| {
+            self.producer_seqs
+                .write()
+                .unwrap()
+                .entry(producer.to_string())
+                .or_default()
+                .clone()
+        });
+        (counter.fetch_add(1, Ordering::Relaxed) + 1) as i64
+    }
+
+    // Attend la fin de la prochaine purge périodique.
+    pub async fn purge_completed(&self) {
+        self.purge_done.notified().await;
+    }
+
+    // Parmi `candidates` (plus les sujets numérotés en mémoire), retourne les sujets morts :
+    // sans message restant en base ni abonné (en base ou en mémoire), avec leur dernier `seq` observé.
+    pub async fn dead_topics(&self, candidates: Vec<String>) -> Vec<(String, Option<i64>)> {
+        let heads = self.topic_seqs.lock().unwrap().clone();
+        let mut candidates = candidates;
+        candidates.extend(heads.keys().cloned());
+        candidates.sort();
+        candidates.dedup();
+
+        let live: HashSet<String> = match sqlx::query_as::<_, (String,)>(
+            "SELECT DISTINCT topic FROM messages UNION SELECT DISTINCT topic FROM subscriptions",
+        )
+        .fetch_all(&self.db)
+        .await
+        {
+            Ok(rows) => rows.into_iter().map(|(t,)| t).collect(),
+            Err(e) => {
+                // Dans le doute, on ne supprime rien.
+                error!("Erreur lors de la recherche des sujets morts: {}", e);
+                return Vec::with_capacity(0);
+            }
+        };
+
+        let subs = self.subscriptions.read().await;
+        candidates
+            .into_iter()
+            .filter(|topic| !live.contains(topic))
+            .filter(|topic| !subs.values().any(|(_, topics, _)| topics.contains(topic)))
+            .map(|topic| {
+                let seq = heads.get(&topic).copied();
+                (topic, seq)
+            })
+            .collect()
+    }
+
+    // Oublie la numérotation d'un sujet mort, sauf si un message y a été publié depuis `observed_seq`.
+    pub fn forget_topic(&self, topic: &str, observed_seq: Option<i64>) {
+        let mut seqs = self.topic_seqs.lock().unwrap();
+        if seqs.get(topic).copied() == observed_seq {
+            seqs.remove(topic);
+        }
+    }
+
+    // Copie du dernier `seq` attribué par sujet (compteur de publications par sujet).
+    pub fn topic_sequences(&self) -> HashMap<String, i64> {
+        self.topic_seqs.lock().unwrap().clone()
+    }
+
+    // Dernier `seq` attribué sur un sujet, `None` s'il n'a jamais été publié.
+    pub fn topic_head_seq(&self, topic: &str) -> Option<i64> {
+        self.topic_seqs.lock().unwrap().get(topic).copied()
+    }
+
+    // Sujets inconnus parmi `topics` (`REJECT_UNKNOWN_TOPIC_SUBSCRIBE`) : sans publication connue
+    // ni déclaration dans `KNOWN_TOPICS`. Le wildcard `*`, les motifs (`orders.*`) et les sujets alimentés
+    // par le serveur (confirmations, tap) sont toujours acceptés. Vide si l'option est désactivée.
+    pub fn unknown_topics(&self, topics: &[String]) -> Vec<String> {
+        if !self.config.reject_unknown_topic_subscribe {
+            return Vec::new();
+        }
+        let seqs = self.topic_seqs.lock().unwrap();
+        topics
+            .iter()
+            .filter(|topic| {
+                topic.as_str() != "*"
+                    && !is_topic_pattern(topic)
+                    && !seqs.contains_key(*topic)
+                    && !self.config.known_topics.contains(topic)
+                    && !self.config.is_server_topic(topic)
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Commandes d'écriture en attente dans le worker DB.
+    pub fn db_queue_depth(&self) -> usize {
+        self.db_queue_depth.load(Ordering::Relaxed)
+    }
+
+    // Compteurs cumulés du broker, partagés avec l'état de l'application (`GET /metrics`).
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    // Temps écoulé depuis la dernière publication reçue, ou depuis le démarrage.
+    pub fn publish_idle(&self) -> std::time::Duration {
+        let last = self.last_publish.load(Ordering::Relaxed);
+        std::time::Duration::from_millis(current_millis().saturating_sub(last))
+    }
+
+    fn mark_publish(&self) {
+        self.last_publish.store(current_millis(), Ordering::Relaxed);
+    }
+
+    // Nombre de connexions ayant au moins un abonnement.
+    pub async fn subscriber_count(&self) -> usize {
+        self.subscriptions.read().await.len()
+    }
+
+    // Met une publication en attente jusqu'à `deliver_at`. Écriture directe (hors worker par lots) :
+    // la publication ne doit pas être acquittée avant d'être durable.
+    // La place est réservée avant l'écriture, si bien que des publications concurrentes ne peuvent
+    // pas dépasser `MAX_SCHEDULED_MESSAGES` (0 = sans limite).
+    pub async fn schedule_message(
+        &self,
+        payload: &PublishRequest,
+        deliver_at: f64,
+    ) -> Result<Scheduled, sqlx::Error> {
+        self.mark_publish();
+        let max = self.config.max_scheduled_messages;
+        let reserved = self.scheduled_count.fetch_add(1, Ordering::Relaxed);
+        if max > 0 && reserved >= max {
+            self.scheduled_count.fetch_sub(1, Ordering::Relaxed);
+            return Ok(Scheduled::Full);
+        }
+        let result = sqlx::query(
+            "INSERT INTO scheduled_messages (message_id, topic, payload, deliver_at, created_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (message_id) DO NOTHING",
+        )
+        .bind(&payload.message_id)
+        .bind(&payload.topic)
+        .bind(serde_json::to_string(payload).unwrap_or_default())
+        .bind(deliver_at)
+        .bind(current_timestamp())
+        .execute(&self.db)
+        .await;
+        match result {
+            Ok(result) if result.rows_affected() > 0 => {
+                self.scheduled_changed.notify_one();
+                Ok(Scheduled::Accepted)
+            }
+            Ok(_) => {
+                self.scheduled_count.fetch_sub(1, Ordering::Relaxed);
+                Ok(Scheduled::Duplicate)
+            }
+            Err(e) => {
+                self.scheduled_count.fetch_sub(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    // Livraisons différées en attente.
+    pub fn scheduled_count(&self) -> usize {
+        self.scheduled_count.load(Ordering::Relaxed)
+    }
+
+    // Annule une livraison différée ; retourne `false` si aucun message n'était en attente.
+    pub async fn cancel_scheduled(&self, message_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM scheduled_messages WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&self.db)
+            .await?;
+        let cancelled = result.rows_affected() > 0;
+        if cancelled {
+            self.scheduled_count.fetch_sub(1, Ordering::Relaxed);
+        }
+        Ok(cancelled)
+    }
+
+    // Date de la prochaine livraison différée, s'il y en a une.
+    pub async fn next_scheduled_at(&self) -> Result<Option<f64>, sqlx::Error> {
+        let (next,) =
+            sqlx::query_as::<_, (Option<f64>,)>("SELECT MIN(deliver_at) FROM scheduled_messages")
+                .fetch_one(&self.db)
+                .await?;
+        Ok(next)
+    }
+
+    // Retire et retourne les livraisons différées arrivées à échéance, par date croissante.
+    // Chaque ligne est supprimée avant d'être retournée : une annulation concurrente gagne,
+    // et un message n'est jamais livré deux fois (au plus une fois en cas d'arrêt brutal).
+    pub async fn take_due_scheduled(&self) -> Result<Vec<PublishRequest>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT message_id, payload FROM scheduled_messages WHERE deliver_at <= $1 ORDER BY deliver_at",
+        )
+        .bind(current_timestamp())
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut due = Vec::with_capacity(rows.len());
+        for (message_id, payload) in rows {
+            let taken = sqlx::query("DELETE FROM scheduled_messages WHERE message_id = $1")
+                .bind(&message_id)
+                .execute(&self.db)
+                .await?;
+            if taken.rows_affected() == 0 {
+                continue;
+            }
+            self.scheduled_count.fetch_sub(1, Ordering::Relaxed);
+            match serde_json::from_str::<PublishRequest>(&payload) {
+                Ok(mut payload) => {
+                    payload.deliver_at = None;
+                    due.push(payload);
+                }
+                Err(e) => error!("Message différé {} illisible, ignoré: {}", message_id, e),
+            }
         }
+        Ok(due)
+    }
+
+    // Enregistre la livraison QoS 1 de `frame` à `consumer` (worker DB, dans l'ordre des accusés).
+    pub fn record_delivery(&self, consumer: &str, topic: &str, message_id: &str, frame: &str) {
+        let _ = self.db_tx.send(DbCommand::RecordDelivery {
+            consumer: consumer.to_string(),
+            topic: topic.to_string(),
+            message_id: message_id.to_string(),
+            frame: frame.to_string(),
+            timestamp: current_timestamp(),
+        });
+    }
+
+    // Marque la livraison de `message_id` à `consumer` comme acquittée.
+    pub fn ack_delivery(&self, consumer: &str, topic: &str, message_id: &str) {
+        let _ = self.db_tx.send(DbCommand::AckDelivery {
+            consumer: consumer.to_string(),
+            topic: topic.to_string(),
+            message_id: message_id.to_string(),
+            timestamp: current_timestamp(),
+        });
+    }
+
+    // Indique si `consumer` a déjà acquitté ce message. En cas d'erreur de lecture, le message
+    // est considéré comme non acquitté : mieux vaut un doublon qu'une perte.
+    pub async fn delivery_acked(&self, consumer: &str, topic: &str, message_id: &str) -> bool {
+        self.read_with_retry("deliveries", || {
+            sqlx::query_as::<_, (i32,)>(
+                "SELECT 1 FROM deliveries WHERE consumer = $1 AND topic = $2 AND message_id = $3 AND state = 'acked'",
+            )
+            .bind(consumer)
+            .bind(topic)
+            .bind(message_id)
+            .fetch_optional(&self.db)
+        })
+        .await
+        .map(|row| row.is_some())
+        .unwrap_or(false)
+    }
+
+    // Trames livrées à `consumer` sur `topics` et jamais acquittées (connexion perdue avant l'accusé),
+    // dans l'ordre de livraison.
+    pub async fn pending_deliveries(&self, consumer: &str, topics: &[String]) -> Vec<String> {
+        let rows = self
+            .read_with_retry("deliveries", || {
+                sqlx::query_as::<_, (String, String)>(
+                    "SELECT topic, frame FROM deliveries WHERE consumer = $1 AND state = 'delivered' AND frame IS NOT NULL ORDER BY updated_at",
+                )
+                .bind(consumer)
+                .fetch_all(&self.db)
+            })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Lecture des livraisons en attente de {} impossible: {}", consumer, e);
+                Vec::new()
+            });
+        rows.into_iter()
+            .filter(|(topic, _)| topics.contains(topic))
+            .map(|(_, frame)| frame)
+            .collect()
+    }
+
+    // Attend qu'une nouvelle livraison différée soit enregistrée.
+    pub async fn scheduled_changed(&self) {
+        self.scheduled_changed.notified().await;
+    }
+
+    // Indique si la livraison wildcard passe par le forwarder ordonné plutôt que par l'émission directe.
+    pub fn wildcard_merged(&self) -> bool {
+        self.config.wildcard_ordering == WildcardOrdering::PerTopic && self.wildcard_tx.is_some()
     }
 
     // Traite un batch de commandes DB à l'intérieur d'une seule transaction.
     // L'utilisation de transactions garantit l'atomicité : soit toutes les commandes réussissent, soit aucune n'est appliquée.
-    async fn flush_batch(db: &SqlitePool, batch: &mut Vec<DbCommand>) {
+    // Retourne `true` si le batch est validé (ou vide), `false` s'il est perdu.
+    async fn flush_batch(db: &DbPool, batch: &mut Vec<DbCommand>) -> bool {
         if batch.is_empty() {
-            return;
+            return true;
         }
 
         let mut tx = match db.begin().await {
@@ -152,7 +870,7 @@ impl Broker {
                 // On vide le batch pour ne pas retenter des commandes qui ont échoué.
                 error!("Impossible de démarrer une transaction: {}", e);
                 batch.clear();
-                return;
+                return false;
             }
         };
 
@@ -166,13 +884,15 @@ impl Broker {
                     consumer,
                     topic,
                     connected_at,
+                    tags,
                 } => {
-                    // `INSERT OR REPLACE` est utilisé pour mettre à jour l'abonnement s'il existe déjà.
-                    sqlx::query("INSERT OR REPLACE INTO subscriptions (sid, consumer, topic, connected_at) VALUES (?, ?, ?, ?)")
+                    // `ON CONFLICT ... DO UPDATE` met à jour l'abonnement s'il existe déjà.
+                    sqlx::query("INSERT INTO subscriptions (sid, consumer, topic, connected_at, tags) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (sid, topic) DO UPDATE SET consumer = excluded.consumer, connected_at = excluded.connected_at, tags = excluded.tags")
                         .bind(sid)
                         .bind(consumer)
                         .bind(topic)
                         .bind(connected_at)
+                        .bind(tags)
                         .execute(&mut *tx)
                         .await
                 }
@@ -182,13 +902,35 @@ impl Broker {
                     message,
                     producer,
                     timestamp,
+                    seq,
+                    producer_seq,
+                    expires_at,
                 } => {
-                    sqlx::query("INSERT INTO messages (topic, message_id, message, producer, timestamp) VALUES (?, ?, ?, ?, ?)")
+                    sqlx::query("INSERT INTO messages (topic, message_id, message, producer, timestamp, seq, producer_seq, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (message_id) DO NOTHING")
                         .bind(topic)
                         .bind(message_id)
                         .bind(message)
                         .bind(producer)
                         .bind(timestamp)
+                        .bind(seq)
+                        .bind(producer_seq)
+                        .bind(expires_at)
+                        .execute(&mut *tx)
+                        .await
+                }
+                DbCommand::SaveBinaryMessage {
+                    topic,
+                    message_id,
+                    producer,
+                    payload,
+                    timestamp,
+                } => {
+                    sqlx::query("INSERT INTO messages_binary (topic, message_id, producer, payload, timestamp) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (message_id) DO NOTHING")
+                        .bind(topic)
+                        .bind(message_id)
+                        .bind(producer)
+                        .bind(payload)
+                        .bind(timestamp)
                         .execute(&mut *tx)
                         .await
                 }
@@ -199,21 +941,80 @@ impl Broker {
                     message,
                     timestamp,
                 } => {
-                    sqlx::query("INSERT INTO consumptions (consumer, topic, message_id, message, timestamp) VALUES (?, ?, ?, ?, ?)")
+                    // La consommation est rattachée à la publication du message ; un message absent de
+                    // `messages` (binaire, déjà purgé) laisse `message_ref` NULL, la consommation est gardée.
+                    sqlx::query("INSERT INTO consumptions (consumer, topic, message_id, message, timestamp, message_ref) VALUES ($1, $2, $3, $4, $5, (SELECT MAX(id) FROM messages WHERE topic = $6 AND message_id = $7))")
                         .bind(consumer)
-                        .bind(topic)
-                        .bind(message_id)
+                        .bind(&topic)
+                        .bind(&message_id)
                         .bind(message)
                         .bind(timestamp)
+                        .bind(&topic)
+                        .bind(&message_id)
+                        .execute(&mut *tx)
+                        .await
+                }
+                DbCommand::UnregisterSubscription { sid, topic } => {
+                    sqlx::query("DELETE FROM subscriptions WHERE sid = $1 AND topic = $2")
+                        .bind(sid)
+                        .bind(topic)
                         .execute(&mut *tx)
                         .await
                 }
                 DbCommand::UnregisterClient { sid } => {
-                    sqlx::query("DELETE FROM subscriptions WHERE sid = ?")
+                    sqlx::query("DELETE FROM subscriptions WHERE sid = $1")
                         .bind(sid)
                         .execute(&mut *tx)
                         .await
                 }
+                DbCommand::RecordDelivery {
+                    consumer,
+                    topic,
+                    message_id,
+                    frame,
+                    timestamp,
+                } => {
+                    // Une livraison déjà acquittée n'est jamais ramenée à l'état `delivered`.
+                    sqlx::query("INSERT INTO deliveries (consumer, topic, message_id, state, frame, updated_at) VALUES ($1, $2, $3, 'delivered', $4, $5) ON CONFLICT (consumer, topic, message_id) DO UPDATE SET frame = excluded.frame, updated_at = excluded.updated_at WHERE deliveries.state = 'delivered'")
+                        .bind(consumer)
+                        .bind(topic)
+                        .bind(message_id)
+                        .bind(frame)
+                        .bind(timestamp)
+                        .execute(&mut *tx)
+                        .await
+                }
+                DbCommand::AckDelivery {
+                    consumer,
+                    topic,
+                    message_id,
+                    timestamp,
+                } => {
+                    sqlx::query("INSERT INTO deliveries (consumer, topic, message_id, state, frame, updated_at) VALUES ($1, $2, $3, 'acked', NULL, $4) ON CONFLICT (consumer, topic, message_id) DO UPDATE SET state = 'acked', frame = NULL, updated_at = excluded.updated_at")
+                        .bind(consumer)
+                        .bind(topic)
+                        .bind(message_id)
+                        .bind(timestamp)
+                        .execute(&mut *tx)
+                        .await
+                }
+                DbCommand::SaveEvent {
+                    event_type,
+                    data,
+                    timestamp,
+                } => {
+                    sqlx::query("INSERT INTO events (event_type, data, timestamp) VALUES ($1, $2, $3)")
+                        .bind(event_type)
+                        .bind(data)
+                        .bind(timestamp)
+                        .execute(&mut *tx)
+                        .await
+                }
+                // Interceptée par le worker avant d'entrer dans un batch.
+                DbCommand::Flush { done } => {
+                    let _ = done.send(false);
+                    continue;
+                }
             };
 
             if let Err(e) = result {
@@ -232,15 +1033,92 @@ impl Broker {
             } else {
                 warn!("Transaction annulée suite à une erreur");
             }
+            false
         } else if let Err(e) = tx.commit().await {
             error!("Erreur lors du commit de la transaction: {}", e);
+            false
+        } else {
+            true
         }
     }
 
-    // Supprime les anciennes données de la base de données pour éviter qu'elle ne grossisse indéfiniment.
-    async fn purge_old_data(db: &SqlitePool) {
-        use tracing::info;
+    // Effectue une passe de compactation incrémentale, par petites étapes espacées.
+    // La passe s'interrompt dès qu'une écriture récente est détectée, pour ne pas concurrencer le worker DB.
+    // Retourne `false` si la base n'est pas en `auto_vacuum = INCREMENTAL` (la compactation est alors impossible).
+    async fn incremental_vacuum(db: &DbPool, config: &Config, last_write_ms: &AtomicU64) -> bool {
+        // `PRAGMA auto_vacuum` ne s'applique qu'aux bases créées avec ce réglage : 2 = INCREMENTAL.
+        match sqlx::query_as::<_, (i64,)>("PRAGMA auto_vacuum")
+            .fetch_one(db)
+            .await
+        {
+            Ok((2,)) => {}
+            Ok((mode,)) => {
+                warn!(
+                    "Compactation désactivée: auto_vacuum = {} (INCREMENTAL requis, un VACUUM complet est nécessaire pour l'activer)",
+                    mode
+                );
+                return false;
+            }
+            Err(e) => {
+                error!("Impossible de lire auto_vacuum: {}", e);
+                return true;
+            }
+        }
+
+        let is_idle = || {
+            current_millis().saturating_sub(last_write_ms.load(Ordering::Relaxed))
+                >= config.vacuum_idle_ms
+        };
+
+        let start = std::time::Instant::now();
+        let mut freed_pages = 0i64;
+
+        loop {
+            if !is_idle() {
+                // Des écritures sont en cours : on reporte la suite à la prochaine passe.
+                break;
+            }
+
+            let free_pages = match sqlx::query_as::<_, (i64,)>("PRAGMA freelist_count")
+                .fetch_one(db)
+                .await
+            {
+                Ok((n,)) => n,
+                Err(e) => {
+                    error!("Impossible de lire freelist_count: {}", e);
+                    break;
+                }
+            };
+            if free_pages == 0 {
+                break;
+            }
+
+            let chunk = free_pages.min(config.vacuum_chunk_pages);
+            if let Err(e) = sqlx::query(&format!("PRAGMA incremental_vacuum({})", chunk))
+                .execute(db)
+                .await
+            {
+                error!("Erreur lors de incremental_vacuum: {}", e);
+                break;
+            }
+            freed_pages += chunk;
+
+            // Laisse respirer la base entre deux étapes.
+            tokio::time::sleep(tokio::time::Duration::from_millis(config.vacuum_pause_ms)).await;
+        }
+
+        if freed_pages > 0 {
+            info!(
+                "Compactation: {} pages libérées en {:?}",
+                freed_pages,
+                start.elapsed()
+            );
+        }
+        true
+    }
 
+    // Supprime les anciennes données de la base de données pour éviter qu'elle ne grossisse indéfiniment.
+    async fn purge_old_data(db: &DbPool, config: &Config, archive: Option<&Archive>) {
         let start = std::time::Instant::now();
         let cutoff_timestamp = current_timestamp() - (MAX_AGE_HOURS * 3600.0);
 
@@ -258,12 +1136,183 @@ impl Broker {
 
         // Purge messages: keep only MAX_MESSAGES most recent AND remove anything older than MAX_AGE_HOURS
         // Purge les messages en gardant les `MAX_MESSAGES` plus récents et en supprimant tout ce qui est plus vieux que `MAX_AGE_HOURS`.
-        match sqlx::query(
-            "DELETE FROM messages WHERE id NOT IN (
-                SELECT id FROM messages ORDER BY timestamp DESC LIMIT ?
-            ) OR timestamp < ?",
-        )
-        .bind(MAX_MESSAGES)
+        // En mode `per_topic`, la limite s'applique à chaque sujet séparément (`ROW_NUMBER() ... PARTITION BY topic`),
+        // pour qu'un sujet très actif ne puisse pas évincer tout l'historique d'un sujet calme.
+        // Avec l'archivage à froid, la suppression retourne les lignes retirées.
+        let returning = if archive.is_some() {
+            " RETURNING topic, message_id, message, producer, timestamp, seq, producer_seq, key"
+        } else {
+            ""
+        };
+        let (messages_sql, keep) = match config.message_retention {
+            MessageRetention::Global => (
+                format!(
+                    "DELETE FROM messages WHERE id NOT IN (
+                        SELECT id FROM messages ORDER BY timestamp DESC LIMIT $1
+                    ) OR timestamp < $2 OR expires_at < $3{}",
+                    returning
+                ),
+                MAX_MESSAGES,
+            ),
+            MessageRetention::PerTopic => (
+                format!(
+                    "DELETE FROM messages WHERE id IN (
+                        SELECT id FROM (
+                            SELECT id, ROW_NUMBER() OVER (PARTITION BY topic ORDER BY timestamp DESC) AS rn
+                            FROM messages
+                        ) ranked WHERE rn > $1
+                    ) OR timestamp < $2 OR expires_at < $3{}",
+                    returning
+                ),
+                config.max_messages_per_topic,
+            ),
+        };
+        let messages_purge = sqlx::query(&messages_sql).bind(keep);
+        let messages_purge = messages_purge
+            .bind(cutoff_timestamp)
+            .bind(current_timestamp());
+        let deleted = match archive {
+            // Archivage à froid : les lignes supprimées sont récupérées (`RETURNING`) et archivées
+            // avant la validation ; un échec d'archivage annule la suppression.
+            Some(archive) => {
+                let purged = messages_purge.fetch_all(&mut *tx).await.and_then(|rows| {
+                    rows.iter()
+                        .map(ArchivedMessage::from_row)
+                        .collect::<Result<Vec<_>, _>>()
+                });
+                match purged {
+                    Ok(purged) => match archive.write(&purged).await {
+                        Ok(()) => Ok(purged.len() as u64),
+                        Err(e) => {
+                            error!(
+                                "Archivage de {} messages impossible, purge annulée: {}",
+                                purged.len(),
+                                e
+                            );
+                            let _ = tx.rollback().await;
+                            return;
+                        }
+                    },
+                    Err(e) => Err(e),
+                }
+            }
+            None => messages_purge
+                .execute(&mut *tx)
+                .await
+                .map(|result| result.rows_affected()),
+        };
+        match deleted {
+            Ok(deleted) => {
+                if deleted > 0 {
+                    info!("Purge: supprimé {} anciens messages", deleted);
+                    total_deleted += deleted as i64;
+                }
+            }
+            Err(e) => {
+                // Annule la transaction en cas d'erreur.
+                error!("Erreur lors de la purge des messages: {}", e);
+                let _ = tx.rollback().await;
+                return;
+            }
+        }
+
+        // Purge consumptions: keep only MAX_CONSUMPTIONS most recent AND remove anything older than MAX_AGE_HOURS
+        // Fait de même pour les consommations. En mode `per_consumer`, la limite s'applique à chaque
+        // consommateur (`PARTITION BY consumer`), pour qu'un consommateur bavard n'évince pas l'historique des autres.
+        let (consumptions_sql, keep) = match config.consumption_retention {
+            ConsumptionRetention::Global => (
+                "DELETE FROM consumptions WHERE id NOT IN (
+                    SELECT id FROM consumptions ORDER BY timestamp DESC LIMIT $1
+                ) OR timestamp < $2",
+                MAX_CONSUMPTIONS,
+            ),
+            ConsumptionRetention::PerConsumer => (
+                "DELETE FROM consumptions WHERE id IN (
+                    SELECT id FROM (
+                        SELECT id, ROW_NUMBER() OVER (PARTITION BY consumer ORDER BY timestamp DESC) AS rn
+                        FROM consumptions
+                    ) ranked WHERE rn > $1
+                ) OR timestamp < $2",
+                config.max_consumptions_per_consumer,
+            ),
+        };
+        match sqlx::query(consumptions_sql)
+            .bind(keep)
+            .bind(cutoff_timestamp)
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(result) => {
+                let deleted = result.rows_affected();
+                if deleted > 0 {
+                    info!("Purge: supprimé {} anciennes consommations", deleted);
+                    total_deleted += deleted as i64;
+                }
+            }
+            Err(e) => {
+                error!("Erreur lors de la purge des consommations: {}", e);
+                let _ = tx.rollback().await;
+                return;
+            }
+        }
+
+        // Purge les états de livraison QoS 1 selon le même âge maximum : au-delà, un message acquitté
+        // a de toute façon quitté l'historique et ne peut plus être rejoué.
+        if config.persist_delivery_acks {
+            match sqlx::query("DELETE FROM deliveries WHERE updated_at < $1")
+                .bind(cutoff_timestamp)
+                .execute(&mut *tx)
+                .await
+            {
+                Ok(result) => {
+                    let deleted = result.rows_affected();
+                    if deleted > 0 {
+                        info!("Purge: supprimé {} anciens états de livraison", deleted);
+                        total_deleted += deleted as i64;
+                    }
+                }
+                Err(e) => {
+                    error!("Erreur lors de la purge des états de livraison: {}", e);
+                    let _ = tx.rollback().await;
+                    return;
+                }
+            }
+        }
+
+        // Purge le journal d'audit selon sa propre limite (`MAX_EVENTS`) et le même âge maximum.
+        if config.persist_events {
+            match sqlx::query(
+                "DELETE FROM events WHERE id NOT IN (
+                    SELECT id FROM events ORDER BY timestamp DESC LIMIT $1
+                ) OR timestamp < $2",
+            )
+            .bind(config.max_events)
+            .bind(cutoff_timestamp)
+            .execute(&mut *tx)
+            .await
+            {
+                Ok(result) => {
+                    let deleted = result.rows_affected();
+                    if deleted > 0 {
+                        info!("Purge: supprimé {} anciens événements", deleted);
+                        total_deleted += deleted as i64;
+                    }
+                }
+                Err(e) => {
+                    error!("Erreur lors de la purge des événements: {}", e);
+                    let _ = tx.rollback().await;
+                    return;
+                }
+            }
+        }
+
+        // Purge les publications binaires selon la même limite et le même âge que les messages JSON.
+        match sqlx::query(
+            "DELETE FROM messages_binary WHERE id NOT IN (
+                SELECT id FROM messages_binary ORDER BY timestamp DESC LIMIT $1
+            ) OR timestamp < $2",
+        )
+        .bind(MAX_MESSAGES)
         .bind(cutoff_timestamp)
         .execute(&mut *tx)
         .await
@@ -271,26 +1320,24 @@ impl Broker {
             Ok(result) => {
                 let deleted = result.rows_affected();
                 if deleted > 0 {
-                    info!("Purge: supprimé {} anciens messages", deleted);
+                    info!("Purge: supprimé {} anciens messages binaires", deleted);
                     total_deleted += deleted as i64;
                 }
             }
             Err(e) => {
-                // Annule la transaction en cas d'erreur.
-                error!("Erreur lors de la purge des messages: {}", e);
+                error!("Erreur lors de la purge des messages binaires: {}", e);
                 let _ = tx.rollback().await;
                 return;
             }
         }
 
-        // Purge consumptions: keep only MAX_CONSUMPTIONS most recent AND remove anything older than MAX_AGE_HOURS
-        // Fait de même pour les consommations.
+        // Purge les lettres mortes selon la même limite et le même âge, comptés depuis leur détection.
         match sqlx::query(
-            "DELETE FROM consumptions WHERE id NOT IN (
-                SELECT id FROM consumptions ORDER BY timestamp DESC LIMIT ?
-            ) OR timestamp < ?",
+            "DELETE FROM dead_letters WHERE id NOT IN (
+                SELECT id FROM dead_letters ORDER BY detected_at DESC LIMIT $1
+            ) OR detected_at < $2",
         )
-        .bind(MAX_CONSUMPTIONS)
+        .bind(MAX_MESSAGES)
         .bind(cutoff_timestamp)
         .execute(&mut *tx)
         .await
@@ -298,12 +1345,12 @@ impl Broker {
             Ok(result) => {
                 let deleted = result.rows_affected();
                 if deleted > 0 {
-                    info!("Purge: supprimé {} anciennes consommations", deleted);
+                    info!("Purge: supprimé {} anciennes lettres mortes", deleted);
                     total_deleted += deleted as i64;
                 }
             }
             Err(e) => {
-                error!("Erreur lors de la purge des consommations: {}", e);
+                error!("Erreur lors de la purge des lettres mortes: {}", e);
                 let _ = tx.rollback().await;
                 return;
             }
@@ -326,13 +1373,29 @@ impl Broker {
     }
 
     // Enregistre un nouvel abonnement.
-    pub async fn register_subscription(&self, sid: String, consumer: String, topic: String) {
+    // Des étiquettes non vides remplacent celles déjà déclarées par la connexion.
+    pub async fn register_subscription(
+        &self,
+        sid: String,
+        consumer: String,
+        topic: String,
+        tags: &BTreeMap<String, String>,
+    ) {
         if sid.is_empty() || consumer.is_empty() || topic.is_empty() {
             warn!("register_subscription: Paramètres requis manquants");
             return;
         }
 
         let connected_at = current_timestamp();
+        let tags = {
+            let mut client_tags = self.client_tags.write().unwrap();
+            if !tags.is_empty() {
+                client_tags.insert(sid.clone(), tags.clone());
+            }
+            client_tags
+                .get(&sid)
+                .and_then(|tags| serde_json::to_string(tags).ok())
+        };
 
         // Envoie la commande d'enregistrement au worker DB. L'opération est asynchrone et ne bloque pas.
         let _ = self.db_tx.send(DbCommand::RegisterSubscription {
@@ -340,20 +1403,37 @@ impl Broker {
             consumer: consumer.clone(),
             topic: topic.clone(),
             connected_at,
+            tags,
         });
+        self.metrics
+            .subscriptions_registered
+            .fetch_add(1, Ordering::Relaxed);
 
-        {
+        // Nombre de connexions abonnées, si celle-ci vient de s'y ajouter.
+        let client_count = {
             // Met à jour le cache en mémoire des abonnements.
             // `write().await` obtient un verrou en écriture sur le `RwLock`.
             let mut subs = self.subscriptions.write().await;
+            let mut added = true;
+            let new_client = !subs.contains_key(&sid);
             subs.entry(sid.clone())
                 .and_modify(|(_, topics, _)| {
-                    if !topics.contains(&topic) {
+                    added = !topics.contains(&topic);
+                    if added {
                         topics.push(topic.clone());
                     }
                 })
                 .or_insert((consumer.clone(), vec![topic.clone()], connected_at));
-        }
+            if added && is_topic_pattern(&topic) {
+                *self
+                    .pattern_refs
+                    .write()
+                    .unwrap()
+                    .entry(topic.clone())
+                    .or_insert(0) += 1;
+            }
+            new_client.then_some(subs.len())
+        };
 
         // Diffuse un événement pour notifier (par exemple, le dashboard) qu'un nouveau client s'est connecté.
         let event = Arc::new(BroadcastEvent {
@@ -366,6 +1446,157 @@ impl Broker {
         });
 
         let _ = self.event_tx.send(event);
+        if let Some(count) = client_count {
+            self.broadcast_client_count(count);
+        }
+    }
+
+    // Enregistre une publication binaire (`POST /publish/binary`) et retourne son horodatage ;
+    // `None` pour un `message_id` déjà publié, ni stocké ni diffusé (voir `is_duplicate`).
+    // Le contenu est stocké tel quel (table `messages_binary`), sans numéro de séquence : les messages
+    // binaires ne figurent ni dans `/messages` ni dans `/replay`. Le flux d'événements n'en reçoit
+    // qu'un marqueur `{ binary, size }`, comme pour un corps trop volumineux.
+    pub async fn publish_binary(
+        &self,
+        request: &BinaryPublishQuery,
+        payload: &[u8],
+    ) -> Option<f64> {
+        self.mark_publish();
+        if self
+            .is_duplicate(&request.topic, &request.message_id, &request.producer)
+            .await
+        {
+            return None;
+        }
+        let timestamp = current_timestamp();
+        let _ = self.db_tx.send(DbCommand::SaveBinaryMessage {
+            topic: request.topic.clone(),
+            message_id: request.message_id.clone(),
+            producer: request.producer.clone(),
+            payload: payload.to_vec(),
+            timestamp,
+        });
+        self.metrics
+            .messages_published
+            .fetch_add(1, Ordering::Relaxed);
+        self.metrics
+            .record_publish(&request.topic, &request.message_id, timestamp);
+
+        let event = Arc::new(BroadcastEvent {
+            event_type: "new_message".to_string(),
+            data: serde_json::json!({
+                "topic": request.topic,
+                "message_id": request.message_id,
+                "message": {
+                    "binary": true,
+                    "size": payload.len(),
+                    "message_id": request.message_id,
+                },
+                "producer": request.producer,
+                "timestamp": timestamp,
+            }),
+        });
+        let _ = self.event_tx.send(event);
+        Some(timestamp)
+    }
+
+    // Diffuse `client_count` : nombre de connexions abonnées (SID distincts, quel que soit leur
+    // nombre de sujets), valeur de référence pour le compteur du dashboard.
+    fn broadcast_client_count(&self, count: usize) {
+        let event = Arc::new(BroadcastEvent {
+            event_type: "client_count".to_string(),
+            data: serde_json::json!({ "count": count }),
+        });
+        let _ = self.event_tx.send(event);
+    }
+
+    // Clôt une demande d'abonnement : diffuse `subscription_confirmed` avec la liste complète
+    // des sujets actifs de la connexion, état de référence pour le dashboard
+    // (contrairement aux `new_client`, émis un par sujet). Activé par `SUBSCRIPTION_CONFIRMED_EVENTS`.
+    pub async fn confirm_subscription(&self, sid: &str) {
+        if !self.config.subscription_confirmed_events {
+            return;
+        }
+        let Some((consumer, topics, connected_at)) = self.get_client_by_sid(sid).await else {
+            return;
+        };
+
+        let event = Arc::new(BroadcastEvent {
+            event_type: "subscription_confirmed".to_string(),
+            data: serde_json::json!({
+                "sid": sid,
+                "consumer": consumer,
+                "topics": topics,
+                "connected_at": connected_at,
+            }),
+        });
+
+        let _ = self.event_tx.send(event);
+    }
+
+    // Retire un sujet des abonnements d'une connexion sans la fermer ; l'entrée disparaît avec
+    // son dernier sujet. Retourne `false` si la connexion n'était pas abonnée à ce sujet.
+    pub async fn unregister_subscription(&self, sid: &str, topic: &str) -> bool {
+        let (consumer, client_count) = {
+            let mut subs = self.subscriptions.write().await;
+            let Some((consumer, topics, _)) = subs.get_mut(sid) else {
+                return false;
+            };
+            let Some(index) = topics.iter().position(|t| t == topic) else {
+                return false;
+            };
+            topics.remove(index);
+            let consumer = consumer.clone();
+            let removed = topics.is_empty();
+            if removed {
+                subs.remove(sid);
+                self.client_tags.write().unwrap().remove(sid);
+            }
+            self.release_patterns(std::iter::once(topic));
+            (consumer, removed.then_some(subs.len()))
+        };
+
+        let _ = self.db_tx.send(DbCommand::UnregisterSubscription {
+            sid: sid.to_string(),
+            topic: topic.to_string(),
+        });
+
+        let event = Arc::new(BroadcastEvent {
+            event_type: "client_unsubscribed".to_string(),
+            data: serde_json::json!({
+                "consumer": consumer,
+                "topic": topic,
+            }),
+        });
+        let _ = self.event_tx.send(event);
+        if let Some(count) = client_count {
+            self.broadcast_client_count(count);
+        }
+        true
+    }
+
+    // Décompte les abonnements retirés aux motifs hiérarchiques ; un motif sans abonné est oublié.
+    fn release_patterns<'a>(&self, topics: impl Iterator<Item = &'a str>) {
+        let mut pattern_refs = self.pattern_refs.write().unwrap();
+        for topic in topics {
+            if let Some(count) = pattern_refs.get_mut(topic) {
+                *count -= 1;
+                if *count == 0 {
+                    pattern_refs.remove(topic);
+                }
+            }
+        }
+    }
+
+    // Motifs hiérarchiques abonnés auxquels correspond le sujet publié.
+    pub fn matching_patterns(&self, topic: &str) -> Vec<String> {
+        self.pattern_refs
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|pattern| topic_matches(pattern, topic))
+            .cloned()
+            .collect()
     }
 
     // Gère la déconnexion d'un client.
@@ -378,11 +1609,18 @@ impl Broker {
             sid: sid.to_string(),
         });
 
-        {
+        let client_count = {
             // Supprime le client du cache en mémoire.
             let mut subs = self.subscriptions.write().await;
-            subs.remove(sid);
-        }
+            subs.remove(sid).map(|(_, topics, _)| {
+                self.release_patterns(topics.iter().map(String::as_str));
+                subs.len()
+            })
+        };
+        self.client_tags.write().unwrap().remove(sid);
+        self.metrics
+            .clients_disconnected
+            .fetch_add(1, Ordering::Relaxed);
 
         // Si le client existait, diffuse des événements de déconnexion pour chaque sujet auquel il était abonné.
         if let Some((consumer, topics, _)) = client_info {
@@ -397,42 +1635,355 @@ impl Broker {
                 let _ = self.event_tx.send(event);
             }
         }
+        if let Some(count) = client_count {
+            self.broadcast_client_count(count);
+        }
     }
 
-    // Sauvegarde un message et diffuse un événement.
-    pub async fn save_message(
+    // Diffuse `delivery_failed` : la livraison en direct d'un message à une salle Socket.IO a échoué
+    // malgré les nouvelles tentatives (le message reste enregistré).
+    pub fn report_delivery_failure(&self, topic: &str, message_id: &str, room: &str, error: &str) {
+        let event = Arc::new(BroadcastEvent {
+            event_type: "delivery_failed".to_string(),
+            data: serde_json::json!({
+                "topic": topic,
+                "message_id": message_id,
+                "room": room,
+                "error": error,
+                "timestamp": current_timestamp(),
+            }),
+        });
+        let _ = self.event_tx.send(event);
+    }
+
+    // Remplace l'ensemble des connexions silencieuses et diffuse `client_stale` pour chaque sujet
+    // des connexions qui viennent de le devenir.
+    pub async fn mark_stale(&self, sids: HashSet<String>) {
+        let newly_stale: Vec<String> = {
+            let mut stale_sids = self.stale_sids.write().unwrap();
+            let newly_stale = sids.difference(&stale_sids).cloned().collect();
+            *stale_sids = sids;
+            newly_stale
+        };
+        for sid in newly_stale {
+            if let Some((consumer, topics, _)) = self.get_client_by_sid(&sid).await {
+                for topic in topics {
+                    let event = Arc::new(BroadcastEvent {
+                        event_type: "client_stale".to_string(),
+                        data: serde_json::json!({
+                            "consumer": consumer.clone(),
+                            "topic": topic,
+                        }),
+                    });
+                    let _ = self.event_tx.send(event);
+                }
+            }
+        }
+    }
+
+    // Abonnements en cache plus anciens que `before` dont la connexion n'est plus enregistrée
+    // (déconnexion manquée) : à retirer par le balayage de vivacité.
+    pub async fn orphaned_subscriptions(&self, live: &HashSet<String>, before: f64) -> Vec<String> {
+        self.subscriptions
+            .read()
+            .await
+            .iter()
+            .filter(|(sid, (_, _, connected_at))| !live.contains(*sid) && *connected_at < before)
+            .map(|(sid, _)| sid.clone())
+            .collect()
+    }
+
+    // Sujet du tap de débogage si ce message doit y être recopié (`TAP_TOPIC`, échantillonné
+    // selon `TAP_SAMPLE_EVERY`). Les messages publiés directement sur le tap ne sont pas recopiés.
+    pub fn tap_target(&self, topic: &str) -> Option<&str> {
+        let tap = self.config.tap_topic.as_deref()?;
+        if tap == topic {
+            return None;
+        }
+        let every = self.config.tap_sample_every.max(1);
+        self.tap_counter
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(every)
+            .then_some(tap)
+    }
+
+    // Fait passer une publication dans le pipeline de transformation (no-op s'il est vide).
+    // Le sujet et le message peuvent être modifiés ; `Err` contient la raison d'un rejet.
+    pub fn transform(
         &self,
-        topic: String,
-        message_id: String,
-        message: serde_json::Value,
-        producer: String,
-    ) {
-        let timestamp = current_timestamp();
+        topic: &mut String,
+        message: &mut serde_json::Value,
+    ) -> Result<(), String> {
+        if self.pipeline.is_empty() {
+            return Ok(());
+        }
+        self.pipeline.apply(topic, message)
+    }
+
+    // Sauvegarde un message et diffuse un événement.
+    // `timestamp` est l'horodatage fourni par le client (déjà validé), sinon l'heure du serveur.
+    // Retourne les numéros de séquence attribués au message.
+    pub async fn save_message(&self, payload: &PublishRequest) -> Result<Saved, DuplicateKey> {
+        self.mark_publish();
+        let topic = payload.topic.clone();
+        let message_id = payload.message_id.clone();
+        let message = payload.message.clone();
+        let producer = payload.producer.clone();
+        let timestamp = payload.timestamp.unwrap_or_else(current_timestamp);
+        let key = payload.key.clone();
+        let expires_at = payload.ttl_seconds.map(|ttl| timestamp + ttl);
+        // Déjà expiré (horodatage ancien, livraison différée tardive) : stocké mais pas diffusé.
+        let expired = expires_at.is_some_and(|expires_at| expires_at <= current_timestamp());
+
+        if self.is_duplicate(&topic, &message_id, &producer).await {
+            return Ok(Saved::Duplicate);
+        }
+
+        // Sujet à clé unique : la vérification et l'écriture sont sérialisées sous `unique_keys`
+        // et l'écriture est directe (hors worker par lots), pour qu'aucune publication
+        // concurrente de la même clé ne passe entre les deux.
+        let unique = key.and_then(|key| {
+            self.config
+                .unique_key_topics
+                .get(&topic)
+                .map(|policy| (key, *policy))
+        });
+        let _unique_guard = match &unique {
+            Some((key, policy)) => {
+                let guard = self.unique_keys.lock().await;
+                if *policy == UniqueKeyPolicy::Reject && self.key_exists(&topic, key).await {
+                    // Refusée : une nouvelle tentative avec ce `message_id` ne sera pas un doublon.
+                    self.recent_message_ids.remove(&message_id);
+                    return Err(DuplicateKey);
+                }
+                Some(guard)
+            }
+            None => None,
+        };
+
+        let producer_seq = self
+            .config
+            .producer_sequences
+            .then(|| self.next_producer_seq(&producer));
         // Sérialise le message en JSON.
         let message_json = message.to_string();
+        let message_size = message_json.len();
 
-        // Envoie la commande de sauvegarde au worker DB.
-        let _ = self.db_tx.send(DbCommand::SaveMessage {
+        // Attribue le `seq` et alimente le forwarder wildcard sous le même verrou :
+        // l'ordre d'entrée dans la file est ainsi exactement l'ordre des `seq` pour chaque sujet.
+        // Le forwarder ne sert que le namespace racine ; les autres sont livrés directement.
+        let root_namespace = matches!(payload.namespace.as_deref(), None | Some("/"));
+        let seq = {
+            let mut seqs = self.topic_seqs.lock().unwrap();
+            let seq = seqs.entry(topic.clone()).or_insert(0);
+            *seq += 1;
+            if let Some(wildcard_tx) = self
+                .wildcard_tx
+                .as_ref()
+                .filter(|_| !expired && root_namespace)
+            {
+                // Même trame que l'émission par sujet : la publication complétée par le serveur.
+                let mut delivered = payload.clone();
+                delivered.timestamp = Some(timestamp);
+                delivered.seq = Some(*seq);
+                delivered.producer_seq = producer_seq;
+                if let Ok(frame) = serde_json::to_value(&delivered) {
+                    let _ = wildcard_tx.send(WildcardItem {
+                        topic: topic.clone(),
+                        frame,
+                    });
+                }
+            }
+            *seq
+        };
+
+        let command = DbCommand::SaveMessage {
             topic: topic.clone(),
             message_id: message_id.clone(),
             message: message_json,
             producer: producer.clone(),
             timestamp,
-        });
+            seq,
+            producer_seq,
+            expires_at,
+        };
+        match unique {
+            Some((key, policy)) => self.write_keyed_message(command, key, policy).await,
+            // Envoie la commande de sauvegarde au worker DB.
+            None => {
+                let _ = self.db_tx.send(command);
+            }
+        }
+        self.metrics
+            .messages_published
+            .fetch_add(1, Ordering::Relaxed);
+        self.metrics.record_publish(&topic, &message_id, timestamp);
 
-        // Diffuse l'événement de nouveau message.
+        // Diffuse l'événement de nouveau message. Un corps volumineux est remplacé par un marqueur :
+        // le flux d'événements reste léger, le message complet reste disponible via `/messages`.
+        let max_event_bytes = self.config.max_event_payload_bytes;
+        let event_message = if max_event_bytes > 0 && message_size > max_event_bytes {
+            serde_json::json!({
+                "elided": true,
+                "size": message_size,
+                "message_id": message_id,
+            })
+        } else {
+            message
+        };
         let event = Arc::new(BroadcastEvent {
             event_type: "new_message".to_string(),
             data: serde_json::json!({
                 "topic": topic,
                 "message_id": message_id,
-                "message": message,
+                "message": event_message,
                 "producer": producer,
                 "timestamp": timestamp,
+                "seq": seq,
+                "producer_seq": producer_seq,
             }),
         });
 
         let _ = self.event_tx.send(event);
+
+        Ok(Saved::Accepted(AssignedSeq { seq, producer_seq }))
+    }
+
+    // Indique si un message de `topic` porte déjà `key`. Une erreur de lecture est journalisée
+    // et laisse passer la publication : l'index unique reste le dernier rempart.
+    async fn key_exists(&self, topic: &str, key: &str) -> bool {
+        match sqlx::query_as::<_, (i32,)>(
+            "SELECT 1 FROM messages WHERE topic = $1 AND key = $2 LIMIT 1",
+        )
+        .bind(topic)
+        .bind(key)
+        .fetch_optional(&self.db)
+        .await
+        {
+            Ok(row) => row.is_some(),
+            Err(e) => {
+                error!(
+                    "Erreur lors de la vérification de la clé {} sur {}: {}",
+                    key, topic, e
+                );
+                false
+            }
+        }
+    }
+
+    // Écrit immédiatement un message de sujet à clé unique ; en mode `replace`, l'ancien message
+    // de même clé est supprimé dans la même transaction.
+    async fn write_keyed_message(&self, command: DbCommand, key: String, policy: UniqueKeyPolicy) {
+        let DbCommand::SaveMessage {
+            topic,
+            message_id,
+            message,
+            producer,
+            timestamp,
+            seq,
+            producer_seq,
+            expires_at,
+        } = command
+        else {
+            return;
+        };
+        let result = async {
+            let mut tx = self.db.begin().await?;
+            if policy == UniqueKeyPolicy::Replace {
+                sqlx::query("DELETE FROM messages WHERE topic = $1 AND key = $2")
+                    .bind(&topic)
+                    .bind(&key)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            sqlx::query("INSERT INTO messages (topic, message_id, message, producer, timestamp, seq, producer_seq, key, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT (message_id) DO NOTHING")
+                .bind(&topic)
+                .bind(message_id)
+                .bind(message)
+                .bind(producer)
+                .bind(timestamp)
+                .bind(seq)
+                .bind(producer_seq)
+                .bind(&key)
+                .bind(expires_at)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            Ok::<_, sqlx::Error>(())
+        };
+        if let Err(e) = self.timed("save_keyed_message", result).await {
+            error!(
+                "Erreur lors de l'écriture du message de clé {} sur {}: {}",
+                key, topic, e
+            );
+        }
+    }
+
+    // Enregistre un message importé (`POST /import`) via le worker DB, avec son horodatage d'origine.
+    // C'est une restauration, pas une publication : ni diffusion, ni événement, ni métrique. Retourne
+    // `false` si le message est ignoré (sujet invalide, horodatage aberrant, `message_id` déjà connu).
+    // Le `seq` exporté n'est pas repris : le message reçoit le prochain numéro du sujet, comme une publication,
+    // pour que la séquence reste croissante et sans doublon.
+    pub async fn import_message(&self, record: ImportRecord) -> bool {
+        self.mark_publish();
+        if record.topic.is_empty()
+            || is_topic_pattern(&record.topic)
+            || self.config.is_reserved_topic(&record.topic)
+            || record.message_id.is_empty()
+            || !self.import_timestamp_valid(record.timestamp)
+        {
+            return false;
+        }
+        if self.message_id_known(&record.message_id).await {
+            return false;
+        }
+        let seq = {
+            let mut seqs = self.topic_seqs.lock().unwrap();
+            let seq = seqs.entry(record.topic.clone()).or_insert(0);
+            *seq += 1;
+            *seq
+        };
+        let _ = self.db_tx.send(DbCommand::SaveMessage {
+            topic: record.topic,
+            message_id: record.message_id,
+            message: record.message.to_string(),
+            producer: record.producer,
+            timestamp: record.timestamp,
+            seq,
+            producer_seq: None,
+            expires_at: record.expires_at,
+        });
+        true
+    }
+
+    // Horodatage d'un message importé : ancien par nature, il ne peut pas être négatif ni dépasser l'heure du
+    // serveur de plus de `MAX_CLOCK_SKEW_SECS` (ce qui écarte aussi les horodatages en millisecondes).
+    fn import_timestamp_valid(&self, timestamp: f64) -> bool {
+        let max_skew = self.config.max_clock_skew_secs;
+        timestamp.is_finite()
+            && timestamp >= 0.0
+            && (max_skew <= 0.0 || timestamp - current_timestamp() <= max_skew)
+    }
+
+    // Attend que toutes les écritures déjà envoyées au worker DB (consommations comprises) soient validées.
+    // Retourne `false` si le worker est arrêté, si un batch a échoué depuis la barrière précédente
+    // ou si `timeout` expire avant la validation.
+    pub async fn flush_writes(&self, timeout: tokio::time::Duration) -> bool {
+        let (done, committed) = oneshot::channel();
+        if self.db_tx.send(DbCommand::Flush { done }).is_err() {
+            return false;
+        }
+        matches!(tokio::time::timeout(timeout, committed).await, Ok(Ok(true)))
+    }
+
+    // Arrêt du serveur : attend la validation de toutes les écritures encore en file ou dans le
+    // batch courant du worker DB (au plus `SHUTDOWN_FLUSH_TIMEOUT`).
+    pub async fn shutdown(&self) {
+        if self.flush_writes(SHUTDOWN_FLUSH_TIMEOUT).await {
+            info!("Pending database writes flushed");
+        } else {
+            warn!("Pending database writes could not be flushed before shutdown");
+        }
     }
 
     // Sauvegarde une consommation de message et diffuse un événement.
@@ -443,17 +1994,45 @@ impl Broker {
         message_id: String,
         message: serde_json::Value,
     ) {
+        self.queue_consumption(&consumer, topic, message_id, message, current_timestamp());
+    }
+
+    // Enregistre d'un coup les confirmations d'une trame `consumed_batch` et retourne leur nombre.
+    // Les commandes partent ensemble vers le worker DB, qui les valide dans la même transaction
+    // (dans la limite de 500 commandes par lot).
+    pub fn save_consumptions_batch(&self, consumer: &str, acks: Vec<ConsumedAck>) -> usize {
         let timestamp = current_timestamp();
+        let count = acks.len();
+        for ack in acks {
+            self.queue_consumption(consumer, ack.topic, ack.message_id, ack.message, timestamp);
+        }
+        count
+    }
+
+    // Met en file l'écriture d'une consommation et diffuse `new_consumption`.
+    fn queue_consumption(
+        &self,
+        consumer: &str,
+        topic: String,
+        message_id: String,
+        message: serde_json::Value,
+        timestamp: f64,
+    ) {
         let message_json = message.to_string();
 
         // Envoie la commande de sauvegarde au worker DB.
         let _ = self.db_tx.send(DbCommand::SaveConsumption {
-            consumer: consumer.clone(),
+            consumer: consumer.to_string(),
             topic: topic.clone(),
             message_id: message_id.clone(),
             message: message_json,
             timestamp,
         });
+        self.metrics
+            .consumptions_recorded
+            .fetch_add(1, Ordering::Relaxed);
+        self.metrics
+            .record_consumption(&topic, &message_id, timestamp);
 
         // Diffuse l'événement de nouvelle consommation.
         let event = Arc::new(BroadcastEvent {
@@ -470,6 +2049,23 @@ impl Broker {
         let _ = self.event_tx.send(event);
     }
 
+    // SID des connexions abonnées à `topic`, directement, par un motif hiérarchique ou par `*`.
+    pub async fn subscribed_sids(&self, topic: &str) -> Vec<String> {
+        self.subscriptions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, (_, topics, _))| {
+                topics.iter().any(|subscribed| {
+                    subscribed == topic
+                        || subscribed == "*"
+                        || (is_topic_pattern(subscribed) && topic_matches(subscribed, topic))
+                })
+            })
+            .map(|(sid, _)| sid.clone())
+            .collect()
+    }
+
     // Récupère les informations d'un client par son SID depuis le cache en mémoire.
     // C'est une lecture, donc elle est rapide grâce au `RwLock`.
     pub async fn get_client_by_sid(&self, sid: &str) -> Option<(String, Vec<String>, f64)> {
@@ -478,18 +2074,140 @@ impl Broker {
         subs.get(sid).cloned()
     }
 
+    // Met à jour le message retenu d'un sujet (publication avec `retain`). Comme en MQTT, un corps
+    // vide (`null` ou `""`) efface la valeur retenue au lieu de la remplacer.
+    pub async fn retain_message(&self, payload: &PublishRequest) {
+        let mut retained = self.retained.write().await;
+        let empty = payload.message.is_null() || payload.message.as_str() == Some("");
+        if empty {
+            if retained.remove(&payload.topic).is_some() {
+                info!("Retained message cleared on topic {}", payload.topic);
+            }
+            return;
+        }
+        retained.insert(
+            payload.topic.clone(),
+            MessageInfo {
+                topic: payload.topic.clone(),
+                message_id: payload.message_id.clone(),
+                message: payload.message.clone(),
+                producer: payload.producer.clone(),
+                timestamp: payload.timestamp.unwrap_or_else(current_timestamp),
+                seq: payload.seq,
+                producer_seq: payload.producer_seq,
+                expires_at: payload
+                    .ttl_seconds
+                    .zip(payload.timestamp)
+                    .map(|(ttl, timestamp)| timestamp + ttl),
+            },
+        );
+    }
+
+    // Message retenu d'un sujet, à remettre à un nouvel abonné.
+    pub async fn retained_message(&self, topic: &str) -> Option<MessageInfo> {
+        let now = current_timestamp();
+        self.retained
+            .read()
+            .await
+            .get(topic)
+            .filter(|retained| {
+                retained
+                    .expires_at
+                    .is_none_or(|expires_at| expires_at > now)
+            })
+            .cloned()
+    }
+
+    // Consommateurs distincts abonnés à chaque sujet, calculés depuis le cache en mémoire (sans accès DB).
+    // Les abonnements wildcard (`*`, `__all__`) n'entrent dans aucun sujet : ils sont comptés dans une
+    // entrée synthétique `*`, toujours présente et placée en dernier.
+    pub async fn get_topic_stats(&self) -> Vec<TopicStat> {
+        let subs = self.subscriptions.read().await;
+        let mut consumers_by_topic: BTreeMap<&str, HashSet<&str>> = BTreeMap::new();
+        let mut wildcard_consumers = HashSet::new();
+        for (consumer, topics, _) in subs.values() {
+            for topic in topics {
+                if topic == "*" || topic == "__all__" {
+                    wildcard_consumers.insert(consumer.as_str());
+                    continue;
+                }
+                consumers_by_topic
+                    .entry(topic.as_str())
+                    .or_default()
+                    .insert(consumer.as_str());
+            }
+        }
+        let mut stats: Vec<TopicStat> = consumers_by_topic
+            .into_iter()
+            .map(|(topic, consumers)| TopicStat {
+                topic: topic.to_string(),
+                subscriber_count: consumers.len(),
+                wildcard: false,
+            })
+            .collect();
+        stats.push(TopicStat {
+            topic: "*".to_string(),
+            subscriber_count: wildcard_consumers.len(),
+            wildcard: true,
+        });
+        stats
+    }
+
+    // Copie du cache des abonnements (sid -> consommateur, sujets, date de connexion), pour le débogage.
+    pub async fn subscriptions_snapshot(&self) -> HashMap<String, SubscriptionSnapshot> {
+        let subs = self.subscriptions.read().await;
+        subs.iter()
+            .map(|(sid, (consumer, topics, connected_at))| {
+                (
+                    sid.clone(),
+                    SubscriptionSnapshot {
+                        consumer: consumer.clone(),
+                        topics: topics.clone(),
+                        connected_at: *connected_at,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    // Abonnements regroupés par connexion, des plus anciennes aux plus récentes.
+    pub async fn get_subscriptions_grouped(&self) -> Vec<SubscriptionInfo> {
+        let subs = self.subscriptions.read().await;
+        let mut grouped: Vec<SubscriptionInfo> = subs
+            .iter()
+            .map(|(sid, (consumer, topics, connected_at))| SubscriptionInfo {
+                sid: sid.clone(),
+                consumer: consumer.clone(),
+                topics: topics.clone(),
+                connected_at: *connected_at,
+            })
+            .collect();
+        grouped.sort_by(|a, b| {
+            a.connected_at
+                .total_cmp(&b.connected_at)
+                .then_with(|| a.sid.cmp(&b.sid))
+        });
+        grouped
+    }
+
     // Récupère la liste de tous les clients connectés depuis le cache.
     pub async fn get_clients(&self) -> Vec<ClientInfo> {
         let subs = self.subscriptions.read().await;
+        let stale_sids = self.stale_sids.read().unwrap();
+        let client_tags = self.client_tags.read().unwrap();
         // Pré-allocation pour la performance.
         let mut clients = Vec::with_capacity(subs.len());
 
-        for (_, (consumer, topics, connected_at)) in subs.iter() {
+        for (sid, (consumer, topics, connected_at)) in subs.iter() {
+            let stale = stale_sids.contains(sid);
+            let tags = client_tags.get(sid).cloned().unwrap_or_default();
             for topic in topics {
                 clients.push(ClientInfo {
                     consumer: consumer.clone(),
                     topic: topic.clone(),
                     connected_at: *connected_at,
+                    stale,
+                    tags: tags.clone(),
                 });
             }
         }
@@ -499,137 +2217,731 @@ impl Broker {
 
     // Récupère les 100 derniers messages depuis la base de données.
     // C'est une opération de lecture directe sur la DB.
-    pub async fn get_messages(&self) -> Vec<MessageInfo> {
-        let result = sqlx::query_as::<_, (String, String, String, String, f64)>(
-            "SELECT topic, message_id, message, producer, timestamp FROM messages ORDER BY timestamp DESC LIMIT 100"
+    // Une erreur est remontée telle quelle (après nouvelles tentatives si la base est verrouillée)
+    // plutôt que masquée par une liste vide, indiscernable d'une absence de données.
+    pub async fn get_messages(&self) -> Result<Vec<MessageInfo>, sqlx::Error> {
+        self.get_messages_filtered(None, None, DEFAULT_PAGE_SIZE)
+            .await
+    }
+
+    // Récupère les `limit` derniers messages, éventuellement limités à un sujet et, pour la
+    // pagination, à ceux antérieurs au curseur `before`.
+    pub async fn get_messages_filtered(
+        &self,
+        topic: Option<&str>,
+        before: Option<f64>,
+        limit: i64,
+    ) -> Result<Vec<MessageInfo>, sqlx::Error> {
+        // `now` et `limit` sont liés en premier (`$1`, `$2`), puis les filtres présents, dans l'ordre.
+        let sql = format!(
+            "SELECT {} FROM messages WHERE (expires_at IS NULL OR expires_at > $1){}{} ORDER BY timestamp DESC LIMIT $2",
+            MESSAGE_COLUMNS,
+            if topic.is_some() { " AND topic = $3" } else { "" },
+            match (topic, before) {
+                (_, None) => "",
+                (Some(_), Some(_)) => " AND timestamp < $4",
+                (None, Some(_)) => " AND timestamp < $3",
+            }
+        );
+        let now = current_timestamp();
+        let rows = self
+            .read_with_retry("messages", || {
+                let mut query = sqlx::query_as::<_, MessageRow>(&sql).bind(now).bind(limit);
+                if let Some(topic) = topic {
+                    query = query.bind(topic);
+                }
+                if let Some(before) = before {
+                    query = query.bind(before);
+                }
+                query.fetch_all(&self.db)
+            })
+            .await
+            .inspect_err(|e| error!("Erreur lors de la récupération des messages: {}", e))?;
+
+        Ok(rows.into_iter().map(message_info).collect())
+    }
+
+    // Recopie dans `dead_letters` les messages publiés depuis plus de `grace` secondes sans aucune
+    // consommation (même `message_id`) et diffuse un événement `dead_letter` pour chacun. Un message
+    // déjà relevé ne l'est pas une seconde fois, même s'il est consommé entre-temps.
+    async fn scan_dead_letters(
+        db: &DbPool,
+        event_tx: &broadcast::Sender<Arc<BroadcastEvent>>,
+        grace: f64,
+    ) {
+        let now = current_timestamp();
+        let found = sqlx::query_as::<_, (String, String, String, f64)>(
+            "INSERT INTO dead_letters (topic, message_id, message, producer, published_at, detected_at)
+             SELECT m.topic, m.message_id, m.message, m.producer, m.timestamp, $1 FROM messages m
+             WHERE m.timestamp < $2
+               AND m.topic IS NOT NULL AND m.message_id IS NOT NULL
+               AND m.message IS NOT NULL AND m.producer IS NOT NULL
+               AND NOT EXISTS (SELECT 1 FROM consumptions c WHERE c.message_id = m.message_id)
+               AND NOT EXISTS (
+                   SELECT 1 FROM dead_letters d WHERE d.topic = m.topic AND d.message_id = m.message_id
+               )
+             ON CONFLICT (topic, message_id) DO NOTHING
+             RETURNING topic, message_id, producer, published_at",
         )
-            .fetch_all(&self.db)
-            .await;
+        .bind(now)
+        .bind(now - grace)
+        .fetch_all(db)
+        .await;
 
-        match result {
-            Ok(rows) => rows
-                .into_iter()
-                // `filter_map` est utilisé pour traiter les lignes et ignorer celles qui ont un JSON invalide.
-                .filter_map(|(topic, message_id, message_str, producer, timestamp)| {
+        match found {
+            Ok(rows) => {
+                if !rows.is_empty() {
+                    warn!(
+                        "{} messages not consumed after {}s recorded as dead letters",
+                        rows.len(),
+                        grace
+                    );
+                }
+                for (topic, message_id, producer, published_at) in rows {
+                    let _ = event_tx.send(Arc::new(BroadcastEvent {
+                        event_type: "dead_letter".to_string(),
+                        data: serde_json::json!({
+                            "topic": topic,
+                            "message_id": message_id,
+                            "producer": producer,
+                            "published_at": published_at,
+                            "detected_at": now,
+                        }),
+                    }));
+                }
+            }
+            Err(e) => error!("Erreur lors du balayage des lettres mortes: {}", e),
+        }
+    }
+
+    // Lettres mortes les plus récemment relevées, éventuellement limitées à un sujet.
+    pub async fn get_dead_letters(
+        &self,
+        topic: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<DeadLetterInfo>, sqlx::Error> {
+        let sql = format!(
+            "SELECT topic, message_id, message, producer, published_at, detected_at FROM dead_letters{} ORDER BY detected_at DESC, id DESC LIMIT $1",
+            if topic.is_some() { " WHERE topic = $2" } else { "" }
+        );
+        let rows = self
+            .read_with_retry("dead_letters", || {
+                let mut query =
+                    sqlx::query_as::<_, (String, String, String, String, f64, f64)>(&sql)
+                        .bind(limit);
+                if let Some(topic) = topic {
+                    query = query.bind(topic);
+                }
+                query.fetch_all(&self.db)
+            })
+            .await
+            .inspect_err(|e| error!("Erreur lors de la récupération des lettres mortes: {}", e))?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(topic, message_id, message_str, producer, published_at, detected_at)| {
+                    // Comme pour `/messages`, un JSON invalide est conservé avec le texte brut.
                     let message = serde_json::from_str(&message_str).unwrap_or_else(
                         |_| serde_json::json!({"error": "Invalid JSON", "raw": message_str}),
                     );
-
-                    Some(MessageInfo {
+                    DeadLetterInfo {
                         topic,
                         message_id,
                         message,
                         producer,
-                        timestamp,
-                    })
-                })
-                .collect(),
-            Err(e) => {
-                // Retourne un vecteur vide en cas d'erreur.
-                error!("Erreur lors de la récupération des messages: {}", e);
-                Vec::with_capacity(0)
-            }
+                        published_at,
+                        detected_at,
+                    }
+                },
+            )
+            .collect())
+    }
+
+    // Producteur idempotent : un `message_id` déjà publié, en JSON ou en binaire, n'est ni stocké ni
+    // diffusé. Un doublon est signalé par `duplicate_message`.
+    async fn is_duplicate(&self, topic: &str, message_id: &str, producer: &str) -> bool {
+        let duplicate = self.message_id_known(message_id).await;
+        if duplicate {
+            let event = Arc::new(BroadcastEvent {
+                event_type: "duplicate_message".to_string(),
+                data: serde_json::json!({
+                    "topic": topic,
+                    "message_id": message_id,
+                    "producer": producer,
+                    "timestamp": current_timestamp(),
+                }),
+            });
+            let _ = self.event_tx.send(event);
         }
+        duplicate
+    }
+
+    // Indique si `message_id` est déjà connu, et l'enregistre sinon. Absent de la fenêtre, il est cherché
+    // en base si la fenêtre a oublié des identifiants (republication tardive).
+    async fn message_id_known(&self, message_id: &str) -> bool {
+        !self.recent_message_ids.insert(message_id)
+            || (!self.recent_message_ids.is_complete() && self.message_id_stored(message_id).await)
+    }
+
+    // Indique si un message portant `message_id` est déjà stocké. En cas d'erreur de lecture, le message
+    // est considéré comme nouveau : les index uniques empêchent de toute façon son insertion en double.
+    async fn message_id_stored(&self, message_id: &str) -> bool {
+        sqlx::query_as::<_, (i32,)>("SELECT 1 FROM messages WHERE message_id = $1 UNION ALL SELECT 1 FROM messages_binary WHERE message_id = $1")
+            .bind(message_id)
+            .fetch_optional(&self.db)
+            .await
+            .inspect_err(|e| {
+                error!(
+                    "Erreur lors de la recherche du message {}: {}",
+                    message_id, e
+                )
+            })
+            .is_ok_and(|row| row.is_some())
+    }
+
+    // Message stocké portant `message_id` (recherche directe sur l'index unique), même expiré.
+    pub async fn get_message_by_id(
+        &self,
+        message_id: &str,
+    ) -> Result<Option<MessageInfo>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {} FROM messages WHERE message_id = $1",
+            MESSAGE_COLUMNS
+        );
+        let row = self
+            .read_with_retry("message", || {
+                sqlx::query_as::<_, MessageRow>(&sql)
+                    .bind(message_id)
+                    .fetch_optional(&self.db)
+            })
+            .await
+            .inspect_err(|e| {
+                error!(
+                    "Erreur lors de la récupération du message {}: {}",
+                    message_id, e
+                )
+            })?;
+
+        Ok(row.map(message_info))
+    }
+
+    // Récupère au plus `limit` messages d'un sujet publiés après `since`, du plus ancien au plus récent,
+    // pour qu'un consommateur reconnecté rattrape ce qu'il a manqué.
+    pub async fn get_messages_since(
+        &self,
+        topic: &str,
+        since: f64,
+        limit: i64,
+    ) -> Result<Vec<MessageInfo>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {} FROM messages WHERE topic = $1 AND timestamp > $2 AND (expires_at IS NULL OR expires_at > $3) ORDER BY timestamp ASC, id ASC LIMIT $4",
+            MESSAGE_COLUMNS
+        );
+        let now = current_timestamp();
+        let rows = self
+            .read_with_retry("replay", || {
+                sqlx::query_as::<_, MessageRow>(&sql)
+                    .bind(topic)
+                    .bind(since)
+                    .bind(now)
+                    .bind(limit)
+                    .fetch_all(&self.db)
+            })
+            .await
+            .inspect_err(|e| error!("Erreur lors de la relecture du sujet {}: {}", topic, e))?;
+
+        Ok(rows.into_iter().map(message_info).collect())
+    }
+
+    // Supprime tout l'historique d'un sujet (messages JSON et binaires, consommations), sans attendre
+    // la purge périodique, et retourne le nombre de messages supprimés.
+    pub async fn delete_topic_messages(&self, topic: &str) -> Result<u64, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        let consumptions = sqlx::query("DELETE FROM consumptions WHERE topic = $1")
+            .bind(topic)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let messages = sqlx::query("DELETE FROM messages WHERE topic = $1")
+            .bind(topic)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let binary = sqlx::query("DELETE FROM messages_binary WHERE topic = $1")
+            .bind(topic)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let messages = messages + binary;
+        tx.commit().await?;
+        info!(
+            "Topic {} purged on demand: {} messages, {} consumptions",
+            topic, messages, consumptions
+        );
+        Ok(messages)
     }
 
     // Récupère les 100 dernières consommations depuis la base de données.
-    pub async fn get_consumptions(&self) -> Vec<ConsumptionInfo> {
-        let result = sqlx::query_as::<_, (String, String, String, String, f64)>(
-            "SELECT consumer, topic, message_id, message, timestamp FROM consumptions ORDER BY timestamp DESC LIMIT 100"
-        )
-            .fetch_all(&self.db)
-            .await;
+    pub async fn get_consumptions(&self) -> Result<Vec<ConsumptionInfo>, sqlx::Error> {
+        self.get_consumptions_before(None, DEFAULT_PAGE_SIZE).await
+    }
 
-        match result {
-            Ok(rows) => rows
-                .into_iter()
-                .filter_map(|(consumer, topic, message_id, message_str, timestamp)| {
-                    let message = serde_json::from_str(&message_str).unwrap_or_else(
-                        |_| serde_json::json!({"error": "Invalid JSON", "raw": message_str}),
+    // Récupère les `limit` dernières consommations antérieures au curseur `before` (pagination).
+    pub async fn get_consumptions_before(
+        &self,
+        before: Option<f64>,
+        limit: i64,
+    ) -> Result<Vec<ConsumptionInfo>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {} FROM consumptions{} ORDER BY timestamp DESC LIMIT $1",
+            CONSUMPTION_COLUMNS,
+            if before.is_some() {
+                " WHERE timestamp < $2"
+            } else {
+                ""
+            }
+        );
+        let rows = self
+            .read_with_retry("consumptions", || {
+                let mut query = sqlx::query_as::<_, ConsumptionRow>(&sql).bind(limit);
+                if let Some(before) = before {
+                    query = query.bind(before);
+                }
+                query.fetch_all(&self.db)
+            })
+            .await
+            .inspect_err(|e| error!("Erreur lors de la récupération des consommations: {}", e))?;
+
+        Ok(rows.into_iter().map(consumption_info).collect())
+    }
+
+    // Exporte toute une table, une ligne JSON par enregistrement (NDJSON), sans charger le résultat
+    // en mémoire : une tâche lit les lignes en flux et les passe par un canal borné, qui cale la
+    // lecture sur le rythme du client. Le canal se ferme à la fin de l'export ou sur erreur.
+    pub fn export_ndjson(&self, table: ExportTable) -> mpsc::Receiver<Result<String, sqlx::Error>> {
+        let (tx, rx) = mpsc::channel(EXPORT_CHANNEL_CAPACITY);
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            let exported = match table {
+                ExportTable::Messages => {
+                    let sql = format!("SELECT {} FROM messages ORDER BY id", MESSAGE_COLUMNS);
+                    let rows = sqlx::query_as::<_, MessageRow>(&sql)
+                        .fetch(&db)
+                        .map(|row| row.map(message_info));
+                    send_ndjson(rows, &tx).await
+                }
+                ExportTable::Consumptions => {
+                    let sql = format!(
+                        "SELECT {} FROM consumptions ORDER BY id",
+                        CONSUMPTION_COLUMNS
                     );
+                    let rows = sqlx::query_as::<_, ConsumptionRow>(&sql)
+                        .fetch(&db)
+                        .map(|row| row.map(consumption_info));
+                    send_ndjson(rows, &tx).await
+                }
+            };
+            info!("Export of {:?}: {} rows sent", table, exported);
+        });
+        rx
+    }
 
-                    Some(ConsumptionInfo {
-                        consumer,
-                        topic,
-                        message_id,
-                        message,
-                        timestamp,
-                    })
+    // Récupère les événements du journal d'audit, filtrés par date et/ou par type.
+    // Avec `since`, les événements sont retournés dans l'ordre chronologique (pour suivre le flux) ;
+    // sans, ce sont les plus récents, du plus récent au plus ancien.
+    pub async fn get_events(&self, since: Option<f64>, event_type: Option<&str>) -> Vec<EventInfo> {
+        let mut query = sqlx::QueryBuilder::<Backend>::new(
+            "SELECT event_type, data, timestamp FROM events WHERE 1 = 1",
+        );
+        if let Some(since) = since {
+            query.push(" AND timestamp > ").push_bind(since);
+        }
+        if let Some(event_type) = event_type {
+            query.push(" AND event_type = ").push_bind(event_type);
+        }
+        if since.is_some() {
+            query.push(" ORDER BY timestamp ASC LIMIT 1000");
+        } else {
+            query.push(" ORDER BY timestamp DESC LIMIT 100");
+        }
+
+        match self
+            .timed(
+                "events",
+                query
+                    .build_query_as::<(String, String, f64)>()
+                    .fetch_all(&self.db),
+            )
+            .await
+        {
+            Ok(rows) => rows
+                .into_iter()
+                .map(|(event_type, data_str, timestamp)| EventInfo {
+                    event_type,
+                    data: serde_json::from_str(&data_str).unwrap_or_else(
+                        |_| serde_json::json!({"error": "Invalid JSON", "raw": data_str}),
+                    ),
+                    timestamp,
                 })
                 .collect(),
             Err(e) => {
-                error!("Erreur lors de la récupération des consommations: {}", e);
+                error!("Erreur lors de la récupération des événements: {}", e);
                 Vec::with_capacity(0)
             }
         }
     }
 
+    // Estime le retard du consommateur sur chacun de ses sujets (abonnés ou déjà consommés).
+    // La tête de chaque sujet vient des séquences en mémoire ; la position du consommateur est
+    // le plus haut `seq` des messages qu'il a confirmés. Un abonnement `*` couvre tous les sujets connus.
+    pub async fn get_consumer_backlog(&self, consumer: &str) -> Vec<TopicBacklog> {
+        let result = self
+            .timed(
+                "backlog",
+                sqlx::query_as::<_, (String, Option<i64>)>(
+                    "SELECT t.topic, (
+                        SELECT MAX(m.seq) FROM consumptions c
+                        JOIN messages m ON m.topic = c.topic AND m.message_id = c.message_id
+                        WHERE c.consumer = $1 AND c.topic = t.topic
+                    )
+                    FROM (
+                        SELECT topic FROM subscriptions WHERE consumer = $1
+                        UNION SELECT topic FROM consumptions WHERE consumer = $1
+                    ) t",
+                )
+                .bind(consumer)
+                .fetch_all(&self.db),
+            )
+            .await;
+
+        let rows = match result {
+            Ok(rows) => rows,
+            Err(e) => {
+                error!("Erreur lors du calcul du retard de {}: {}", consumer, e);
+                return Vec::with_capacity(0);
+            }
+        };
+
+        let mut acked: HashMap<String, Option<i64>> = rows.into_iter().collect();
+        let heads = self.topic_seqs.lock().unwrap().clone();
+        if acked.remove("*").is_some() {
+            for topic in heads.keys() {
+                acked.entry(topic.clone()).or_insert(None);
+            }
+        }
+
+        let mut backlog: Vec<TopicBacklog> = acked
+            .into_iter()
+            .map(|(topic, acked_seq)| {
+                let head_seq = heads.get(&topic).copied().unwrap_or(0);
+                TopicBacklog {
+                    backlog: (head_seq - acked_seq.unwrap_or(0)).max(0),
+                    topic,
+                    head_seq,
+                    acked_seq,
+                }
+            })
+            .collect();
+        backlog.sort_by(|a, b| a.topic.cmp(&b.topic));
+        backlog
+    }
+
     // Construit l'état du graphe pour le dashboard en agrégeant les données de la DB.
     pub async fn get_graph_state(&self) -> GraphState {
         // `tokio::join!` exécute toutes ces requêtes en parallèle pour de meilleures performances.
-        let (producers_res, consumers_res, topics_res, subscriptions_res, publications_res) = tokio::join!(
-            sqlx::query_as::<_, (String,)>("SELECT DISTINCT producer FROM messages").fetch_all(&self.db),
-            sqlx::query_as::<_, (String,)>("SELECT DISTINCT consumer FROM subscriptions UNION SELECT DISTINCT consumer FROM consumptions").fetch_all(&self.db),
-            sqlx::query_as::<_, (String,)>("SELECT DISTINCT topic FROM messages UNION SELECT DISTINCT topic FROM subscriptions").fetch_all(&self.db),
-            sqlx::query_as::<_, (String, String)>("SELECT topic, consumer FROM subscriptions").fetch_all(&self.db),
-            sqlx::query_as::<_, (String, String)>("SELECT DISTINCT producer, topic FROM messages").fetch_all(&self.db)
-        );
+        // Le lot est chronométré dans son ensemble : c'est la latence perçue par le dashboard.
+        let (producers_res, consumers_res, topics_res, subscriptions_res, publications_res) = self
+            .timed("graph", async {
+                tokio::join!(
+                    sqlx::query_as::<_, (String,)>("SELECT DISTINCT producer FROM messages").fetch_all(&self.db),
+                    sqlx::query_as::<_, (String,)>("SELECT DISTINCT consumer FROM subscriptions UNION SELECT DISTINCT consumer FROM consumptions").fetch_all(&self.db),
+                    sqlx::query_as::<_, (String,)>("SELECT DISTINCT topic FROM messages UNION SELECT DISTINCT topic FROM subscriptions").fetch_all(&self.db),
+                    sqlx::query_as::<_, (String, String)>("SELECT topic, consumer FROM subscriptions").fetch_all(&self.db),
+                    sqlx::query_as::<_, (String, String)>("SELECT DISTINCT producer, topic FROM messages").fetch_all(&self.db)
+                )
+            })
+            .await;
 
-        // Traite les résultats des requêtes pour construire les listes de nœuds.
-        let producers = producers_res
-            .unwrap_or_default()
-            .into_iter()
-            .map(|(p,)| p)
-            .collect();
-        let consumers = consumers_res
-            .unwrap_or_default()
-            .into_iter()
-            .map(|(c,)| c)
-            .collect();
-        let topics = topics_res
-            .unwrap_or_default()
-            .into_iter()
-            .map(|(t,)| t)
-            .collect();
+        build_graph(
+            producers_res,
+            consumers_res,
+            topics_res,
+            subscriptions_res,
+            publications_res,
+        )
+    }
 
-        let mut links = Vec::with_capacity(200);
+    // Topologie « vivante » : seuls les sujets ayant un abonné actuel ou un message publié depuis
+    // `window_secs` apparaissent, avec les producteurs et consommateurs actifs sur cette fenêtre.
+    pub async fn get_live_graph_state(&self, window_secs: u64) -> GraphState {
+        let since = current_timestamp() - window_secs as f64;
+        let (producers_res, consumers_res, topics_res, subscriptions_res, publications_res) = self
+            .timed("graph_live", async {
+                tokio::join!(
+                    sqlx::query_as::<_, (String,)>("SELECT DISTINCT producer FROM messages WHERE timestamp >= $1").bind(since).fetch_all(&self.db),
+                    sqlx::query_as::<_, (String,)>("SELECT DISTINCT consumer FROM subscriptions UNION SELECT DISTINCT consumer FROM consumptions WHERE timestamp >= $1").bind(since).fetch_all(&self.db),
+                    sqlx::query_as::<_, (String,)>("SELECT DISTINCT topic FROM messages WHERE timestamp >= $1 UNION SELECT DISTINCT topic FROM subscriptions").bind(since).fetch_all(&self.db),
+                    sqlx::query_as::<_, (String, String)>("SELECT topic, consumer FROM subscriptions").fetch_all(&self.db),
+                    sqlx::query_as::<_, (String, String)>("SELECT DISTINCT producer, topic FROM messages WHERE timestamp >= $1").bind(since).fetch_all(&self.db)
+                )
+            })
+            .await;
 
-        // Construit les liens de consommation.
-        if let Ok(subs) = subscriptions_res {
-            for (topic, consumer) in subs {
-                links.push(Link {
-                    source: topic,
-                    target: consumer,
-                    link_type: "consume".to_string(),
-                });
-            }
-        }
+        build_graph(
+            producers_res,
+            consumers_res,
+            topics_res,
+            subscriptions_res,
+            publications_res,
+        )
+    }
 
-        // Construit les liens de publication.
-        if let Ok(pubs) = publications_res {
-            for (producer, topic) in pubs {
-                links.push(Link {
-                    source: producer,
-                    target: topic,
-                    link_type: "publish".to_string(),
-                });
+    // Exécute une lecture (chronométrée) en la relançant brièvement si la base est verrouillée
+    // (`SQLITE_BUSY`/`SQLITE_LOCKED` malgré le `busy_timeout`, ex: longue écriture en cours ;
+    // conflit de verrou sous PostgreSQL).
+    async fn read_with_retry<T, F, Fut>(&self, label: &str, query: F) -> Result<T, sqlx::Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.timed(label, query()).await {
+                Err(e) if is_busy(&e) && attempt < READ_RETRIES => {
+                    attempt += 1;
+                    warn!(
+                        "Lecture [{}] bloquée par un verrou, nouvelle tentative {}/{}",
+                        label, attempt, READ_RETRIES
+                    );
+                    tokio::time::sleep(tokio::time::Duration::from_millis(50 * attempt)).await;
+                }
+                result => return result,
             }
         }
+    }
 
-        GraphState {
-            producers,
-            consumers,
-            topics,
-            links,
+    // Exécute une lecture DB et la journalise si elle dépasse `SLOW_QUERY_MS`.
+    // `label` identifie la requête dans les logs (messages, consumptions, events, graph, backlog).
+    async fn timed<T>(&self, label: &str, query: impl Future<Output = T>) -> T {
+        let started = Instant::now();
+        let result = query.await;
+        let elapsed = started.elapsed();
+        let threshold = self.config.slow_query_ms;
+        if threshold > 0 && elapsed.as_millis() >= u128::from(threshold) {
+            warn!(
+                "Requête lente [{}]: {} ms (seuil {} ms)",
+                label,
+                elapsed.as_millis(),
+                threshold
+            );
         }
+        result
+    }
+
+    // Sonde d'écriture pour `DEEP_HEALTH_CHECK` : une insertion minimale annulée aussitôt.
+    // Contrairement à `acquire()`, elle échoue si la base est en lecture seule ou le disque plein.
+    // L'insertion vise `schema_migrations` (version négative, jamais validée) pour ne pas exiger de table dédiée.
+    pub async fn probe_write(&self) -> Result<(), sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        sqlx::query("INSERT INTO schema_migrations (version, name, applied_at) VALUES (-1, 'health_probe', $1)")
+            .bind(current_timestamp())
+            .execute(&mut *tx)
+            .await?;
+        tx.rollback().await
     }
 
     // Getter pour le pool de connexions DB.
-    pub fn db(&self) -> &SqlitePool {
+    pub fn db(&self) -> &DbPool {
         &self.db
     }
 }
 
+// Échec de l'enregistrement d'un schéma de sujet.
+pub enum SchemaError {
+    // Le document n'est pas un schéma JSON valide.
+    Invalid(String),
+    Database(sqlx::Error),
+}
+
+// Compile un schéma JSON ; l'erreur décrit en quoi le document n'est pas un schéma valide.
+fn compile_schema(schema: &serde_json::Value) -> Result<jsonschema::Validator, String> {
+    jsonschema::validator_for(schema).map_err(|e| e.to_string())
+}
+
+// Colonnes lues pour construire un `MessageInfo`, dans l'ordre de `MessageRow`.
+const MESSAGE_COLUMNS: &str =
+    "topic, message_id, message, producer, timestamp, seq, producer_seq, expires_at";
+
+type MessageRow = (
+    String,
+    String,
+    String,
+    String,
+    f64,
+    Option<i64>,
+    Option<i64>,
+    Option<f64>,
+);
+
+// Les lignes au JSON invalide sont conservées, avec le texte brut.
+fn message_info(
+    (topic, message_id, message_str, producer, timestamp, seq, producer_seq, expires_at): MessageRow,
+) -> MessageInfo {
+    let message = serde_json::from_str(&message_str)
+        .unwrap_or_else(|_| serde_json::json!({"error": "Invalid JSON", "raw": message_str}));
+    MessageInfo {
+        topic,
+        message_id,
+        message,
+        producer,
+        timestamp,
+        seq,
+        producer_seq,
+        expires_at,
+    }
+}
+
+// Colonnes lues pour construire un `ConsumptionInfo`, dans l'ordre de `ConsumptionRow`.
+const CONSUMPTION_COLUMNS: &str = "consumer, topic, message_id, message, timestamp";
+
+type ConsumptionRow = (String, String, String, String, f64);
+
+fn consumption_info(
+    (consumer, topic, message_id, message_str, timestamp): ConsumptionRow,
+) -> ConsumptionInfo {
+    let message = serde_json::from_str(&message_str)
+        .unwrap_or_else(|_| serde_json::json!({"error": "Invalid JSON", "raw": message_str}));
+    ConsumptionInfo {
+        consumer,
+        topic,
+        message_id,
+        message,
+        timestamp,
+    }
+}
+
+// Lignes d'export en attente d'envoi au client (`GET /export`).
+const EXPORT_CHANNEL_CAPACITY: usize = 256;
+
+// Transmet chaque enregistrement sérialisé sur une ligne ; s'arrête à la première erreur de lecture
+// (transmise, pour interrompre la réponse) ou quand le client a fermé la connexion.
+async fn send_ndjson<T: serde::Serialize>(
+    rows: impl futures_util::Stream<Item = Result<T, sqlx::Error>>,
+    tx: &mpsc::Sender<Result<String, sqlx::Error>>,
+) -> usize {
+    let mut rows = std::pin::pin!(rows);
+    let mut sent = 0;
+    while let Some(row) = rows.next().await {
+        let line = match row {
+            Ok(row) => match serde_json::to_string(&row) {
+                Ok(json) => json + "\n",
+                Err(e) => {
+                    error!("Export: unserializable row: {}", e);
+                    continue;
+                }
+            },
+            Err(e) => {
+                error!("Export interrupted: {}", e);
+                let _ = tx.send(Err(e)).await;
+                break;
+            }
+        };
+        if tx.send(Ok(line)).await.is_err() {
+            break;
+        }
+        sent += 1;
+    }
+    sent
+}
+
+type GraphRows<T> = Result<Vec<T>, sqlx::Error>;
+
+// Assemble les nœuds et les liens du graphe à partir des résultats de requêtes.
+fn build_graph(
+    producers_res: GraphRows<(String,)>,
+    consumers_res: GraphRows<(String,)>,
+    topics_res: GraphRows<(String,)>,
+    subscriptions_res: GraphRows<(String, String)>,
+    publications_res: GraphRows<(String, String)>,
+) -> GraphState {
+    // Traite les résultats des requêtes pour construire les listes de nœuds.
+    let producers = producers_res
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(p,)| p)
+        .collect();
+    let consumers = consumers_res
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(c,)| c)
+        .collect();
+    let topics = topics_res
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(t,)| t)
+        .collect();
+
+    let mut links = Vec::with_capacity(200);
+
+    // Construit les liens de consommation.
+    if let Ok(subs) = subscriptions_res {
+        for (topic, consumer) in subs {
+            links.push(Link {
+                source: topic,
+                target: consumer,
+                link_type: "consume".to_string(),
+            });
+        }
+    }
+
+    // Construit les liens de publication.
+    if let Ok(pubs) = publications_res {
+        for (producer, topic) in pubs {
+            links.push(Link {
+                source: producer,
+                target: topic,
+                link_type: "publish".to_string(),
+            });
+        }
+    }
+
+    GraphState {
+        producers,
+        consumers,
+        topics,
+        links,
+    }
+}
+
+// Vérifie qu'un horodatage d'origine externe reste dans la fenêtre `max_skew_secs` autour de l'heure du serveur.
+// Un horodatage aberrant casserait l'ordre des messages, la purge par âge et le dashboard.
+pub fn check_clock_skew(timestamp: f64, max_skew_secs: f64) -> Result<(), String> {
+    if !timestamp.is_finite() {
+        return Err("timestamp is not a finite number".to_string());
+    }
+    let skew = timestamp - current_timestamp();
+    if max_skew_secs > 0.0 && skew.abs() > max_skew_secs {
+        return Err(format!(
+            "timestamp is {:.0}s {} server time (max skew {}s)",
+            skew.abs(),
+            if skew > 0.0 { "ahead of" } else { "behind" },
+            max_skew_secs
+        ));
+    }
+    Ok(())
+}
+
+// Indique si l'erreur est un verrou ou un conflit transitoire de la base (voir `Db::is_busy`).
+pub fn is_busy(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(db_err) => db_err
+            .code()
+            .is_some_and(|code| <Backend as Db>::is_busy(&code)),
+        _ => false,
+    }
+}
+
 // Fonction utilitaire pour obtenir le timestamp actuel en secondes (f64).
 fn current_timestamp() -> f64 {
     SystemTime::now()
@@ -637,3 +2949,11 @@ fn current_timestamp() -> f64 {
         .unwrap()
         .as_secs_f64()
 }
+
+// Horodatage actuel en millisecondes, pour les compteurs atomiques.
+fn current_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
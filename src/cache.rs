@@ -21,6 +21,8 @@ pub struct QueryCache {
     pub consumptions: Arc<RwLock<Option<(Vec<ConsumptionInfo>, std::time::Instant)>>>,
     // Cache pour l'état du graphe de dépendances.
     pub graph_state: Arc<RwLock<Option<(GraphState, std::time::Instant)>>>,
+    // Cache pour la vue « topologie vivante » du graphe (`/graph/state?live=true`).
+    pub live_graph_state: Arc<RwLock<Option<(GraphState, std::time::Instant)>>>,
 
     // `ttl` (Time-To-Live): Durée de validité d'une entrée dans le cache.
     // Après cette durée, l'entrée est considérée comme expirée et devra être rafraîchie.
@@ -35,6 +37,7 @@ impl QueryCache {
             messages: Arc::new(RwLock::new(None)),
             consumptions: Arc::new(RwLock::new(None)),
             graph_state: Arc::new(RwLock::new(None)),
+            live_graph_state: Arc::new(RwLock::new(None)),
             // Définit la durée de vie des entrées du cache à partir des secondes fournies.
             ttl: std::time::Duration::from_secs(ttl_secs),
         }
@@ -0,0 +1,612 @@
+// Ce fichier centralise la configuration de l'application.
+// Toutes les valeurs sont lues une seule fois au démarrage depuis les variables d'environnement,
+// puis partagées (via `Arc<Config>`) avec le `Broker`, l'`AppState` et les handlers.
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::warn;
+
+// Stratégie de livraison des messages aux abonnés wildcard (salle `__all__`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WildcardOrdering {
+    // Comportement historique : chaque publication est émise directement dans `__all__`.
+    // Les sujets s'entrelacent dans l'ordre d'arrivée des requêtes, sans garantie par sujet.
+    Broadcast,
+    // Fusion ordonnée : un forwarder dédié garantit que les messages d'un même sujet
+    // sont livrés par `seq` croissant, et entrelace les sujets équitablement (round-robin).
+    PerTopic,
+}
+
+impl FromStr for WildcardOrdering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "broadcast" => Ok(Self::Broadcast),
+            "per_topic" | "per-topic" => Ok(Self::PerTopic),
+            other => Err(format!("stratégie wildcard inconnue: {}", other)),
+        }
+    }
+}
+
+// Origine de l'identité `consumer` utilisée pour les événements d'une connexion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityMode {
+    // Comportement historique : chaque `subscribe`/`consumed` déclare son propre `consumer`.
+    Declared,
+    // L'identité est fixée une fois par connexion (trame `identify` ou premier `subscribe`)
+    // puis réutilisée pour tous les événements suivants ; le champ `consumer` par événement est ignoré.
+    Connection,
+}
+
+impl FromStr for IdentityMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "declared" => Ok(Self::Declared),
+            "connection" => Ok(Self::Connection),
+            other => Err(format!("mode d'identité inconnu: {}", other)),
+        }
+    }
+}
+
+// Politique de rétention des messages appliquée par la purge périodique.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageRetention {
+    // Conserve les `MAX_MESSAGES` plus récents, tous sujets confondus.
+    Global,
+    // Conserve les `MAX_MESSAGES_PER_TOPIC` plus récents de chaque sujet, indépendamment des autres.
+    PerTopic,
+}
+
+impl FromStr for MessageRetention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "global" => Ok(Self::Global),
+            "per_topic" | "per-topic" => Ok(Self::PerTopic),
+            other => Err(format!("politique de rétention inconnue: {}", other)),
+        }
+    }
+}
+
+// Politique de rétention des consommations appliquée par la purge périodique.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsumptionRetention {
+    // Conserve les `MAX_CONSUMPTIONS` plus récentes, tous consommateurs confondus.
+    Global,
+    // Conserve les `MAX_CONSUMPTIONS_PER_CONSUMER` plus récentes de chaque consommateur.
+    PerConsumer,
+}
+
+impl FromStr for ConsumptionRetention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "global" => Ok(Self::Global),
+            "per_consumer" | "per-consumer" => Ok(Self::PerConsumer),
+            other => Err(format!("politique de rétention inconnue: {}", other)),
+        }
+    }
+}
+
+// Politique appliquée quand un consommateur déjà connecté ouvre une nouvelle connexion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    // Les deux connexions coexistent et reçoivent toutes deux les messages (comportement historique).
+    Allow,
+    // La nouvelle connexion évince l'ancienne, qui est fermée.
+    Replace,
+    // La nouvelle connexion est refusée tant que l'ancienne est active.
+    Reject,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "allow" => Ok(Self::Allow),
+            "replace" => Ok(Self::Replace),
+            "reject" => Ok(Self::Reject),
+            other => Err(format!("politique de doublon inconnue: {}", other)),
+        }
+    }
+}
+
+// Comportement d'un sujet à clé unique (`UNIQUE_KEY_TOPICS`) quand la `key` publiée existe déjà.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniqueKeyPolicy {
+    // La publication est refusée (`409 Conflict`).
+    Reject,
+    // Le message existant est remplacé par le nouveau.
+    Replace,
+}
+
+impl FromStr for UniqueKeyPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "replace" => Ok(Self::Replace),
+            other => Err(format!("politique de clé unique inconnue: {}", other)),
+        }
+    }
+}
+
+// Stratégie d'émission Socket.IO utilisée par `publish_handler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EmitStrategy {
+    // Émet vers la salle du sujet et vers `__all__` en parallèle (latence plus faible).
+    Parallel = 0,
+    // Émet séquentiellement, d'abord le sujet puis `__all__` (comportement original).
+    Sequential = 1,
+}
+
+impl EmitStrategy {
+    // Reconstruit la stratégie depuis sa représentation atomique (`AtomicU8` dans `AppState`).
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Sequential,
+            _ => Self::Parallel,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Parallel => "parallel",
+            Self::Sequential => "sequential",
+        }
+    }
+}
+
+impl FromStr for EmitStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "parallel" => Ok(Self::Parallel),
+            "sequential" => Ok(Self::Sequential),
+            other => Err(format!("stratégie d'émission inconnue: {}", other)),
+        }
+    }
+}
+
+// Configuration complète de l'application.
+#[derive(Debug, Clone)]
+pub struct Config {
+    // URL de la base (`DATABASE_URL`), à défaut `sqlite:` suivi de `DATABASE_FILE` (`:memory:` par défaut).
+    pub database_url: String,
+    // Ordre de livraison pour les abonnés wildcard (`WILDCARD_ORDERING`).
+    pub wildcard_ordering: WildcardOrdering,
+    // Stratégie d'émission initiale (`EMIT_STRATEGY`), modifiable ensuite via `/admin/emit-strategy`.
+    pub emit_strategy: EmitStrategy,
+    // Nouvelles tentatives d'une émission Socket.IO vers une connexion au tampon plein (`EMIT_MAX_RETRIES`, 0 = aucune).
+    pub emit_max_retries: u32,
+    // Attente avant la première nouvelle tentative, doublée ensuite (`EMIT_RETRY_BACKOFF_MS`).
+    pub emit_retry_backoff_ms: u64,
+    // Jeton requis (`Authorization: Bearer ...`) sur les routes `/admin/*` (`ADMIN_TOKEN`).
+    // `None` laisse ces routes ouvertes, avec un avertissement au démarrage.
+    pub admin_token: Option<String>,
+    // Clé requise (`Authorization: Bearer ...`) sur `/publish` (`PUBLISH_API_KEY`) ; `None` laisse la route ouverte.
+    pub publish_api_key: Option<String>,
+    // Mot de passe de `POST /dashboard/login` (`DASHBOARD_PASSWORD`) ; `None` laisse la connexion ouverte.
+    pub dashboard_password: Option<String>,
+    // Persiste chaque `BroadcastEvent` dans la table `events` pour audit (`PERSIST_EVENTS`).
+    pub persist_events: bool,
+    // Nombre maximum d'événements conservés par la purge (`MAX_EVENTS`).
+    pub max_events: i64,
+    // Origine de l'identité des consommateurs (`CONSUMER_IDENTITY`).
+    pub consumer_identity: IdentityMode,
+    // Intervalle entre deux passes de `PRAGMA incremental_vacuum` (`VACUUM_INTERVAL_SECS`, 0 = désactivé).
+    pub vacuum_interval_secs: u64,
+    // Nombre de pages libérées par étape (`VACUUM_CHUNK_PAGES`).
+    pub vacuum_chunk_pages: i64,
+    // Pause entre deux étapes d'une même passe (`VACUUM_PAUSE_MS`), pour laisser passer les écritures.
+    pub vacuum_pause_ms: u64,
+    // Durée sans écriture requise avant de lancer ou poursuivre une passe (`VACUUM_IDLE_MS`).
+    pub vacuum_idle_ms: u64,
+    // Politique de rétention des messages (`MESSAGE_RETENTION`).
+    pub message_retention: MessageRetention,
+    // Messages conservés par sujet en mode `per_topic` (`MAX_MESSAGES_PER_TOPIC`).
+    pub max_messages_per_topic: i64,
+    // Politique de rétention des consommations (`CONSUMPTION_RETENTION`).
+    pub consumption_retention: ConsumptionRetention,
+    // Consommations conservées par consommateur en mode `per_consumer` (`MAX_CONSUMPTIONS_PER_CONSUMER`).
+    pub max_consumptions_per_consumer: i64,
+    // Chemin (notation pointée, ex: `meta.route`) du champ du message servant de sujet
+    // lorsqu'une publication n'en précise pas (`TOPIC_FROM_FIELD`).
+    pub topic_from_field: Option<String>,
+    // Délai maximal d'attente d'une réponse client pour `POST /clients/{sid}/request` (`CLIENT_REQUEST_TIMEOUT_MS`).
+    pub client_request_timeout_ms: u64,
+    // Traitement des connexions multiples d'un même consommateur (`DUPLICATE_CONNECTION_POLICY`).
+    pub duplicate_connection_policy: DuplicatePolicy,
+    // Seuil au-delà duquel une lecture DB du broker est journalisée comme lente (`SLOW_QUERY_MS`, 0 = désactivé).
+    pub slow_query_ms: u64,
+    // Affiche les migrations en attente puis quitte, sans toucher à la base (`MIGRATE_DRY_RUN`).
+    pub migrate_dry_run: bool,
+    // Sujet recevant une confirmation (métadonnées seules) pour chaque publication acceptée.
+    // `None` si `PUBLISH_CONFIRMATIONS` n'est pas activé (`PUBLISH_CONFIRMATIONS_TOPIC`, défaut `__publishes__`).
+    pub publish_confirmations_topic: Option<String>,
+    // Nombre maximum de récepteurs par canal de diffusion de topic avant de passer par un relais
+    // (`TOPIC_SUBSCRIBER_CAP`, 0 = illimité).
+    pub topic_subscriber_cap: usize,
+    // `/health` vérifie aussi que la base accepte les écritures (`DEEP_HEALTH_CHECK`).
+    pub deep_health_check: bool,
+    // Diffuse un événement `subscription_confirmed` à la fin de chaque `subscribe` (`SUBSCRIPTION_CONFIRMED_EVENTS`).
+    pub subscription_confirmed_events: bool,
+    // Durée de vie maximale d'une connexion avant fermeture avec `reconnect_suggested`
+    // (`MAX_CONNECTION_LIFETIME_SECS`, 0 = illimitée).
+    pub max_connection_lifetime_secs: u64,
+    // Après chaque purge, oublie les sujets sans message ni abonné (`TOPIC_CLEANUP`).
+    pub topic_cleanup: bool,
+    // Taille maximale d'un corps HTTP, d'une trame WebSocket ou d'un paquet Socket.IO (`MAX_MESSAGE_BYTES`).
+    pub max_message_bytes: usize,
+    // Fichier JSON décrivant le pipeline de transformation des publications (`PIPELINE_CONFIG`).
+    pub pipeline_config: Option<String>,
+    // Attribue aussi un numéro de séquence par producteur à chaque publication (`PRODUCER_SEQUENCES`).
+    pub producer_sequences: bool,
+    // Écart maximal toléré entre un horodatage fourni par un client et l'heure du serveur
+    // (`MAX_CLOCK_SKEW_SECS`, 0 = aucune vérification).
+    pub max_clock_skew_secs: f64,
+    // Sujet de débogage recevant une copie de chaque message publié (`TAP_TOPIC`).
+    pub tap_topic: Option<String>,
+    // Échantillonnage du tap : une copie tous les N messages (`TAP_SAMPLE_EVERY`, 1 = tous).
+    pub tap_sample_every: u64,
+    // Journal d'accès HTTP (méthode, chemin, statut, latence) (`ACCESS_LOG`).
+    pub access_log: bool,
+    // Niveau de journalisation des accès (`ACCESS_LOG_LEVEL`).
+    pub access_log_level: tracing::Level,
+    // Chemins exclus du journal d'accès, sous-chemins compris (`ACCESS_LOG_EXCLUDE`, séparés par des virgules).
+    pub access_log_exclude: Vec<String>,
+    // Délai d'accusé (`consumed`) avant renvoi d'une livraison QoS 1 (`QOS_ACK_TIMEOUT_MS`).
+    pub qos_ack_timeout_ms: u64,
+    // Nombre maximum de renvois d'une livraison QoS 1 avant abandon (`QOS_MAX_REDELIVERIES`).
+    pub qos_max_redeliveries: u32,
+    // Persiste l'état des livraisons QoS 1 par consommateur (`PERSIST_DELIVERY_ACKS`) : un message acquitté
+    // n'est plus relivré, un message non acquitté est renvoyé à la reconnexion.
+    pub persist_delivery_acks: bool,
+    // Taille maximale (en octets, JSON sérialisé) d'un message recopié dans l'événement `new_message` ;
+    // au-delà, le corps est remplacé par un marqueur `elided` (`MAX_EVENT_PAYLOAD_BYTES`, 0 = sans limite).
+    pub max_event_payload_bytes: usize,
+    // Fenêtre d'activité de la vue `/graph/state?live=true` (`GRAPH_LIVE_WINDOW_SECS`).
+    pub graph_live_window_secs: u64,
+    // Intervalle d'enregistrement de l'historique du graphe (`GRAPH_HISTORY_INTERVAL_SECS`, 0 = désactivé).
+    pub graph_history_interval_secs: u64,
+    // Un instantané complet tous les N enregistrements, les autres sont des deltas (`GRAPH_HISTORY_CHECKPOINT_EVERY`).
+    pub graph_history_checkpoint_every: u32,
+    // Supprime au démarrage les abonnements persistés par l'exécution précédente (`PURGE_STALE_SUBSCRIPTIONS`).
+    // Désactivé, ces lignes restent en base jusqu'à la purge périodique.
+    pub purge_stale_subscriptions: bool,
+    // URL de base des brokers pairs vers lesquels relayer les publications (`FEDERATION_PEERS`).
+    pub federation_peers: Vec<String>,
+    // Sujets relayés aux pairs (`FEDERATION_TOPICS`, vide = tous).
+    pub federation_topics: Vec<String>,
+    // Identifiant de ce nœud dans le maillage, pour la détection des boucles (`FEDERATION_NODE_ID`).
+    pub federation_node_id: String,
+    // Nombre maximum de nouvelles tentatives d'envoi à un pair (`FEDERATION_MAX_RETRIES`).
+    pub federation_max_retries: u32,
+    // Clé envoyée en `Authorization: Bearer ...` aux pairs (`FEDERATION_API_KEY`), le `PUBLISH_API_KEY` de ceux-ci.
+    pub federation_api_key: Option<String>,
+    // Validateur HTTP externe consulté avant chaque publication (`VALIDATION_WEBHOOK_URL`).
+    pub validation_webhook_url: Option<String>,
+    // Délai maximal de réponse du validateur (`VALIDATION_WEBHOOK_TIMEOUT_MS`).
+    pub validation_webhook_timeout_ms: u64,
+    // Accepte les publications quand le validateur est injoignable (`VALIDATION_WEBHOOK_FAIL_OPEN`).
+    pub validation_webhook_fail_open: bool,
+    // Sujets n'acceptant qu'un message par `key`, avec leur politique (`UNIQUE_KEY_TOPICS`,
+    // ex: `countries,users=replace` ; politique par défaut `reject`).
+    pub unique_key_topics: HashMap<String, UniqueKeyPolicy>,
+    // Intervalle de l'événement `stats_snapshot` poussé au dashboard (`STATS_SNAPSHOT_INTERVAL_SECS`, 0 = désactivé).
+    pub stats_snapshot_interval_secs: u64,
+    // Destination d'archivage des messages purgés (`ARCHIVE_SINK`, ex: `sqlite:archive.db`, `ndjson:archive.ndjson.gz`).
+    pub archive_sink: Option<String>,
+    // Rejette (`422`) toute publication dont le `message` n'est pas un objet JSON (`REQUIRE_OBJECT_MESSAGES`).
+    pub require_object_messages: bool,
+    // Sujets exigeant un objet JSON même sans `REQUIRE_OBJECT_MESSAGES` (`OBJECT_MESSAGE_TOPICS`).
+    pub object_message_topics: Vec<String>,
+    // Types de contenu acceptés par sujet (`TOPIC_CONTENT_TYPES`) ; un sujet absent accepte tous les types.
+    pub topic_content_types: HashMap<String, Vec<String>>,
+    // Préfixe de toutes les routes, pour un déploiement derrière un reverse proxy (`BASE_PATH`, ex: `/pubsub`).
+    // Normalisé sans `/` final ; vide pour la racine.
+    pub base_path: String,
+    // Port séparé servant seules les routes d'administration (`ADMIN_PORT`) ; `None` les garde sur le port public.
+    // Valeur brute : une valeur qui n'est pas un port empêche le démarrage (voir `main::admin_port`).
+    pub admin_port: Option<String>,
+    // Débit d'envoi maximal par connexion WebSocket, en messages/s (`WS_MAX_SEND_RATE`, 0 = sans limite).
+    pub ws_max_send_rate: u64,
+    // Débit d'envoi maximal par connexion WebSocket, en octets/s (`WS_MAX_SEND_BYTES_PER_SEC`, 0 = sans limite).
+    pub ws_max_send_bytes_per_sec: u64,
+    // Trames en attente tolérées sur une connexion limitée avant abandon des plus anciennes (`WS_SEND_BUFFER`).
+    pub ws_send_buffer: usize,
+    // Nombre maximum de livraisons différées en attente (`MAX_SCHEDULED_MESSAGES`, 0 = sans limite).
+    pub max_scheduled_messages: usize,
+    // Silence (aucune trame reçue) au-delà duquel un client WebSocket est signalé `stale` (`CLIENT_STALE_SECS`, 0 = jamais).
+    pub client_stale_secs: u64,
+    // Silence au-delà duquel un client WebSocket est déconnecté (`CLIENT_EXPIRY_SECS`, 0 = jamais).
+    pub client_expiry_secs: u64,
+    // Nombre maximal de messages renvoyés par `GET /replay` (`REPLAY_MAX_MESSAGES`).
+    pub replay_max_messages: i64,
+    // Capacité du canal global des événements du broker (`EVENT_CHANNEL_CAPACITY`).
+    pub event_channel_capacity: usize,
+    // Capacité de chaque canal de diffusion par sujet, primaire ou relais (`TOPIC_CHANNEL_CAPACITY`).
+    pub topic_channel_capacity: usize,
+    // Intervalle entre deux `Ping` envoyés par le serveur sur `/ws` (`WS_PING_INTERVAL_SECS`, 0 = jamais).
+    pub ws_ping_interval_secs: u64,
+    // Attente maximale du `Pong` répondant à un `Ping` du serveur avant déconnexion (`WS_PONG_TIMEOUT_SECS`).
+    pub ws_pong_timeout_secs: u64,
+    // Fenêtre de regroupement des événements relayés au dashboard en trames `events_batch`
+    // (`EVENT_BATCH_WINDOW_MS`, 0 = un événement Socket.IO par événement du broker).
+    pub event_batch_window_ms: u64,
+    // Refuse les abonnements à des sujets jamais publiés ni déclarés (`REJECT_UNKNOWN_TOPIC_SUBSCRIBE`).
+    pub reject_unknown_topic_subscribe: bool,
+    // Sujets acceptés à l'abonnement avant leur première publication (`KNOWN_TOPICS`, séparés par des virgules).
+    pub known_topics: Vec<String>,
+    // Arrêt du serveur après cette durée sans connexion ni publication (`AUTO_SHUTDOWN_IDLE_SECS`, 0 = jamais).
+    pub auto_shutdown_idle_secs: u64,
+    // Namespaces Socket.IO servis en plus de `/` (`SOCKETIO_NAMESPACES`), cibles possibles d'une publication.
+    pub socketio_namespaces: Vec<String>,
+    // Valide les consommations en attente d'une connexion WebSocket avant de terminer sa fermeture propre
+    // (`WS_FLUSH_ON_DISCONNECT`).
+    pub ws_flush_on_disconnect: bool,
+    // Attente maximale d'une validation demandée par `flush` ou à la déconnexion (`WS_FLUSH_TIMEOUT_MS`).
+    pub ws_flush_timeout_ms: u64,
+    // Accepte l'extension `permessage-deflate` proposée par les clients de `/ws` (`WS_COMPRESSION`).
+    pub ws_compression: bool,
+    // Destination du push des métriques (`METRICS_PUSH_URL`) : `statsd://host:port` ou une URL OTLP/HTTP.
+    pub metrics_push_url: Option<String>,
+    // Intervalle entre deux push des métriques, en secondes (`METRICS_PUSH_INTERVAL`).
+    pub metrics_push_interval_secs: u64,
+    // Préfixes de sujets réservés au serveur (`RESERVED_TOPIC_PREFIXES`, `__` par défaut).
+    pub reserved_topic_prefixes: Vec<String>,
+    // Publications acceptées par seconde et par producteur (`PUBLISH_RATE_PER_SEC`, 0 = sans limite).
+    pub publish_rate_per_sec: f64,
+    // Âge au-delà duquel un message jamais consommé devient une lettre morte (`DEAD_LETTER_GRACE_SECS`, 0 = désactivé).
+    pub dead_letter_grace_secs: u64,
+    // Intervalle du balayage des lettres mortes (`DEAD_LETTER_SCAN_INTERVAL_SECS`).
+    pub dead_letter_scan_interval_secs: u64,
+    // Certificat et clé PEM (`TLS_CERT_PATH`, `TLS_KEY_PATH`) : servis en HTTPS lorsque les deux sont définis.
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+}
+
+impl Config {
+    // Construit la configuration à partir des variables d'environnement.
+    pub fn from_env() -> Self {
+        Self {
+            database_url: env_opt("DATABASE_URL").unwrap_or_else(|| {
+                let file =
+                    std::env::var("DATABASE_FILE").unwrap_or_else(|_| ":memory:".to_string());
+                format!("sqlite:{}", file)
+            }),
+            wildcard_ordering: env_or("WILDCARD_ORDERING", WildcardOrdering::Broadcast),
+            emit_strategy: env_or("EMIT_STRATEGY", EmitStrategy::Parallel),
+            emit_max_retries: env_or("EMIT_MAX_RETRIES", 2),
+            emit_retry_backoff_ms: env_or("EMIT_RETRY_BACKOFF_MS", 20),
+            admin_token: env_opt("ADMIN_TOKEN"),
+            publish_api_key: env_opt("PUBLISH_API_KEY"),
+            dashboard_password: env_opt("DASHBOARD_PASSWORD"),
+            persist_events: env_flag("PERSIST_EVENTS", false),
+            max_events: env_or("MAX_EVENTS", 50_000),
+            consumer_identity: env_or("CONSUMER_IDENTITY", IdentityMode::Declared),
+            vacuum_interval_secs: env_or("VACUUM_INTERVAL_SECS", 600),
+            vacuum_chunk_pages: env_or("VACUUM_CHUNK_PAGES", 256),
+            vacuum_pause_ms: env_or("VACUUM_PAUSE_MS", 100),
+            vacuum_idle_ms: env_or("VACUUM_IDLE_MS", 1000),
+            message_retention: env_or("MESSAGE_RETENTION", MessageRetention::Global),
+            max_messages_per_topic: env_or("MAX_MESSAGES_PER_TOPIC", 1000),
+            consumption_retention: env_or("CONSUMPTION_RETENTION", ConsumptionRetention::Global),
+            max_consumptions_per_consumer: env_or("MAX_CONSUMPTIONS_PER_CONSUMER", 1000),
+            topic_from_field: env_opt("TOPIC_FROM_FIELD"),
+            client_request_timeout_ms: env_or("CLIENT_REQUEST_TIMEOUT_MS", 5000),
+            duplicate_connection_policy: env_or(
+                "DUPLICATE_CONNECTION_POLICY",
+                DuplicatePolicy::Allow,
+            ),
+            slow_query_ms: env_or("SLOW_QUERY_MS", 200),
+            migrate_dry_run: env_flag("MIGRATE_DRY_RUN", false),
+            publish_confirmations_topic: env_flag("PUBLISH_CONFIRMATIONS", false).then(|| {
+                env_opt("PUBLISH_CONFIRMATIONS_TOPIC")
+                    .unwrap_or_else(|| "__publishes__".to_string())
+            }),
+            topic_subscriber_cap: env_or("TOPIC_SUBSCRIBER_CAP", 0),
+            deep_health_check: env_flag("DEEP_HEALTH_CHECK", false),
+            subscription_confirmed_events: env_flag("SUBSCRIPTION_CONFIRMED_EVENTS", false),
+            max_connection_lifetime_secs: env_or("MAX_CONNECTION_LIFETIME_SECS", 0),
+            topic_cleanup: env_flag("TOPIC_CLEANUP", false),
+            max_message_bytes: env_or("MAX_MESSAGE_BYTES", 2 * 1024 * 1024),
+            pipeline_config: env_opt("PIPELINE_CONFIG"),
+            producer_sequences: env_flag("PRODUCER_SEQUENCES", false),
+            max_clock_skew_secs: env_or("MAX_CLOCK_SKEW_SECS", 300.0),
+            tap_topic: env_opt("TAP_TOPIC"),
+            tap_sample_every: env_or("TAP_SAMPLE_EVERY", 1),
+            access_log: env_flag("ACCESS_LOG", true),
+            access_log_level: env_or("ACCESS_LOG_LEVEL", tracing::Level::INFO),
+            access_log_exclude: env_list("ACCESS_LOG_EXCLUDE", &["/health", "/metrics"]),
+            qos_ack_timeout_ms: env_or("QOS_ACK_TIMEOUT_MS", 5000),
+            qos_max_redeliveries: env_or("QOS_MAX_REDELIVERIES", 5),
+            persist_delivery_acks: env_flag("PERSIST_DELIVERY_ACKS", false),
+            max_event_payload_bytes: env_or("MAX_EVENT_PAYLOAD_BYTES", 16 * 1024),
+            graph_live_window_secs: env_or("GRAPH_LIVE_WINDOW_SECS", 300),
+            graph_history_interval_secs: env_or("GRAPH_HISTORY_INTERVAL_SECS", 0),
+            graph_history_checkpoint_every: env_or("GRAPH_HISTORY_CHECKPOINT_EVERY", 20),
+            purge_stale_subscriptions: env_flag("PURGE_STALE_SUBSCRIPTIONS", true),
+            federation_peers: env_list("FEDERATION_PEERS", &[]),
+            federation_topics: env_list("FEDERATION_TOPICS", &[]),
+            federation_node_id: env_opt("FEDERATION_NODE_ID")
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            federation_max_retries: env_or("FEDERATION_MAX_RETRIES", 5),
+            federation_api_key: env_opt("FEDERATION_API_KEY"),
+            validation_webhook_url: env_opt("VALIDATION_WEBHOOK_URL"),
+            validation_webhook_timeout_ms: env_or("VALIDATION_WEBHOOK_TIMEOUT_MS", 2000),
+            validation_webhook_fail_open: env_flag("VALIDATION_WEBHOOK_FAIL_OPEN", false),
+            unique_key_topics: env_unique_keys("UNIQUE_KEY_TOPICS"),
+            stats_snapshot_interval_secs: env_or("STATS_SNAPSHOT_INTERVAL_SECS", 2),
+            archive_sink: env_opt("ARCHIVE_SINK"),
+            require_object_messages: env_flag("REQUIRE_OBJECT_MESSAGES", false),
+            object_message_topics: env_list("OBJECT_MESSAGE_TOPICS", &[]),
+            topic_content_types: env_content_types("TOPIC_CONTENT_TYPES"),
+            base_path: normalize_base_path(&env_opt("BASE_PATH").unwrap_or_default()),
+            admin_port: env_opt("ADMIN_PORT"),
+            ws_max_send_rate: env_or("WS_MAX_SEND_RATE", 0),
+            ws_max_send_bytes_per_sec: env_or("WS_MAX_SEND_BYTES_PER_SEC", 0),
+            ws_send_buffer: env_or("WS_SEND_BUFFER", 10_000),
+            max_scheduled_messages: env_or("MAX_SCHEDULED_MESSAGES", 100_000),
+            client_stale_secs: env_or("CLIENT_STALE_SECS", 0),
+            client_expiry_secs: env_or("CLIENT_EXPIRY_SECS", 0),
+            replay_max_messages: env_or("REPLAY_MAX_MESSAGES", 1000_i64).max(1),
+            event_channel_capacity: env_or("EVENT_CHANNEL_CAPACITY", 1000_usize).max(1),
+            topic_channel_capacity: env_or("TOPIC_CHANNEL_CAPACITY", 1000_usize).max(1),
+            ws_ping_interval_secs: env_or("WS_PING_INTERVAL_SECS", 30),
+            ws_pong_timeout_secs: env_or("WS_PONG_TIMEOUT_SECS", 10),
+            event_batch_window_ms: env_or("EVENT_BATCH_WINDOW_MS", 0),
+            reject_unknown_topic_subscribe: env_flag("REJECT_UNKNOWN_TOPIC_SUBSCRIBE", false),
+            known_topics: env_list("KNOWN_TOPICS", &[]),
+            auto_shutdown_idle_secs: env_or("AUTO_SHUTDOWN_IDLE_SECS", 0),
+            socketio_namespaces: env_list("SOCKETIO_NAMESPACES", &[])
+                .into_iter()
+                .map(|ns| format!("/{}", ns.trim_start_matches('/')))
+                .filter(|ns| ns != "/")
+                .collect(),
+            ws_flush_on_disconnect: env_flag("WS_FLUSH_ON_DISCONNECT", false),
+            ws_flush_timeout_ms: env_or("WS_FLUSH_TIMEOUT_MS", 5000),
+            ws_compression: env_flag("WS_COMPRESSION", true),
+            metrics_push_url: env_opt("METRICS_PUSH_URL"),
+            metrics_push_interval_secs: env_or("METRICS_PUSH_INTERVAL", 10),
+            reserved_topic_prefixes: env_list("RESERVED_TOPIC_PREFIXES", &["__"]),
+            publish_rate_per_sec: env_or("PUBLISH_RATE_PER_SEC", 0.0),
+            dead_letter_grace_secs: env_or("DEAD_LETTER_GRACE_SECS", 0),
+            dead_letter_scan_interval_secs: env_or("DEAD_LETTER_SCAN_INTERVAL_SECS", 60_u64).max(1),
+            tls_cert_path: env_opt("TLS_CERT_PATH"),
+            tls_key_path: env_opt("TLS_KEY_PATH"),
+        }
+    }
+
+    // Sujet réservé au routage interne (`__all__`, salle des abonnés wildcard) ou à l'un des
+    // préfixes de `RESERVED_TOPIC_PREFIXES` : aucune publication n'y est acceptée.
+    pub fn is_reserved_topic(&self, topic: &str) -> bool {
+        topic == "__all__"
+            || self
+                .reserved_topic_prefixes
+                .iter()
+                .any(|prefix| topic.starts_with(prefix.as_str()))
+    }
+
+    // Sujet réservé qu'un client ne peut pas non plus demander à l'abonnement. Les sujets alimentés
+    // par le serveur lui-même restent ouverts aux abonnés.
+    pub fn is_reserved_subscription(&self, topic: &str) -> bool {
+        self.is_reserved_topic(topic) && !self.is_server_topic(topic)
+    }
+
+    // Sujet alimenté par le serveur lui-même : confirmations de publication ou tap de débogage.
+    pub fn is_server_topic(&self, topic: &str) -> bool {
+        self.publish_confirmations_topic.as_deref() == Some(topic)
+            || self.tap_topic.as_deref() == Some(topic)
+    }
+}
+
+// Lit une variable d'environnement et la parse vers `T`.
+// Si la variable est absente, retourne `default`. Si elle est invalide, journalise un avertissement
+// et retourne également `default`, pour ne jamais bloquer le démarrage sur une faute de frappe.
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(raw) => raw.trim().parse().unwrap_or_else(|_| {
+            warn!(
+                "Valeur invalide pour {}: '{}', utilisation de la valeur par défaut",
+                name, raw
+            );
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+// Lit une variable d'environnement optionnelle ; une valeur vide est traitée comme absente.
+fn env_opt(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+// Lit une liste séparée par des virgules ; les éléments vides sont ignorés.
+// Une variable définie mais vide donne une liste vide (ce qui permet de désactiver les valeurs par défaut).
+fn env_list(name: &str, default: &[&str]) -> Vec<String> {
+    match std::env::var(name) {
+        Ok(raw) => raw
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(_) => default.iter().map(|item| item.to_string()).collect(),
+    }
+}
+
+// Lit une liste `sujet[=politique]` de sujets à clé unique ; une politique invalide ignore l'entrée.
+fn env_unique_keys(name: &str) -> HashMap<String, UniqueKeyPolicy> {
+    env_list(name, &[])
+        .into_iter()
+        .filter_map(|entry| match entry.split_once('=') {
+            None => Some((entry, UniqueKeyPolicy::Reject)),
+            Some((topic, policy)) => match policy.trim().parse() {
+                Ok(policy) => Some((topic.trim().to_string(), policy)),
+                Err(e) => {
+                    warn!("Entrée ignorée dans {}: '{}' ({})", name, entry, e);
+                    None
+                }
+            },
+        })
+        .collect()
+}
+
+// Lit `topic=type1|type2,...` : types de contenu acceptés par sujet, normalisés (voir `normalize_content_type`).
+fn env_content_types(name: &str) -> HashMap<String, Vec<String>> {
+    env_list(name, &[])
+        .into_iter()
+        .filter_map(|entry| match entry.split_once('=') {
+            Some((topic, types)) if !topic.trim().is_empty() => Some((
+                topic.trim().to_string(),
+                types.split('|').map(normalize_content_type).collect(),
+            )),
+            _ => {
+                warn!(
+                    "Entrée ignorée dans {}: '{}' (attendu topic=type)",
+                    name, entry
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+// Type de contenu sans paramètres et en minuscules (`Application/JSON; charset=utf-8` -> `application/json`).
+pub fn normalize_content_type(raw: &str) -> String {
+    raw.split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+// Normalise un préfixe de chemin : `/` initial, sans `/` final (`/`, `pubsub/` -> ``, `/pubsub`).
+fn normalize_base_path(raw: &str) -> String {
+    let trimmed = raw.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+// Lit un booléen depuis l'environnement : `1`, `true`, `yes` et `on` (insensibles à la casse) valent `true`.
+fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(raw) => matches!(
+            raw.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        ),
+        Err(_) => default,
+    }
+}
@@ -1,9 +1,48 @@
-// Importe le pool de connexions SQLite de SQLx et le logger `info` de `tracing`.
-use sqlx::sqlite::SqlitePool;
-use tracing::info;
+// Importe les pools de connexions SQLite et PostgreSQL de SQLx et le logger `info` de `tracing`.
+#[cfg(feature = "postgres")]
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+// Moteur de base de données, choisi à la compilation : SQLite par défaut, PostgreSQL avec la
+// fonctionnalité `postgres`. Le reste du serveur ne manipule que `Backend` et `DbPool`.
+#[cfg(not(feature = "postgres"))]
+pub type Backend = sqlx::Sqlite;
+#[cfg(feature = "postgres")]
+pub type Backend = sqlx::Postgres;
+
+// Pool de connexions du moteur compilé.
+pub type DbPool = sqlx::Pool<Backend>;
+
+// URL de la base SQLite en mémoire (`DATABASE_FILE` absent).
+const SQLITE_MEMORY_URL: &str = "sqlite::memory:";
+
+// Ce qui diffère d'un moteur à l'autre : schéma d'URL, migrations, ouverture des connexions et
+// classement des erreurs. Les requêtes sont communes aux deux (paramètres `$N`, `ON CONFLICT`).
+pub trait Db: sqlx::Database {
+    // Schémas acceptés dans `DATABASE_URL`.
+    const SCHEMES: &'static [&'static str];
+    // Migrations propres au moteur, dans l'ordre d'application.
+    const MIGRATIONS: &'static [Migration];
+    // Retourne une ligne si la table `schema_migrations` existe (mode dry-run).
+    const MIGRATIONS_TABLE_QUERY: &'static str;
+    // Le moteur sait récupérer l'espace libéré par petites étapes (`PRAGMA incremental_vacuum`).
+    const INCREMENTAL_VACUUM: bool;
+
+    // Ouvre le pool et applique les réglages de connexion du moteur.
+    async fn connect(url: &str) -> Result<sqlx::Pool<Self>, sqlx::Error>;
+
+    // Ouvre la base en lecture seule pour le dry-run ; `None` si elle n'existe pas encore.
+    async fn connect_read_only(url: &str) -> Result<Option<sqlx::Pool<Self>>, sqlx::Error>;
+
+    // Le code d'erreur désigne un verrou ou un conflit transitoire : l'opération peut être relancée.
+    fn is_busy(code: &str) -> bool;
+}
 
 // Définit une structure pour représenter une migration de base de données.
-struct Migration {
+pub struct Migration {
     // Le numéro de version de la migration, utilisé pour l'ordre d'application.
     version: i32,
     // Un nom descriptif pour la migration.
@@ -12,9 +51,9 @@ struct Migration {
     sql: &'static str,
 }
 
-// Un tableau statique contenant toutes les migrations à appliquer.
+// Un tableau statique contenant toutes les migrations SQLite à appliquer.
 // L'ordre est important.
-const MIGRATIONS: &[Migration] = &[
+const SQLITE_MIGRATIONS: &[Migration] = &[
     Migration {
         version: 1,
         name: "add_message_id_and_producer",
@@ -31,75 +70,301 @@ const MIGRATIONS: &[Migration] = &[
         name: "add_subscriptions_table",
         sql: include_str!("../migrations/003_add_subscriptions_table.sql"),
     },
+    Migration {
+        version: 4,
+        name: "add_topic_sequence",
+        sql: include_str!("../migrations/004_add_topic_sequence.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "add_events_table",
+        sql: include_str!("../migrations/005_add_events_table.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "add_producer_sequence",
+        sql: include_str!("../migrations/006_add_producer_sequence.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "add_message_key",
+        sql: include_str!("../migrations/007_add_message_key.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "add_scheduled_messages",
+        sql: include_str!("../migrations/008_add_scheduled_messages.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "add_consumption_foreign_key",
+        sql: include_str!("../migrations/009_add_consumption_foreign_key.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "add_deliveries",
+        sql: include_str!("../migrations/010_add_deliveries.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "add_graph_snapshots",
+        sql: include_str!("../migrations/011_add_graph_snapshots.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "add_message_expiry",
+        sql: include_str!("../migrations/012_add_message_expiry.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "add_subscription_tags",
+        sql: include_str!("../migrations/013_add_subscription_tags.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "add_topic_schemas",
+        sql: include_str!("../migrations/014_add_topic_schemas.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "add_messages_binary",
+        sql: include_str!("../migrations/015_add_messages_binary.sql"),
+    },
+    Migration {
+        version: 16,
+        name: "add_dead_letters",
+        sql: include_str!("../migrations/016_add_dead_letters.sql"),
+    },
+    Migration {
+        version: 17,
+        name: "unique_message_id",
+        sql: include_str!("../migrations/017_unique_message_id.sql"),
+    },
+    Migration {
+        version: 18,
+        name: "nullable_consumption_message_ref",
+        sql: include_str!("../migrations/018_nullable_consumption_message_ref.sql"),
+    },
+    Migration {
+        version: 19,
+        name: "unique_binary_message_id",
+        sql: include_str!("../migrations/019_unique_binary_message_id.sql"),
+    },
 ];
 
-// Fonction asynchrone pour initialiser la base de données.
-// Retourne un `Result` avec le pool de connexions ou une erreur.
-pub async fn init_database(db_file: &str) -> Result<SqlitePool, Box<dyn std::error::Error>> {
-    // Se connecte à la base de données SQLite. `?mode=rwc` signifie "read-write-create" : ouvre en lecture/écriture, et crée le fichier s'il n'existe pas.
-    let pool = SqlitePool::connect(&format!("sqlite:{}?mode=rwc", db_file)).await?;
+// Migrations PostgreSQL : le schéma initial reprend l'état final des migrations SQLite ci-dessus.
+// Une évolution ultérieure du schéma ajoute une migration de chaque côté.
+#[cfg(feature = "postgres")]
+const POSTGRES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: include_str!("../migrations/postgres/001_initial_schema.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "add_messages_binary",
+        sql: include_str!("../migrations/postgres/002_add_messages_binary.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "add_dead_letters",
+        sql: include_str!("../migrations/postgres/003_add_dead_letters.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "unique_message_id",
+        sql: include_str!("../migrations/postgres/004_unique_message_id.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "nullable_consumption_message_ref",
+        sql: include_str!("../migrations/postgres/005_nullable_consumption_message_ref.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "unique_binary_message_id",
+        sql: include_str!("../migrations/postgres/006_unique_binary_message_id.sql"),
+    },
+];
 
-    // --- Configuration SQLite optimisée pour les performances en écriture et lecture --- 
-    // `PRAGMA` sont des commandes spécifiques à SQLite pour modifier son comportement.
+impl Db for sqlx::Sqlite {
+    const SCHEMES: &'static [&'static str] = &["sqlite"];
+    const MIGRATIONS: &'static [Migration] = SQLITE_MIGRATIONS;
+    const MIGRATIONS_TABLE_QUERY: &'static str =
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'";
+    const INCREMENTAL_VACUUM: bool = true;
 
-    // `journal_mode = WAL` (Write-Ahead Logging) : Améliore la concurrence en permettant aux lecteurs de ne pas être bloqués par les écritures.
-    sqlx::query("PRAGMA journal_mode = WAL")
-        .execute(&pool)
-        .await?;
-    // `synchronous = NORMAL` : Moins de `fsync` sur le disque, plus rapide mais avec un risque minime de corruption en cas de crash système.
-    sqlx::query("PRAGMA synchronous = NORMAL")
-        .execute(&pool)
-        .await?;
-    // `cache_size = -128000` : Alloue 128MB de RAM pour le cache de pages, réduisant les I/O disque.
-    sqlx::query("PRAGMA cache_size = -128000")
-        .execute(&pool)
-        .await?;
-    // `temp_store = MEMORY` : Utilise la RAM pour les tables temporaires.
-    sqlx::query("PRAGMA temp_store = MEMORY")
-        .execute(&pool)
-        .await?;
-    // `mmap_size` : Utilise le mapping mémoire pour accéder aux données, peut être plus rapide.
-    sqlx::query("PRAGMA mmap_size = 536870912")
-        .execute(&pool)
-        .await?;
-    // `page_size` : Augmente la taille des pages pour de meilleures performances sur les SSD.
-    sqlx::query("PRAGMA page_size = 8192")
-        .execute(&pool)
-        .await?;
-    // `auto_vacuum = INCREMENTAL` : Permet de récupérer l'espace non utilisé.
-    sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
-        .execute(&pool)
-        .await?;
-    // `busy_timeout` : Attend 5s si la base est verrouillée avant de retourner une erreur.
-    sqlx::query("PRAGMA busy_timeout = 5000")
-        .execute(&pool)
-        .await?;
-    // `wal_autocheckpoint` : Déclenche un checkpoint du WAL automatiquement.
-    sqlx::query("PRAGMA wal_autocheckpoint = 1000")
-        .execute(&pool)
-        .await?;
-    // Force un checkpoint au démarrage pour nettoyer le fichier WAL.
-    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
-        .execute(&pool)
-        .await
-        .ok();
+    async fn connect(url: &str) -> Result<SqlitePool, sqlx::Error> {
+        // `foreign_keys` est un réglage propre à chaque connexion : il est fixé dans les options de connexion
+        // pour s'appliquer à toutes les connexions du pool (cascade consommations -> messages, migration 009).
+        let pool = if url == SQLITE_MEMORY_URL {
+            warn!("DATABASE_FILE is not set: using an in-memory database, intended for testing only (all data is lost on exit)");
+            // Une base `:memory:` appartient à la connexion qui l'ouvre : sans cache partagé, chaque connexion
+            // du pool aurait sa propre base vide et les lectures ne verraient pas les écritures du worker DB.
+            // Toutes les connexions partagent donc la même base (`shared_cache`), qui disparaît avec sa
+            // dernière connexion : une connexion reste ouverte en permanence (ni expiration ni recyclage).
+            let options = SqliteConnectOptions::from_str(url)?
+                .shared_cache(true)
+                .foreign_keys(true);
+            SqlitePoolOptions::new()
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(options)
+                .await?
+        } else {
+            // `create_if_missing` équivaut à `?mode=rwc` : ouvre en lecture/écriture et crée le fichier s'il n'existe pas.
+            let options = SqliteConnectOptions::from_str(url)?
+                .create_if_missing(true)
+                .foreign_keys(true);
+            SqlitePool::connect_with(options).await?
+        };
+
+        // --- Configuration SQLite optimisée pour les performances en écriture et lecture ---
+        // `PRAGMA` sont des commandes spécifiques à SQLite pour modifier son comportement.
+
+        // `journal_mode = WAL` (Write-Ahead Logging) : Améliore la concurrence en permettant aux lecteurs de ne pas être bloqués par les écritures.
+        sqlx::query("PRAGMA journal_mode = WAL")
+            .execute(&pool)
+            .await?;
+        // `synchronous = NORMAL` : Moins de `fsync` sur le disque, plus rapide mais avec un risque minime de corruption en cas de crash système.
+        sqlx::query("PRAGMA synchronous = NORMAL")
+            .execute(&pool)
+            .await?;
+        // `cache_size = -128000` : Alloue 128MB de RAM pour le cache de pages, réduisant les I/O disque.
+        sqlx::query("PRAGMA cache_size = -128000")
+            .execute(&pool)
+            .await?;
+        // `temp_store = MEMORY` : Utilise la RAM pour les tables temporaires.
+        sqlx::query("PRAGMA temp_store = MEMORY")
+            .execute(&pool)
+            .await?;
+        // `mmap_size` : Utilise le mapping mémoire pour accéder aux données, peut être plus rapide.
+        sqlx::query("PRAGMA mmap_size = 536870912")
+            .execute(&pool)
+            .await?;
+        // `page_size` : Augmente la taille des pages pour de meilleures performances sur les SSD.
+        sqlx::query("PRAGMA page_size = 8192")
+            .execute(&pool)
+            .await?;
+        // `auto_vacuum = INCREMENTAL` : Permet de récupérer l'espace non utilisé.
+        sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
+            .execute(&pool)
+            .await?;
+        // `busy_timeout` : Attend 5s si la base est verrouillée avant de retourner une erreur.
+        sqlx::query("PRAGMA busy_timeout = 5000")
+            .execute(&pool)
+            .await?;
+        // `wal_autocheckpoint` : Déclenche un checkpoint du WAL automatiquement.
+        sqlx::query("PRAGMA wal_autocheckpoint = 1000")
+            .execute(&pool)
+            .await?;
+        // Force un checkpoint au démarrage pour nettoyer le fichier WAL.
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&pool)
+            .await
+            .ok();
+        // Limite le nombre de connexions pour éviter de surcharger la base de données.
+        sqlx::query("PRAGMA max_connections = 10")
+            .execute(&pool)
+            .await
+            .ok();
+
+        Ok(pool)
+    }
+
+    async fn connect_read_only(url: &str) -> Result<Option<SqlitePool>, sqlx::Error> {
+        // Une base en mémoire ou absente n'a encore aucune migration appliquée.
+        // La base est ouverte en lecture seule (aucun PRAGMA, aucune création de fichier ni de table).
+        let options = SqliteConnectOptions::from_str(url)?.read_only(true);
+        if url == SQLITE_MEMORY_URL || !options.get_filename().exists() {
+            return Ok(None);
+        }
+        SqlitePool::connect_with(options).await.map(Some)
+    }
+
+    // `SQLITE_BUSY` = 5, `SQLITE_LOCKED` = 6 (code primaire = 8 bits de poids faible).
+    fn is_busy(code: &str) -> bool {
+        code.parse::<i32>()
+            .is_ok_and(|code| matches!(code & 0xff, 5 | 6))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl Db for sqlx::Postgres {
+    const SCHEMES: &'static [&'static str] = &["postgres", "postgresql"];
+    const MIGRATIONS: &'static [Migration] = POSTGRES_MIGRATIONS;
+    const MIGRATIONS_TABLE_QUERY: &'static str =
+        "SELECT CAST(tablename AS TEXT) FROM pg_tables WHERE schemaname = current_schema() AND tablename = 'schema_migrations'";
+    // L'autovacuum de PostgreSQL s'en charge.
+    const INCREMENTAL_VACUUM: bool = false;
+
+    async fn connect(url: &str) -> Result<sqlx::PgPool, sqlx::Error> {
+        PgPoolOptions::new().max_connections(10).connect(url).await
+    }
+
+    async fn connect_read_only(url: &str) -> Result<Option<sqlx::PgPool>, sqlx::Error> {
+        // Toute transaction de la session est en lecture seule : le dry-run ne peut rien modifier.
+        let options =
+            PgConnectOptions::from_str(url)?.options([("default_transaction_read_only", "on")]);
+        PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map(Some)
+    }
+
+    // `serialization_failure`, `deadlock_detected`, `lock_not_available`.
+    fn is_busy(code: &str) -> bool {
+        matches!(code, "40001" | "40P01" | "55P03")
+    }
+}
+
+// Vérifie que `DATABASE_URL` désigne le moteur compilé dans ce binaire.
+fn check_scheme(url: &str) -> Result<(), String> {
+    let scheme = url.split_once(':').map_or(url, |(scheme, _)| scheme);
+    if <Backend as Db>::SCHEMES.contains(&scheme) {
+        return Ok(());
+    }
+    let hint = if cfg!(feature = "postgres") {
+        ""
+    } else {
+        " (PostgreSQL requires building with `--features postgres`)"
+    };
+    Err(format!(
+        "DATABASE_URL scheme '{}' is not supported by this build, expected {}{}",
+        scheme,
+        <Backend as Db>::SCHEMES.join(" or "),
+        hint
+    ))
+}
+
+// Fonction asynchrone pour initialiser la base de données désignée par `DATABASE_URL`.
+// Retourne un `Result` avec le pool de connexions ou une erreur.
+pub async fn init_database(url: &str) -> Result<DbPool, Box<dyn std::error::Error>> {
+    check_scheme(url)?;
+    let pool = <Backend as Db>::connect(url).await?;
 
     // Crée la table pour suivre les migrations déjà appliquées, si elle n'existe pas.
     sqlx::query(
         "CREATE TABLE IF NOT EXISTS schema_migrations (
             version INTEGER PRIMARY KEY,
             name TEXT NOT NULL,
-            applied_at REAL NOT NULL
+            applied_at DOUBLE PRECISION NOT NULL
         )",
     )
     .execute(&pool)
     .await?;
 
+    // Vérifie que l'historique enregistré correspond aux migrations connues de ce binaire.
+    verify_applied_migrations(&applied_migrations(&pool).await?);
+
     // Boucle sur toutes les migrations définies.
-    for migration in MIGRATIONS {
+    for migration in <Backend as Db>::MIGRATIONS {
         // Vérifie si la migration a déjà été appliquée en consultant la table `schema_migrations`.
         let applied =
-            sqlx::query_as::<_, (i32,)>("SELECT version FROM schema_migrations WHERE version = ?")
+            sqlx::query_as::<_, (i32,)>("SELECT version FROM schema_migrations WHERE version = $1")
                 .bind(migration.version)
                 .fetch_optional(&pool)
                 .await?
@@ -118,7 +383,7 @@ pub async fn init_database(db_file: &str) -> Result<SqlitePool, Box<dyn std::err
 
             // Enregistre la migration comme étant appliquée dans la table `schema_migrations`.
             sqlx::query(
-                "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?, ?, ?)",
+                "INSERT INTO schema_migrations (version, name, applied_at) VALUES ($1, $2, $3)",
             )
             .bind(migration.version)
             .bind(migration.name)
@@ -136,7 +401,7 @@ pub async fn init_database(db_file: &str) -> Result<SqlitePool, Box<dyn std::err
     }
 
     // `ANALYZE` collecte des statistiques sur les tables et les index.
-    // L'optimiseur de requêtes de SQLite utilise ces statistiques pour choisir les meilleurs plans d'exécution.
+    // L'optimiseur de requêtes utilise ces statistiques pour choisir les meilleurs plans d'exécution.
     sqlx::query("ANALYZE").execute(&pool).await?;
 
     info!("Database initialization complete");
@@ -145,6 +410,75 @@ pub async fn init_database(db_file: &str) -> Result<SqlitePool, Box<dyn std::err
     Ok(pool)
 }
 
+// Mode `MIGRATE_DRY_RUN` : indique quelles migrations seraient appliquées, sans modifier la base.
+pub async fn dry_run_migrations(url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    check_scheme(url)?;
+    let applied = match <Backend as Db>::connect_read_only(url).await? {
+        Some(pool) => {
+            let has_table = sqlx::query_as::<_, (String,)>(<Backend as Db>::MIGRATIONS_TABLE_QUERY)
+                .fetch_optional(&pool)
+                .await?
+                .is_some();
+            let applied = if has_table {
+                applied_migrations(&pool).await?
+            } else {
+                HashMap::new()
+            };
+            pool.close().await;
+            applied
+        }
+        None => HashMap::new(),
+    };
+
+    verify_applied_migrations(&applied);
+
+    let pending: Vec<&Migration> = <Backend as Db>::MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains_key(&m.version))
+        .collect();
+    if pending.is_empty() {
+        info!("[dry-run] Database schema is up to date, no migration to apply");
+    } else {
+        for migration in pending {
+            info!(
+                "[dry-run] Would run migration {}: {}",
+                migration.version, migration.name
+            );
+        }
+    }
+    Ok(())
+}
+
+// Lit l'historique des migrations appliquées (`version` -> `name`).
+async fn applied_migrations(pool: &DbPool) -> Result<HashMap<i32, String>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (i32, String)>("SELECT version, name FROM schema_migrations")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().collect())
+}
+
+// Signale toute divergence entre l'historique de la base et les migrations du binaire :
+// un nom différent pour une même version (schéma altéré ou binaire incompatible),
+// ou une version inconnue (base migrée par un binaire plus récent).
+fn verify_applied_migrations(applied: &HashMap<i32, String>) {
+    for (version, name) in applied {
+        match <Backend as Db>::MIGRATIONS
+            .iter()
+            .find(|m| m.version == *version)
+        {
+            Some(migration) if migration.name != name => warn!(
+                "Migration drift: version {} recorded as '{}' but this binary defines '{}'",
+                version, name, migration.name
+            ),
+            Some(_) => {}
+            None => warn!(
+                "Migration drift: version {} ('{}') is applied but unknown to this binary",
+                version, name
+            ),
+        }
+    }
+}
+
 // Fonction utilitaire pour obtenir le timestamp actuel en secondes (f64).
 fn current_timestamp() -> f64 {
     std::time::SystemTime::now()
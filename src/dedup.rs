@@ -0,0 +1,63 @@
+// Déduplication des publications par `message_id` (producteurs idempotents).
+//
+// Un producteur qui republie après une coupure réseau renvoie le même `message_id` : la seconde
+// publication ne doit être ni stockée ni diffusée. L'écriture en base passant par le worker par lots,
+// la détection se fait en mémoire, sur les `message_id` les plus récents (rechargés au démarrage).
+// Une fois des `message_id` oubliés, un identifiant absent de la fenêtre est cherché en base avant
+// d'être accepté ; l'index unique `messages(message_id)` garantit en plus qu'aucun doublon n'atteint la table.
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+// Nombre de `message_id` retenus ; au-delà, les plus anciens sont oubliés.
+pub const DEDUP_WINDOW: usize = 100_000;
+
+#[derive(Default)]
+struct Window {
+    ids: HashSet<String>,
+    // Ordre d'arrivée, pour oublier les plus anciens.
+    order: VecDeque<String>,
+    // Des `message_id` stockés ne sont pas (ou plus) dans la fenêtre.
+    incomplete: bool,
+}
+
+#[derive(Default)]
+pub struct RecentMessageIds {
+    // `std::sync::Mutex` : sections courtes, sans `await`.
+    window: Mutex<Window>,
+}
+
+impl RecentMessageIds {
+    // Retient `message_id` ; retourne `false` s'il l'était déjà (doublon).
+    pub fn insert(&self, message_id: &str) -> bool {
+        let mut window = self.window.lock().unwrap();
+        if !window.ids.insert(message_id.to_string()) {
+            return false;
+        }
+        window.order.push_back(message_id.to_string());
+        if window.order.len() > DEDUP_WINDOW {
+            if let Some(oldest) = window.order.pop_front() {
+                window.ids.remove(&oldest);
+                window.incomplete = true;
+            }
+        }
+        true
+    }
+
+    // Indique que la base peut contenir des `message_id` absents de la fenêtre (rechargement partiel).
+    pub fn mark_incomplete(&self) {
+        self.window.lock().unwrap().incomplete = true;
+    }
+
+    // La fenêtre contient tous les `message_id` stockés : un identifiant absent est forcément nouveau.
+    pub fn is_complete(&self) -> bool {
+        !self.window.lock().unwrap().incomplete
+    }
+
+    // Oublie `message_id`, pour une publication finalement refusée après sa réservation.
+    pub fn remove(&self, message_id: &str) {
+        let mut window = self.window.lock().unwrap();
+        if window.ids.remove(message_id) {
+            window.order.retain(|id| id != message_id);
+        }
+    }
+}
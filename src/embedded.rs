@@ -1,11 +1,14 @@
 // Importations nécessaires depuis Axum pour la gestion des réponses HTTP,
 // et `RustEmbed` pour l'intégration des fichiers statiques.
+use crate::app_state::AppState;
 use axum::{
     body::Body,
+    extract::State,
     http::{header, StatusCode, Uri},
     response::{IntoResponse, Response},
 };
 use rust_embed::RustEmbed;
+use socketioxide::SocketIo;
 use std::borrow::Cow;
 
 // La macro `#[derive(RustEmbed)]` transforme cette structure en un conteneur pour les fichiers embarqués.
@@ -36,15 +39,34 @@ fn build_response(content: Cow<'static, [u8]>, path: &str) -> Response {
         .unwrap()
 }
 
+// Prépare une page HTML pour `BASE_PATH` : expose le préfixe aux scripts (`window.BASE_PATH`,
+// utilisé pour les appels `fetch`, Socket.IO et les redirections) et préfixe les ressources `/static/`.
+fn with_base_path(html: &[u8], base_path: &str) -> Cow<'static, [u8]> {
+    let html = String::from_utf8_lossy(html);
+    let script = format!(
+        "<head>\n    <script>window.BASE_PATH = {};</script>",
+        serde_json::Value::from(base_path)
+    );
+    let html = html
+        .replacen("<head>", &script, 1)
+        .replace("src=\"/static/", &format!("src=\"{}/static/", base_path))
+        .replace("href=\"/static/", &format!("href=\"{}/static/", base_path));
+    Cow::Owned(html.into_bytes())
+}
+
 // Le handler Axum principal pour servir les fichiers embarqués.
 // Il reçoit l'URI demandée et retourne la réponse appropriée.
-pub async fn serve_embedded(uri: Uri) -> Response {
+// Derrière `BASE_PATH`, l'URI reçue est déjà débarrassée du préfixe ; seules les URL renvoyées
+// au navigateur (redirection, liens des pages) doivent le porter.
+pub async fn serve_embedded(State((state, _)): State<(AppState, SocketIo)>, uri: Uri) -> Response {
+    let base_path = state.config.base_path.as_str();
     // Nettoie le chemin de l'URI.
     let path = uri.path().trim_start_matches('/');
 
     // Cas spécial : si la requête est pour la racine, on redirige vers `login.html`.
     if path.is_empty() || path == "/" {
-        return axum::response::Redirect::permanent("/login.html").into_response();
+        return axum::response::Redirect::permanent(&format!("{}/login.html", base_path))
+            .into_response();
     }
 
     // Tente de trouver le fichier dans les `HtmlAssets` (fichiers .html).
@@ -53,7 +75,7 @@ pub async fn serve_embedded(uri: Uri) -> Response {
         // C'est une recherche en mémoire, donc très rapide.
         if let Some(content) = <HtmlAssets as RustEmbed>::get(path) {
             // `content.data` est un `Cow<'static, [u8]>` contenant les octets du fichier.
-            return build_response(content.data, path);
+            return build_response(with_base_path(&content.data, base_path), path);
         }
     }
 
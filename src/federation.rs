@@ -0,0 +1,181 @@
+// Fédération : relais des publications vers d'autres brokers (`FEDERATION_PEERS`).
+//
+// - Chaque publication acceptée sur un sujet de `FEDERATION_TOPICS` (tous si la liste est vide)
+//   est renvoyée au `/publish` de chaque pair, dans l'ordre, par une tâche dédiée au pair.
+// - Anti-boucle : le message relayé porte dans `via` la liste des nœuds (`FEDERATION_NODE_ID`) qu'il a
+//   traversés. Un nœud ne relaie jamais un message qui contient déjà son identifiant, et ignore
+//   un message (`producer`, `message_id`) déjà vu, si bien qu'un maillage quelconque converge.
+// - Échec réseau ou 5xx : nouvelle tentative avec attente exponentielle, au plus `FEDERATION_MAX_RETRIES`
+//   fois ; un 4xx est définitif (le pair refuse le message) et le message est abandonné.
+// - Authentification : `FEDERATION_API_KEY`, si définie, est envoyée en `Authorization: Bearer` à chaque pair,
+//   qui la vérifie comme son `PUBLISH_API_KEY`.
+use crate::config::Config;
+use crate::models::PublishRequest;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+// Nombre de publications mémorisées pour la détection des doublons.
+const SEEN_CAPACITY: usize = 100_000;
+// Attente avant la première nouvelle tentative, doublée ensuite jusqu'à `MAX_BACKOFF`.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Ensemble borné des publications déjà vues : les plus anciennes sont oubliées en premier.
+#[derive(Default)]
+struct SeenSet {
+    keys: HashSet<(String, String)>,
+    order: VecDeque<(String, String)>,
+}
+
+impl SeenSet {
+    // Retourne `false` si la clé était déjà présente.
+    fn insert(&mut self, key: (String, String)) -> bool {
+        if !self.keys.insert(key.clone()) {
+            return false;
+        }
+        self.order.push_back(key);
+        if self.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+pub struct Federation {
+    node_id: String,
+    // Sujets relayés ; vide = tous.
+    topics: Vec<String>,
+    // Une file par pair, consommée par sa tâche d'envoi.
+    peers: Vec<mpsc::UnboundedSender<Arc<PublishRequest>>>,
+    // `std::sync::Mutex` : section critique courte, sans `await`.
+    seen: Mutex<SeenSet>,
+}
+
+impl Federation {
+    // Lance une tâche d'envoi par pair. Retourne `None` si aucun pair n'est configuré.
+    pub fn start(config: &Config) -> Option<Arc<Self>> {
+        if config.federation_peers.is_empty() {
+            return None;
+        }
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("client HTTP de fédération");
+
+        let peers = config
+            .federation_peers
+            .iter()
+            .map(|peer| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                let url = format!("{}/publish", peer.trim_end_matches('/'));
+                tokio::spawn(forward_to_peer(
+                    client.clone(),
+                    url,
+                    rx,
+                    config.federation_api_key.clone(),
+                    config.federation_max_retries,
+                ));
+                tx
+            })
+            .collect();
+
+        info!(
+            "Federation enabled as node {} with {} peer(s)",
+            config.federation_node_id,
+            config.federation_peers.len()
+        );
+        Some(Arc::new(Self {
+            node_id: config.federation_node_id.clone(),
+            topics: config.federation_topics.clone(),
+            peers,
+            seen: Mutex::new(SeenSet::default()),
+        }))
+    }
+
+    // Enregistre une publication entrante. Retourne `false` si elle a déjà traversé ce nœud
+    // (boucle) ou déjà été vue : elle doit alors être ignorée.
+    // Les publications locales sont mémorisées mais toujours admises : un producteur peut
+    // republier le même `message_id`, seul un relais déjà vu est un doublon.
+    pub fn admit(&self, payload: &PublishRequest) -> bool {
+        if payload.via.contains(&self.node_id) {
+            return false;
+        }
+        let first_seen = self
+            .seen
+            .lock()
+            .unwrap()
+            .insert((payload.producer.clone(), payload.message_id.clone()));
+        first_seen || payload.via.is_empty()
+    }
+
+    // Met la publication en file vers chaque pair, si son sujet est fédéré.
+    pub fn forward(&self, payload: &PublishRequest, timestamp: f64) {
+        if !self.topics.is_empty() && !self.topics.contains(&payload.topic) {
+            return;
+        }
+        let mut relayed = payload.clone();
+        relayed.via.push(self.node_id.clone());
+        // L'horodatage d'origine est conservé d'un nœud à l'autre.
+        relayed.timestamp = Some(timestamp);
+        let relayed = Arc::new(relayed);
+        for peer in &self.peers {
+            let _ = peer.send(relayed.clone());
+        }
+    }
+}
+
+// Envoie les publications d'une file à un pair, dans l'ordre, avec nouvelles tentatives.
+async fn forward_to_peer(
+    client: reqwest::Client,
+    url: String,
+    mut rx: mpsc::UnboundedReceiver<Arc<PublishRequest>>,
+    api_key: Option<String>,
+    max_retries: u32,
+) {
+    while let Some(payload) = rx.recv().await {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            let mut request = client.post(&url).json(payload.as_ref());
+            if let Some(key) = &api_key {
+                request = request.bearer_auth(key);
+            }
+            let retryable = match request.send().await {
+                Ok(response) if response.status().is_success() => break,
+                Ok(response) => {
+                    let status = response.status();
+                    warn!(
+                        "Federation: {} answered {} for message {}",
+                        url, status, payload.message_id
+                    );
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => {
+                    warn!(
+                        "Federation: {} unreachable for message {}: {}",
+                        url, payload.message_id, e
+                    );
+                    true
+                }
+            };
+            if !retryable || attempt >= max_retries {
+                warn!(
+                    "Federation: message {} dropped for {} after {} attempt(s)",
+                    payload.message_id,
+                    url,
+                    attempt + 1
+                );
+                break;
+            }
+            attempt += 1;
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
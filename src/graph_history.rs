@@ -0,0 +1,224 @@
+// Historique de l'état du graphe (`GRAPH_HISTORY_INTERVAL_SECS`), pour l'analyse des tendances.
+//
+// Les instantanés successifs se ressemblent beaucoup : seul un sur `GRAPH_HISTORY_CHECKPOINT_EVERY`
+// est stocké en entier (point de reprise), les autres ne contiennent que la différence avec le
+// précédent (nœuds et liens ajoutés ou retirés). Un intervalle sans changement n'écrit rien.
+// L'état à une date est reconstruit en rejouant les deltas depuis le point de reprise le plus proche.
+use crate::app_state::AppState;
+use crate::database::DbPool;
+use crate::models::{GraphState, Link};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+// Durée de conservation de l'historique, alignée sur celle des messages.
+const MAX_AGE_SECS: f64 = 24.0 * 3600.0;
+
+// Différence entre deux instantanés consécutifs.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GraphDelta {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    producers_added: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    producers_removed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    consumers_added: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    consumers_removed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    topics_added: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    topics_removed: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links_added: Vec<Link>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    links_removed: Vec<Link>,
+}
+
+impl GraphDelta {
+    fn between(previous: &GraphState, next: &GraphState) -> Self {
+        let (producers_added, producers_removed) = diff(&previous.producers, &next.producers);
+        let (consumers_added, consumers_removed) = diff(&previous.consumers, &next.consumers);
+        let (topics_added, topics_removed) = diff(&previous.topics, &next.topics);
+        let (links_added, links_removed) = diff(&previous.links, &next.links);
+        Self {
+            producers_added,
+            producers_removed,
+            consumers_added,
+            consumers_removed,
+            topics_added,
+            topics_removed,
+            links_added,
+            links_removed,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.producers_added.is_empty()
+            && self.producers_removed.is_empty()
+            && self.consumers_added.is_empty()
+            && self.consumers_removed.is_empty()
+            && self.topics_added.is_empty()
+            && self.topics_removed.is_empty()
+            && self.links_added.is_empty()
+            && self.links_removed.is_empty()
+    }
+
+    fn apply(self, graph: &mut GraphState) {
+        patch(
+            &mut graph.producers,
+            self.producers_added,
+            &self.producers_removed,
+        );
+        patch(
+            &mut graph.consumers,
+            self.consumers_added,
+            &self.consumers_removed,
+        );
+        patch(&mut graph.topics, self.topics_added, &self.topics_removed);
+        patch(&mut graph.links, self.links_added, &self.links_removed);
+    }
+}
+
+// Éléments ajoutés puis retirés entre deux listes (ordre de `next` conservé).
+fn diff<T: Clone + Eq + Hash>(previous: &[T], next: &[T]) -> (Vec<T>, Vec<T>) {
+    let before: HashSet<&T> = previous.iter().collect();
+    let after: HashSet<&T> = next.iter().collect();
+    let added = next
+        .iter()
+        .filter(|item| !before.contains(item))
+        .cloned()
+        .collect();
+    let removed = previous
+        .iter()
+        .filter(|item| !after.contains(item))
+        .cloned()
+        .collect();
+    (added, removed)
+}
+
+fn patch<T: Eq + Hash>(items: &mut Vec<T>, added: Vec<T>, removed: &[T]) {
+    let removed: HashSet<&T> = removed.iter().collect();
+    items.retain(|item| !removed.contains(item));
+    items.extend(added);
+}
+
+// Enregistre l'état du graphe toutes les `GRAPH_HISTORY_INTERVAL_SECS` secondes.
+pub fn spawn_graph_history(state: AppState) {
+    let every = Duration::from_secs(state.config.graph_history_interval_secs);
+    let checkpoint_every = state.config.graph_history_checkpoint_every.max(1);
+    tokio::spawn(async move {
+        let db = state.broker.db().clone();
+        let mut interval = tokio::time::interval(every);
+        // Le premier instantané de chaque exécution est un point de reprise.
+        let mut previous: Option<GraphState> = None;
+        let mut since_checkpoint = 0u32;
+        loop {
+            interval.tick().await;
+            let graph = state.broker.get_graph_state().await;
+            let (kind, data) = match &previous {
+                Some(previous) if since_checkpoint < checkpoint_every => {
+                    let delta = GraphDelta::between(previous, &graph);
+                    if delta.is_empty() {
+                        continue;
+                    }
+                    ("delta", serde_json::to_string(&delta))
+                }
+                _ => ("full", serde_json::to_string(&graph)),
+            };
+            let Ok(data) = data else { continue };
+            let taken_at = current_timestamp();
+            if let Err(e) = store(&db, taken_at, kind, &data).await {
+                error!(
+                    "Erreur lors de l'enregistrement de l'historique du graphe: {}",
+                    e
+                );
+                continue;
+            }
+            if kind == "full" {
+                since_checkpoint = 0;
+                purge(&db, taken_at - MAX_AGE_SECS).await;
+            }
+            since_checkpoint += 1;
+            previous = Some(graph);
+        }
+    });
+    info!(
+        "Graph history enabled (every {}s, full snapshot every {})",
+        every.as_secs(),
+        checkpoint_every
+    );
+}
+
+async fn store(db: &DbPool, taken_at: f64, kind: &str, data: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO graph_snapshots (taken_at, kind, data) VALUES ($1, $2, $3)")
+        .bind(taken_at)
+        .bind(kind)
+        .bind(data)
+        .execute(db)
+        .await
+        .map(|_| ())
+}
+
+// Supprime l'historique plus ancien que `cutoff`, en gardant le point de reprise dont dépendent
+// les deltas encore conservés.
+async fn purge(db: &DbPool, cutoff: f64) {
+    let result = sqlx::query(
+        "DELETE FROM graph_snapshots WHERE id < (
+            SELECT MAX(id) FROM graph_snapshots WHERE kind = 'full' AND taken_at < $1
+        )",
+    )
+    .bind(cutoff)
+    .execute(db)
+    .await;
+    match result {
+        Ok(done) if done.rows_affected() > 0 => {
+            info!(
+                "Graph history: {} old snapshot(s) purged",
+                done.rows_affected()
+            )
+        }
+        Ok(_) => {}
+        Err(e) => error!("Erreur lors de la purge de l'historique du graphe: {}", e),
+    }
+}
+
+// Reconstruit l'état du graphe tel qu'enregistré à la date `at` (secondes Unix) :
+// dernier point de reprise antérieur, puis deltas successifs jusqu'à `at`.
+pub async fn graph_at(db: &DbPool, at: f64) -> Result<Option<GraphState>, sqlx::Error> {
+    let Some((checkpoint_id, data)) = sqlx::query_as::<_, (i64, String)>(
+        "SELECT id, data FROM graph_snapshots WHERE kind = 'full' AND taken_at <= $1 ORDER BY id DESC LIMIT 1",
+    )
+    .bind(at)
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let Ok(mut graph) = serde_json::from_str::<GraphState>(&data) else {
+        return Ok(None);
+    };
+
+    let deltas = sqlx::query_as::<_, (String,)>(
+        "SELECT data FROM graph_snapshots WHERE kind = 'delta' AND id > $1 AND taken_at <= $2 ORDER BY id",
+    )
+    .bind(checkpoint_id)
+    .bind(at)
+    .fetch_all(db)
+    .await?;
+    for (data,) in deltas {
+        if let Ok(delta) = serde_json::from_str::<GraphDelta>(&data) {
+            delta.apply(&mut graph);
+        }
+    }
+    Ok(Some(graph))
+}
+
+fn current_timestamp() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64()
+}
@@ -0,0 +1,97 @@
+// Groupes de consommateurs : livraison répartie entre les membres d'un même groupe.
+//
+// Un abonnement avec `group` ne reçoit plus tous les messages du sujet (diffusion) : chaque message
+// va à un seul membre de chaque groupe, choisi à tour de rôle. Les abonnés sans groupe continuent
+// de tout recevoir. Les groupes ne portent que sur des sujets concrets (ni motif, ni `*`).
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct Group {
+    // SID des membres, dans l'ordre d'arrivée.
+    members: Vec<String>,
+    // Position du prochain membre servi.
+    next: usize,
+}
+
+#[derive(Default)]
+pub struct ConsumerGroups {
+    // sujet -> groupe -> membres. `std::sync::Mutex` : sections courtes, sans `await`.
+    topics: Mutex<HashMap<String, HashMap<String, Group>>>,
+}
+
+impl ConsumerGroups {
+    // Ajoute `sid` au groupe `group` du sujet, en le retirant d'un autre groupe du même sujet.
+    pub fn join(&self, topic: &str, group: &str, sid: &str) {
+        self.leave(sid, topic);
+        let mut topics = self.topics.lock().unwrap();
+        let members = &mut topics
+            .entry(topic.to_string())
+            .or_default()
+            .entry(group.to_string())
+            .or_default()
+            .members;
+        members.push(sid.to_string());
+    }
+
+    // Retire `sid` du groupe dont il est membre sur ce sujet ; retourne `false` s'il n'en avait pas.
+    pub fn leave(&self, sid: &str, topic: &str) -> bool {
+        let mut topics = self.topics.lock().unwrap();
+        let Some(groups) = topics.get_mut(topic) else {
+            return false;
+        };
+        let mut left = false;
+        groups.retain(|_, group| {
+            if let Some(position) = group.members.iter().position(|member| member == sid) {
+                group.members.remove(position);
+                if group.next > position {
+                    group.next -= 1;
+                }
+                left = true;
+            }
+            !group.members.is_empty()
+        });
+        if groups.is_empty() {
+            topics.remove(topic);
+        }
+        left
+    }
+
+    // Retire `sid` de tous ses groupes (déconnexion).
+    pub fn leave_all(&self, sid: &str) {
+        let mut topics = self.topics.lock().unwrap();
+        topics.retain(|_, groups| {
+            groups.retain(|_, group| {
+                if let Some(position) = group.members.iter().position(|member| member == sid) {
+                    group.members.remove(position);
+                    if group.next > position {
+                        group.next -= 1;
+                    }
+                }
+                !group.members.is_empty()
+            });
+            !groups.is_empty()
+        });
+    }
+
+    // Pour chaque groupe du sujet, ses membres à partir de celui dont c'est le tour ; le tour avance
+    // d'un cran. Les suivants servent de repli si l'envoi au premier échoue.
+    pub fn rotation(&self, topic: &str) -> Vec<Vec<String>> {
+        let mut topics = self.topics.lock().unwrap();
+        let Some(groups) = topics.get_mut(topic) else {
+            return Vec::new();
+        };
+        groups
+            .values_mut()
+            .map(|group| {
+                let start = group.next % group.members.len();
+                group.next = (start + 1) % group.members.len();
+                group.members[start..]
+                    .iter()
+                    .chain(&group.members[..start])
+                    .cloned()
+                    .collect()
+            })
+            .collect()
+    }
+}
@@ -1,18 +1,57 @@
 // Importations de l'état de l'application, des modèles de données, et des composants Axum/Socket.IO.
 use crate::app_state::AppState;
+use crate::auth::constant_time_eq;
+use crate::broker::{check_clock_skew, is_busy, DuplicateKey, Saved, Scheduled, SchemaError};
+use crate::config::{normalize_content_type, Config, EmitStrategy};
+use crate::graph_history::graph_at;
+use crate::metrics;
 use crate::models::{
-    ClientInfo, ConsumptionInfo, GraphState, HealthStatus, MessageInfo, PublishRequest,
+    BinaryPublishQuery, ClientInfo, ConsumptionsQuery, DashboardLoginRequest, DeadLetterInfo,
+    DeadLettersQuery, DebugState, DeleteMessagesQuery, EmitStrategyRequest, EventInfo,
+    EventStreamQuery, EventsQuery, ExportQuery, GraphHistoryQuery, GraphQuery, GraphState,
+    HealthStatus, ImportRecord, ImportSummary, MessageInfo, MessagesQuery, PublishConfirmation,
+    PublishRequest, ReplayPage, ReplayQuery, SubscriptionInfo, TopicBacklog, TopicDeliveryStats,
+    TopicLatency, TopicStat, UnsubscribeRequest, DEFAULT_CONTENT_TYPE,
 };
-use axum::{extract::State, http::StatusCode, Json};
-use socketioxide::SocketIo;
+use crate::ndjson::LineSplitter;
+use crate::registry::RequestError;
+use crate::topic_pattern::is_topic_pattern;
+use crate::validator::Verdict;
+use crate::websocket::deliver_binary;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures_util::{Stream, StreamExt};
+use serde::Serialize;
+use socketioxide::{extract::SocketRef, SendError, SocketError, SocketIo};
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::{atomic::Ordering, Arc};
-use std::time::SystemTime;
-use tokio::sync::RwLock;
-use tracing::info;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info, warn};
+
+// Plafond du paramètre `limit` de `GET /messages` et `GET /consumptions`.
+const MAX_MESSAGES_LIMIT: i64 = 1000;
+// En-tête portant le curseur de la page suivante (horodatage de la ligne la plus ancienne retournée).
+const NEXT_BEFORE_HEADER: &str = "x-next-before";
+// `POST /import` attend la validation en base tous les `IMPORT_FLUSH_EVERY` messages, pour que la file
+// du worker DB ne grossisse pas au rythme de la lecture du corps.
+const IMPORT_FLUSH_EVERY: usize = 1000;
+const IMPORT_FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+// Type de contenu d'une publication binaire sans en-tête `Content-Type`.
+const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
 
 // --- Fonction générique de mise en cache (Cache-Aside Pattern) ---
 // Cette fonction est une abstraction puissante pour gérer la logique de cache.
-async fn get_or_fetch_cached<T, F, Fut>(
+async fn get_or_fetch_cached<T, E, F, Fut>(
     // Le champ de cache spécifique à utiliser (ex: `state.cache.messages`).
     cache: &Arc<RwLock<Option<(T, std::time::Instant)>>>,
     // La durée de vie (TTL) du cache.
@@ -21,14 +60,14 @@ async fn get_or_fetch_cached<T, F, Fut>(
     fetch_fn: F,
     // Un booléen pour activer/désactiver le cache.
     dashboard_enabled: bool,
-) -> T
+) -> Result<T, E>
 where
     // `T` est le type de données à mettre en cache (ex: `Vec<MessageInfo>`).
     T: Clone,
     // `F` est une fonction qui ne prend pas d'arguments et retourne un Future.
     F: FnOnce() -> Fut,
-    // `Fut` est un Future qui se résout en une valeur de type `T`.
-    Fut: std::future::Future<Output = T>,
+    // `Fut` est un Future qui se résout en `T`, ou en erreur (jamais mise en cache).
+    Fut: std::future::Future<Output = Result<T, E>>,
 {
     // Si le dashboard (et donc le cache) est désactivé, on récupère toujours les données fraîches.
     if !dashboard_enabled {
@@ -43,14 +82,15 @@ where
             // Si le cache contient des données et qu'elles n'ont pas expiré...
             if timestamp.elapsed() < ttl {
                 // ... on retourne une copie des données du cache. C'est un "cache hit".
-                return data.clone();
+                return Ok(data.clone());
             }
         }
     } // Le verrou en lecture est libéré ici.
 
     // --- Étape 2: Récupérer les données (Cache Miss) ---
     // Si on arrive ici, c'est un "cache miss" (données absentes ou expirées).
-    let data = fetch_fn().await;
+    // Une erreur est remontée sans toucher au cache.
+    let data = fetch_fn().await?;
 
     // --- Étape 3: Mettre à jour le cache (partie écriture) ---
     {
@@ -61,7 +101,7 @@ where
     } // Le verrou en écriture est libéré ici.
 
     // On retourne les données fraîchement récupérées.
-    data
+    Ok(data)
 }
 
 // Handler pour la publication de messages via une requête POST sur `/publish`.
@@ -69,131 +109,1090 @@ pub async fn publish_handler(
     // `State` est un extracteur Axum qui injecte l'état partagé de l'application.
     State((state, io)): State<(AppState, SocketIo)>,
     // `Json` est un extracteur qui désérialise le corps de la requête en une structure Rust.
-    Json(payload): Json<PublishRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    // Validation simple des données d'entrée.
-    if payload.topic.is_empty() || payload.message_id.is_empty() || payload.producer.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+    Json(mut payload): Json<PublishRequest>,
+) -> Result<Json<serde_json::Value>, PublishRejection> {
+    // Routage par contenu : un sujet explicite est toujours prioritaire ; sinon il est
+    // extrait du message au chemin `TOPIC_FROM_FIELD`, s'il est configuré.
+    if payload.topic.is_empty() {
+        if let Some(path) = state.config.topic_from_field.as_deref() {
+            payload.topic = topic_from_message(&payload.message, path).ok_or_else(|| {
+                warn!("Publish rejected: no string topic at '{}' in message", path);
+                StatusCode::BAD_REQUEST
+            })?;
+        }
+    }
+
+    validate_publish(&state.config, &payload)?;
+    check_publish_rate(&state, &payload.producer)?;
+
+    // Fédération : un message revenu à ce nœud ou déjà relayé est acquitté sans être republié.
+    if let Some(federation) = &state.federation {
+        if !federation.admit(&payload) {
+            return Ok(Json(serde_json::json!({"status": "duplicate"})));
+        }
+    }
+
+    // Pipeline de transformation configuré (masquage, horodatage, réécriture de sujet, validation).
+    if let Err(reason) = state
+        .broker
+        .transform(&mut payload.topic, &mut payload.message)
+    {
+        warn!("Publish rejected by pipeline: {}", reason);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY.into());
+    }
+    // Un sujet réécrit par le pipeline est soumis aux mêmes règles que le sujet d'origine.
+    validate_topic(&state.config, &payload.topic)?;
+
+    // Validateur externe (`VALIDATION_WEBHOOK_URL`) : peut refuser ou remplacer le message.
+    if let Some(validator) = &state.validator {
+        match validator.validate(&payload).await {
+            Verdict::Accepted(Some(message)) => payload.message = message,
+            Verdict::Accepted(None) => {}
+            Verdict::Rejected(reason) => {
+                warn!(
+                    "Publish of {} rejected by validator: {}",
+                    payload.message_id, reason
+                );
+                return Err(PublishRejection::with_reason(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    reason,
+                ));
+            }
+            Verdict::Unavailable => {
+                return Err(PublishRejection::with_reason(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "validator unavailable".to_string(),
+                ));
+            }
+        }
+    }
+
+    // Schéma JSON du sujet (`POST /topics/{topic}/schema`), vérifié sur le message final.
+    if let Err(reason) = state.broker.check_schema(&payload.topic, &payload.message) {
+        warn!(
+            "Publish of {} rejected by topic schema: {}",
+            payload.message_id, reason
+        );
+        return Err(PublishRejection::with_reason(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            reason,
+        ));
+    }
+
+    // Sujet à clé unique : la clé est obligatoire.
+    if state.config.unique_key_topics.contains_key(&payload.topic) && payload.key.is_none() {
+        warn!(
+            "Publish rejected: topic {} requires a message key",
+            payload.topic
+        );
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+
+    // Livraison différée : le message est mis en attente jusqu'à `deliver_at`.
+    if let Some(deliver_at) = payload.deliver_at {
+        if !deliver_at.is_finite() {
+            warn!("Publish rejected: deliver_at is not a finite number");
+            return Err(StatusCode::BAD_REQUEST.into());
+        }
+        if deliver_at > current_timestamp() {
+            return match state.broker.schedule_message(&payload, deliver_at).await {
+                Ok(Scheduled::Accepted) => {
+                    info!(
+                        "Message {} to topic {} scheduled for {}",
+                        payload.message_id, payload.topic, deliver_at
+                    );
+                    Ok(Json(
+                        serde_json::json!({"status": "scheduled", "deliver_at": deliver_at}),
+                    ))
+                }
+                Ok(Scheduled::Duplicate) => Err(StatusCode::CONFLICT.into()),
+                // File des livraisons différées pleine (`MAX_SCHEDULED_MESSAGES`).
+                Ok(Scheduled::Full) => {
+                    warn!(
+                        "Publish rejected: scheduled backlog full, message {} not scheduled",
+                        payload.message_id
+                    );
+                    Err(StatusCode::INSUFFICIENT_STORAGE.into())
+                }
+                Err(e) => {
+                    error!("Scheduling of message {} failed: {}", payload.message_id, e);
+                    Err(StatusCode::INTERNAL_SERVER_ERROR.into())
+                }
+            };
+        }
+        payload.deliver_at = None;
+    }
+
+    Ok(Json(deliver_message(&state, &io, payload).await?))
+}
+
+// Gère `POST /publish/binary` : le corps est le contenu brut, le sujet, l'identifiant et le
+// producteur passent en paramètres de requête. Le contenu est stocké tel quel et livré aux
+// connexions WebSocket abonnées sous forme de trame binaire.
+pub async fn publish_binary_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<BinaryPublishQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<serde_json::Value>, PublishRejection> {
+    if query.message_id.is_empty() || query.producer.is_empty() {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    validate_topic(&state.config, &query.topic)?;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or(BINARY_CONTENT_TYPE);
+    check_content_type(&state.config, &query.topic, content_type)?;
+    check_publish_rate(&state, &query.producer)?;
+
+    info!(
+        "Publishing binary message {} ({} bytes) to topic {} by {}",
+        query.message_id,
+        body.len(),
+        query.topic,
+        query.producer
+    );
+    let size = body.len();
+    // Republication d'un `message_id` déjà publié : acquittée sans nouvelle diffusion.
+    let Some(timestamp) = state.broker.publish_binary(&query, &body).await else {
+        info!(
+            "Binary message {} already published, deduplicated",
+            query.message_id
+        );
+        return Ok(Json(
+            serde_json::json!({"status": "ok", "deduplicated": true}),
+        ));
+    };
+    let delivered = deliver_binary(&state, &query, body, timestamp).await;
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "size": size,
+        "delivered": delivered,
+    })))
+}
+
+// Budget de publication du producteur (`PUBLISH_RATE_PER_SEC`) : `429` avec `Retry-After` s'il est épuisé.
+fn check_publish_rate(state: &AppState, producer: &str) -> Result<(), PublishRejection> {
+    if let Some(limiter) = &state.publish_limiter {
+        if let Err(retry_after) = limiter.check(producer) {
+            warn!(
+                "Publish rejected: producer {} exceeded its rate limit",
+                producer
+            );
+            return Err(PublishRejection::rate_limited(retry_after));
+        }
+    }
+    Ok(())
+}
+
+// Refus d'une publication : un statut seul, ou accompagné d'une raison (`{"error": ...}`)
+// lorsqu'elle vient du validateur externe ou d'un schéma de sujet (aussi pour leur enregistrement).
+// Un refus pour dépassement de débit porte en plus l'en-tête `Retry-After`.
+pub struct PublishRejection {
+    status: StatusCode,
+    reason: Option<String>,
+    retry_after: Option<Duration>,
+}
+
+impl PublishRejection {
+    fn with_reason(status: StatusCode, reason: String) -> Self {
+        Self {
+            status,
+            reason: Some(reason),
+            retry_after: None,
+        }
+    }
+
+    fn rate_limited(retry_after: Duration) -> Self {
+        Self {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            reason: None,
+            retry_after: Some(retry_after),
+        }
+    }
+}
+
+impl From<StatusCode> for PublishRejection {
+    fn from(status: StatusCode) -> Self {
+        Self {
+            status,
+            reason: None,
+            retry_after: None,
+        }
+    }
+}
+
+impl IntoResponse for PublishRejection {
+    fn into_response(self) -> Response {
+        let mut response = match self.reason {
+            Some(reason) => {
+                (self.status, Json(serde_json::json!({"error": reason}))).into_response()
+            }
+            None => self.status.into_response(),
+        };
+        // `Retry-After` en secondes entières, arrondies au-dessus.
+        if let Some(retry_after) = self.retry_after {
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
+}
 
+// Enregistre et diffuse une publication validée ; utilisé aussi par le planificateur pour les
+// messages différés. Retourne le corps de la réponse de `/publish`.
+pub async fn deliver_message(
+    state: &AppState,
+    io: &SocketIo,
+    mut payload: PublishRequest,
+) -> Result<serde_json::Value, StatusCode> {
     info!(
         "Publishing message {} to topic {} by {}",
         payload.message_id, payload.topic, payload.producer
     );
 
-    // Délègue la sauvegarde du message au `Broker`.
-    state
+    // La trame livrée porte toujours `timestamp` (heure de réception à défaut), comme la ligne stockée.
+    let timestamp = *payload.timestamp.get_or_insert_with(current_timestamp);
+
+    // Délègue la sauvegarde du message au `Broker`, qui attribue le numéro de séquence du sujet.
+    let assigned = state
         .broker
-        .save_message(
-            payload.topic.clone(),
-            payload.message_id.clone(),
-            payload.message.clone(),
-            payload.producer.clone(),
-        )
-        .await;
+        .save_message(&payload)
+        .await
+        .map_err(|DuplicateKey| {
+            warn!(
+                "Publish rejected: key already exists on topic {}",
+                payload.topic
+            );
+            StatusCode::CONFLICT
+        })?;
+    // Republication d'un `message_id` déjà publié : acquittée sans nouvelle diffusion.
+    let Saved::Accepted(assigned) = assigned else {
+        info!(
+            "Message {} already published, deduplicated",
+            payload.message_id
+        );
+        return Ok(serde_json::json!({"status": "ok", "deduplicated": true}));
+    };
+    let seq = assigned.seq;
+    payload.seq = Some(seq);
+    payload.producer_seq = assigned.producer_seq;
+
+    // Message déjà expiré (`ttl_seconds`) : stocké, mais aucune diffusion en direct.
+    if payload
+        .ttl_seconds
+        .is_some_and(|ttl| timestamp + ttl <= current_timestamp())
+    {
+        info!(
+            "Message {} on topic {} already expired, not delivered",
+            payload.message_id, payload.topic
+        );
+        return Ok(serde_json::json!({"status": "expired", "seq": seq}));
+    }
+
+    // Dernière valeur du sujet, remise aux abonnés suivants.
+    if payload.retain {
+        state.broker.retain_message(&payload).await;
+    }
+
+    // Namespace Socket.IO de destination (validé contre `SOCKETIO_NAMESPACES`).
+    let namespace = payload.namespace.clone().unwrap_or_else(|| "/".to_string());
+    let namespace = namespace.as_str();
+
+    // En mode `WILDCARD_ORDERING=per_topic`, la salle `__all__` du namespace racine est alimentée
+    // par le forwarder ordonné ; les autres namespaces sont servis directement.
+    let wildcard_direct = namespace != "/" || !state.broker.wildcard_merged();
 
     // Émet le message via Socket.IO aux clients abonnés.
-    // La compilation conditionnelle (`cfg`) permet de choisir entre deux stratégies d'émission.
+    // La stratégie est relue à chaque requête, ce qui permet de la changer à chaud via `/admin/emit-strategy`.
+    // Les salles des motifs hiérarchiques abonnés (`orders.*`, `orders.#`) qui correspondent au sujet
+    // sont servies avec la salle du sujet en une seule émission : un client présent dans plusieurs
+    // de ces salles ne reçoit le message qu'une fois.
+    let patterns = state.broker.matching_patterns(&payload.topic);
+    let mut topic_rooms = vec![payload.topic.clone()];
+    topic_rooms.extend(patterns.iter().cloned());
+    let wildcard_room = ["__all__".to_string()];
+    match state.emit_strategy() {
+        // Stratégie "parallel" : envoie aux deux salles en même temps pour une latence plus faible.
+        EmitStrategy::Parallel => {
+            let topic_emit = emit_with_retry(state, io, namespace, &topic_rooms, &payload);
+            if wildcard_direct {
+                let wildcard_emit = emit_with_retry(state, io, namespace, &wildcard_room, &payload);
+                // `tokio::join!` exécute les deux futurs d'émission en parallèle.
+                tokio::join!(topic_emit, wildcard_emit);
+            } else {
+                topic_emit.await;
+            }
+        }
+        // Stratégie "sequential" : comportement original, envoie séquentiellement.
+        EmitStrategy::Sequential => {
+            emit_with_retry(state, io, namespace, &topic_rooms, &payload).await;
+            if wildcard_direct {
+                emit_with_retry(state, io, namespace, &wildcard_room, &payload).await;
+            }
+        }
+    }
 
-    // Stratégie "parallel-emit" : envoie aux deux salles en même temps pour une latence plus faible.
-    #[cfg(feature = "parallel-emit")]
+    // Alimente le canal de diffusion du sujet, et ceux des motifs correspondants, qui servent
+    // les abonnés WebSocket brut (`/ws`).
     {
-        if let (Some(ns1), Some(ns2)) = (io.of("/"), io.of("/")) {
-            let topic_emit = ns1.to(payload.topic.clone()).emit("message", &payload);
-            let wildcard_emit = ns2.to("__all__").emit("message", &payload);
-            // `tokio::join!` exécute les deux futurs d'émission en parallèle.
-            let _ = tokio::join!(topic_emit, wildcard_emit);
+        let channels = state.topic_channels.read().await;
+        let targets: Vec<_> = topic_rooms
+            .iter()
+            .filter_map(|name| channels.get(name))
+            .collect();
+        if !targets.is_empty() {
+            if let Ok(frame) = serde_json::to_string(&payload) {
+                for tx in targets {
+                    let _ = tx.send(frame.clone());
+                }
+            }
         }
     }
 
-    // Stratégie "sequential-emit" : comportement original, envoie séquentiellement.
-    #[cfg(feature = "sequential-emit")]
-    {
-        if let Some(ns) = io.of("/") {
-            let _ = ns.to(payload.topic.clone()).emit("message", &payload).await;
+    // Groupes de consommateurs du sujet : un seul membre de chaque groupe reçoit le message.
+    state.deliver_to_groups(&payload).await;
+
+    // Tap de débogage : copie du message (sujet d'origine conservé dans `topic`), sur les deux transports.
+    if let Some(tap_topic) = state.broker.tap_target(&payload.topic) {
+        if let Some(ns) = io.of(namespace) {
+            let _ = ns.to(tap_topic.to_string()).emit("message", &payload).await;
+        }
+        if let Some(tx) = state.topic_channels.read().await.get(tap_topic) {
+            if let Ok(frame) = serde_json::to_string(&payload) {
+                let _ = tx.send(frame);
+            }
+        }
+    }
+
+    // Relais vers les brokers pairs.
+    if let Some(federation) = &state.federation {
+        federation.forward(&payload, timestamp);
+    }
+
+    // Confirmation optionnelle sur le sujet dédié : un flux de métadonnées sans le contenu des messages,
+    // sur les deux transports comme le tap.
+    if let Some(confirmations_topic) = state.config.publish_confirmations_topic.as_deref() {
+        let confirmation = PublishConfirmation {
+            topic: payload.topic.clone(),
+            message_id: payload.message_id.clone(),
+            producer: payload.producer.clone(),
+            seq,
+            timestamp,
+        };
+        if let Some(ns) = io.of(namespace) {
+            let _ = ns
+                .to(confirmations_topic.to_string())
+                .emit("message", &confirmation)
+                .await;
+        }
+        if let Some(tx) = state.topic_channels.read().await.get(confirmations_topic) {
+            if let Ok(frame) = serde_json::to_string(&confirmation) {
+                let _ = tx.send(frame);
+            }
+        }
+    }
+
+    let mut response = serde_json::json!({"status": "ok", "seq": seq});
+    if let Some(producer_seq) = assigned.producer_seq {
+        response["producer_seq"] = producer_seq.into();
+    }
+    Ok(response)
+}
+
+// Émet un message aux salles Socket.IO `rooms`, connexion par connexion. Une connexion dont le tampon
+// est plein (`InternalChannelFull`) est retentée seule, jusqu'à `EMIT_MAX_RETRIES` fois avec une
+// attente croissante, en tâche de fond : les autres abonnés ne reçoivent pas le message deux fois et
+// la publication n'attend pas. Une connexion fermée pendant l'émission est une déconnexion ordinaire.
+// Chaque échec est compté (`pubsub_emit_failures_total`) et un échec définitif diffuse `delivery_failed`.
+async fn emit_with_retry(
+    state: &AppState,
+    io: &SocketIo,
+    namespace: &str,
+    rooms: &[String],
+    payload: &PublishRequest,
+) {
+    let Some(ns) = io.of(namespace) else { return };
+    let room = rooms.join(",");
+    let full = emit_to_sockets(state, ns.to(rooms.to_vec()).sockets(), &room, payload);
+    if full.is_empty() {
+        return;
+    }
+    if state.config.emit_max_retries == 0 {
+        report_full_sockets(state, &full, &room, payload);
+        return;
+    }
+    let state = state.clone();
+    let payload = payload.clone();
+    tokio::spawn(async move {
+        let mut full = full;
+        let mut backoff = Duration::from_millis(state.config.emit_retry_backoff_ms);
+        for _ in 0..state.config.emit_max_retries {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            full = emit_to_sockets(&state, full, &room, &payload);
+            if full.is_empty() {
+                return;
+            }
         }
+        report_full_sockets(&state, &full, &room, &payload);
+    });
+}
 
-        if let Some(ns) = io.of("/") {
-            let _ = ns.to("__all__").emit("message", &payload).await;
+// Émet le message à chaque connexion et retourne celles dont le tampon était plein.
+fn emit_to_sockets(
+    state: &AppState,
+    sockets: Vec<SocketRef>,
+    room: &str,
+    payload: &PublishRequest,
+) -> Vec<SocketRef> {
+    let mut full = Vec::new();
+    for socket in sockets {
+        let error = match socket.emit("message", payload) {
+            Ok(()) | Err(SendError::Socket(SocketError::Closed)) => continue,
+            Err(error) => error,
+        };
+        state
+            .delivery_counters(&payload.topic)
+            .emit_failures
+            .fetch_add(1, Ordering::Relaxed);
+        if matches!(error, SendError::Socket(SocketError::InternalChannelFull)) {
+            full.push(socket);
+        } else {
+            report_emit_failure(state, &socket, room, payload, &error.to_string());
         }
     }
+    full
+}
+
+fn report_full_sockets(
+    state: &AppState,
+    sockets: &[SocketRef],
+    room: &str,
+    payload: &PublishRequest,
+) {
+    for socket in sockets {
+        report_emit_failure(state, socket, room, payload, "internal channel full");
+    }
+}
 
-    Ok(Json(serde_json::json!({"status": "ok"})))
+fn report_emit_failure(
+    state: &AppState,
+    socket: &SocketRef,
+    room: &str,
+    payload: &PublishRequest,
+    error: &str,
+) {
+    warn!(
+        "Socket.IO emit of {} to room {} (SID: {}) failed: {}",
+        payload.message_id, room, socket.id, error
+    );
+    state
+        .broker
+        .report_delivery_failure(&payload.topic, &payload.message_id, room, error);
+}
+
+// Sujet de destination d'une publication : ni vide, ni réservé, ni motif.
+fn validate_topic(config: &Config, topic: &str) -> Result<(), StatusCode> {
+    if topic.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Sujets réservés au routage interne (`__all__`) et aux préfixes de `RESERVED_TOPIC_PREFIXES`.
+    if config.is_reserved_topic(topic) {
+        warn!("Publish rejected: topic {} is reserved", topic);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Un motif (`orders.*`, `orders.#`) ne désigne pas un sujet concret.
+    if is_topic_pattern(topic) {
+        warn!("Publish rejected: topic {} is a pattern", topic);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+// Contrat de format du sujet (`TOPIC_CONTENT_TYPES`) : 415 si le type de contenu n'y figure pas.
+fn check_content_type(config: &Config, topic: &str, content_type: &str) -> Result<(), StatusCode> {
+    if let Some(allowed) = config.topic_content_types.get(topic) {
+        let content_type = normalize_content_type(content_type);
+        if !allowed.contains(&content_type) {
+            warn!(
+                "Publish rejected: content type {} not allowed on topic {}",
+                content_type, topic
+            );
+            return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+    }
+    Ok(())
+}
+
+// Validation des données d'entrée d'une publication, avant toute transformation.
+fn validate_publish(config: &Config, payload: &PublishRequest) -> Result<(), StatusCode> {
+    if payload.topic.is_empty() || payload.message_id.is_empty() || payload.producer.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Un horodatage fourni par le client doit rester proche de l'heure du serveur.
+    if let Some(timestamp) = payload.timestamp {
+        if let Err(reason) = check_clock_skew(timestamp, config.max_clock_skew_secs) {
+            warn!("Publish rejected: {}", reason);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    validate_topic(config, &payload.topic)?;
+
+    // Durée de vie strictement positive.
+    if payload
+        .ttl_seconds
+        .is_some_and(|ttl| !ttl.is_finite() || ttl <= 0.0)
+    {
+        warn!("Publish rejected: ttl_seconds must be a positive number");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Namespace de destination : `/` ou l'un des `SOCKETIO_NAMESPACES`.
+    if let Some(namespace) = payload.namespace.as_deref() {
+        if namespace != "/" && !config.socketio_namespaces.iter().any(|ns| ns == namespace) {
+            warn!("Publish rejected: namespace {} is not allowed", namespace);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    check_content_type(
+        config,
+        &payload.topic,
+        payload
+            .content_type
+            .as_deref()
+            .unwrap_or(DEFAULT_CONTENT_TYPE),
+    )?;
+
+    // Corps structuré exigé (`REQUIRE_OBJECT_MESSAGES` ou `OBJECT_MESSAGE_TOPICS`) : pas de scalaire ni de tableau.
+    let object_required =
+        config.require_object_messages || config.object_message_topics.contains(&payload.topic);
+    if object_required && !payload.message.is_object() {
+        warn!(
+            "Publish rejected: message on topic {} is not a JSON object",
+            payload.topic
+        );
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    Ok(())
+}
+
+// Extrait un sujet du message au chemin pointé `path` (ex: `meta.route`).
+// Le champ doit exister et être une chaîne non vide.
+fn topic_from_message(message: &serde_json::Value, path: &str) -> Option<String> {
+    path.split('.')
+        .try_fold(message, |value, key| value.get(key))
+        .and_then(|value| value.as_str())
+        .filter(|topic| !topic.is_empty())
+        .map(str::to_string)
 }
 
 // Handler pour GET `/api/clients` : retourne la liste des clients connectés.
+// Les paramètres `tag.<clé>=<valeur>` ne gardent que les connexions portant toutes ces étiquettes.
 pub async fn clients_handler(
     State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<HashMap<String, String>>,
 ) -> Json<Vec<ClientInfo>> {
     // Les données viennent directement du cache en mémoire du broker, c'est donc très rapide.
-    Json(state.broker.get_clients().await)
+    let mut clients = state.broker.get_clients().await;
+    let filters: Vec<(&str, &String)> = query
+        .iter()
+        .filter_map(|(name, value)| name.strip_prefix("tag.").map(|key| (key, value)))
+        .collect();
+    if !filters.is_empty() {
+        clients.retain(|client| {
+            filters
+                .iter()
+                .all(|(key, value)| client.tags.get(*key) == Some(*value))
+        });
+    }
+    Json(clients)
+}
+
+// Handler pour GET `/messages/{message_id}` : l'enregistrement complet d'un message, `404` s'il
+// n'est pas (ou plus) en base.
+pub async fn message_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Path(message_id): Path<String>,
+) -> Result<Json<MessageInfo>, StatusCode> {
+    match state.broker.get_message_by_id(&message_id).await {
+        Ok(Some(message)) => Ok(Json(message)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Handler pour GET `/subscriptions` : abonnements regroupés par connexion (SID), là où `/clients`
+// retourne une ligne par couple (consommateur, sujet).
+pub async fn subscriptions_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+) -> Json<Vec<SubscriptionInfo>> {
+    Json(state.broker.get_subscriptions_grouped().await)
 }
 
 // Handler pour GET `/api/messages` : retourne les derniers messages.
+// Avec `topic`, `limit` ou `before`, la requête est propre à ses paramètres et contourne le cache.
 pub async fn messages_handler(
     State((state, _)): State<(AppState, SocketIo)>,
-) -> Json<Vec<MessageInfo>> {
+    Query(query): Query<MessagesQuery>,
+) -> Result<Response, StatusCode> {
+    if query.topic.is_some() || query.limit.is_some() || query.before.is_some() {
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_MESSAGES_LIMIT);
+        let messages = state
+            .broker
+            .get_messages_filtered(query.topic.as_deref(), query.before, limit)
+            .await
+            .map_err(read_error_status)?;
+        let oldest = messages.last().map(|message| message.timestamp);
+        return Ok(page_response(messages, oldest));
+    }
+
     let dashboard_enabled = state.dashboard_enabled.load(Ordering::Relaxed);
     // Utilise la fonction de cache générique.
     let messages = get_or_fetch_cached(
         &state.cache.messages, // Le cache à utiliser.
-        state.cache.ttl, // Le TTL.
-        || async { state.broker.get_messages().await }, // La fonction pour fetch les données.
+        state.cache.ttl,       // Le TTL.
+        // La fonction pour fetch les données ; une erreur de lecture devient un statut HTTP.
+        || async { state.broker.get_messages().await.map_err(read_error_status) },
         dashboard_enabled, // L'état d'activation du cache.
     )
-    .await;
-    Json(messages)
+    .await?;
+    let oldest = messages.last().map(|message| message.timestamp);
+    Ok(page_response(messages, oldest))
+}
+
+// Handler pour DELETE `/messages?topic=X` : purge immédiate de l'historique d'un sujet.
+pub async fn delete_messages_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<DeleteMessagesQuery>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if query.topic.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let deleted = state
+        .broker
+        .delete_topic_messages(&query.topic)
+        .await
+        .map_err(read_error_status)?;
+    Ok(Json(serde_json::json!({
+        "topic": query.topic,
+        "deleted": deleted,
+    })))
+}
+
+// Handler pour GET `/export?table=messages|consumptions` : toute la table en NDJSON, transmise au fil
+// de la lecture. Une erreur de lecture en cours d'export interrompt la réponse (corps tronqué).
+pub async fn export_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let rx = state.broker.export_ndjson(query.table);
+    let lines = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (line, rx))
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+// Handler pour POST `/import` : messages au format NDJSON (une ligne par message, comme `GET /export`),
+// lus au fil du corps. Les lignes illisibles, trop longues ou déjà importées sont comptées et ignorées.
+pub async fn import_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    body: Body,
+) -> Result<Json<ImportSummary>, StatusCode> {
+    let mut chunks = body.into_data_stream();
+    let mut splitter = LineSplitter::new(state.config.max_message_bytes);
+    let mut summary = ImportSummary::default();
+    let mut flushed = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| {
+            warn!("Import aborted, unreadable request body: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+        for line in splitter.push(&chunk) {
+            import_line(&state, &line, &mut summary).await;
+        }
+        if summary.imported - flushed >= IMPORT_FLUSH_EVERY {
+            flush_import(&state, &summary).await?;
+            flushed = summary.imported;
+        }
+    }
+    if let Some(line) = splitter.finish() {
+        import_line(&state, &line, &mut summary).await;
+    }
+    summary.skipped += splitter.oversized;
+    flush_import(&state, &summary).await?;
+    info!(
+        "Import done: {} messages imported, {} lines skipped",
+        summary.imported, summary.skipped
+    );
+    Ok(Json(summary))
+}
+
+async fn import_line(state: &AppState, line: &[u8], summary: &mut ImportSummary) {
+    let imported = match serde_json::from_slice::<ImportRecord>(line) {
+        Ok(record) => state.broker.import_message(record).await,
+        Err(_) => false,
+    };
+    if imported {
+        summary.imported += 1;
+    } else {
+        summary.skipped += 1;
+    }
+}
+
+// Attend la validation des messages importés ; `503` si le worker DB ne suit pas ou si une écriture a échoué.
+async fn flush_import(state: &AppState, summary: &ImportSummary) -> Result<(), StatusCode> {
+    if state.broker.flush_writes(IMPORT_FLUSH_TIMEOUT).await {
+        return Ok(());
+    }
+    error!(
+        "Import aborted, database writes not committed after {} messages",
+        summary.imported
+    );
+    Err(StatusCode::SERVICE_UNAVAILABLE)
+}
+
+// Handler pour GET `/replay?topic=X&since=<timestamp>` : messages du sujet publiés après `since`,
+// pour qu'un consommateur reconnecté rattrape ce qu'il a manqué. Au plus `REPLAY_MAX_MESSAGES`,
+// `truncated` indiquant qu'il en reste.
+pub async fn replay_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<ReplayQuery>,
+) -> Result<Json<ReplayPage>, StatusCode> {
+    if query.topic.is_empty() || !query.since.is_finite() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let max = state.config.replay_max_messages;
+    // Un message de plus que le plafond suffit à savoir si la réponse est tronquée.
+    let mut messages = state
+        .broker
+        .get_messages_since(&query.topic, query.since, max + 1)
+        .await
+        .map_err(read_error_status)?;
+    let truncated = messages.len() as i64 > max;
+    messages.truncate(max as usize);
+    Ok(Json(ReplayPage {
+        topic: query.topic,
+        messages,
+        truncated,
+    }))
+}
+
+// Réponse JSON d'une page de résultats triés du plus récent au plus ancien, avec l'en-tête
+// `X-Next-Before` à repasser en `before` pour obtenir la page suivante.
+fn page_response<T: Serialize>(rows: Vec<T>, oldest: Option<f64>) -> Response {
+    let mut response = Json(rows).into_response();
+    if let Some(value) = oldest.and_then(|oldest| HeaderValue::from_str(&oldest.to_string()).ok()) {
+        response.headers_mut().insert(NEXT_BEFORE_HEADER, value);
+    }
+    response
+}
+
+// Statut HTTP d'une lecture en échec : `503` si la base est temporairement verrouillée, `500` sinon.
+fn read_error_status(error: sqlx::Error) -> StatusCode {
+    if is_busy(&error) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
 }
 
 // Handler pour GET `/api/consumptions` : retourne les dernières consommations.
+// Avec `limit` ou `before`, la requête contourne le cache.
 pub async fn consumptions_handler(
     State((state, _)): State<(AppState, SocketIo)>,
-) -> Json<Vec<ConsumptionInfo>> {
+    Query(query): Query<ConsumptionsQuery>,
+) -> Result<Response, StatusCode> {
+    if query.limit.is_some() || query.before.is_some() {
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_MESSAGES_LIMIT);
+        let consumptions = state
+            .broker
+            .get_consumptions_before(query.before, limit)
+            .await
+            .map_err(read_error_status)?;
+        let oldest = consumptions.last().map(|consumption| consumption.timestamp);
+        return Ok(page_response(consumptions, oldest));
+    }
+
     let dashboard_enabled = state.dashboard_enabled.load(Ordering::Relaxed);
     // Utilise la même logique de cache que pour les messages.
     let consumptions = get_or_fetch_cached(
         &state.cache.consumptions,
         state.cache.ttl,
-        || async { state.broker.get_consumptions().await },
+        || async {
+            state
+                .broker
+                .get_consumptions()
+                .await
+                .map_err(read_error_status)
+        },
         dashboard_enabled,
     )
-    .await;
-    Json(consumptions)
+    .await?;
+    let oldest = consumptions.last().map(|consumption| consumption.timestamp);
+    Ok(page_response(consumptions, oldest))
 }
 
 // Handler pour GET `/api/graph-state` : retourne les données pour le graphe.
 pub async fn graph_state_handler(
     State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<GraphQuery>,
 ) -> Json<GraphState> {
     let dashboard_enabled = state.dashboard_enabled.load(Ordering::Relaxed);
-    // Utilise la même logique de cache.
-    let graph = get_or_fetch_cached(
-        &state.cache.graph_state,
-        state.cache.ttl,
-        || async { state.broker.get_graph_state().await },
-        dashboard_enabled,
-    )
-    .await;
+    // Utilise la même logique de cache, avec une entrée distincte pour la vue `live`.
+    let graph = if query.live {
+        let window_secs = state.config.graph_live_window_secs;
+        get_or_fetch_cached(
+            &state.cache.live_graph_state,
+            state.cache.ttl,
+            || async { Ok::<_, Infallible>(state.broker.get_live_graph_state(window_secs).await) },
+            dashboard_enabled,
+        )
+        .await
+    } else {
+        get_or_fetch_cached(
+            &state.cache.graph_state,
+            state.cache.ttl,
+            || async { Ok::<_, Infallible>(state.broker.get_graph_state().await) },
+            dashboard_enabled,
+        )
+        .await
+    }
+    .unwrap_or_else(|never| match never {});
     Json(graph)
 }
 
+// Handler pour GET `/graph/history?at=<timestamp>` : état du graphe enregistré à cette date
+// (`GRAPH_HISTORY_INTERVAL_SECS`). `404` si aucun instantané n'est antérieur à `at`.
+pub async fn graph_history_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<GraphHistoryQuery>,
+) -> Result<Json<GraphState>, StatusCode> {
+    match graph_at(state.broker.db(), query.at).await {
+        Ok(Some(graph)) => Ok(Json(graph)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Err(read_error_status(e)),
+    }
+}
+
+// Handler pour GET `/dead-letters` : messages relevés sans consommation (`DEAD_LETTER_GRACE_SECS`),
+// les plus récemment détectés d'abord.
+pub async fn dead_letters_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<DeadLettersQuery>,
+) -> Result<Json<Vec<DeadLetterInfo>>, StatusCode> {
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_MESSAGES_LIMIT);
+    state
+        .broker
+        .get_dead_letters(query.topic.as_deref(), limit)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// Handler pour GET `/events` : consulte le journal d'audit des événements du broker.
+// Non mis en cache : les paramètres `since`/`type` rendent chaque requête spécifique.
+pub async fn events_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<EventsQuery>,
+) -> Json<Vec<EventInfo>> {
+    Json(
+        state
+            .broker
+            .get_events(query.since, query.event_type.as_deref())
+            .await,
+    )
+}
+
+// Handler pour GET `/events/stream` : événements du broker en direct, au format Server-Sent Events.
+// Comme le relais Socket.IO, rien n'est émis tant que le dashboard est désactivé. Le récepteur
+// `event_tx` vit avec le flux : il est libéré dès que le client se déconnecte.
+pub async fn events_stream_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Query(query): Query<EventStreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let types: Vec<String> = query
+        .types
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+    let rx = state.broker.event_tx.subscribe();
+
+    let stream =
+        futures_util::stream::unfold((rx, state, types), |(mut rx, state, types)| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if !state.dashboard_enabled.load(Ordering::Relaxed)
+                            || (!types.is_empty() && !types.contains(&event.event_type))
+                        {
+                            continue;
+                        }
+                        let sse = Event::default()
+                            .event(event.event_type.as_str())
+                            .data(event.data.to_string());
+                        return Some((Ok(sse), (rx, state, types)));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Event stream lagged by {} events", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+    // Commentaires périodiques : gardent la connexion ouverte à travers les proxys.
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// Handler pour GET `/topics` : sujets abonnés et nombre de consommateurs, sans cache ni accès DB.
+pub async fn topics_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+) -> Json<Vec<TopicStat>> {
+    Json(state.broker.get_topic_stats().await)
+}
+
+// Handler pour GET `/topics/{topic}/rate` : messages transmis et perdus (abonnés lents) sur le sujet.
+pub async fn topic_rate_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Path(topic): Path<String>,
+) -> Result<Json<TopicDeliveryStats>, StatusCode> {
+    state
+        .delivery_stats(&topic)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+// Handler pour POST `/topics/{topic}/schema` : enregistre ou remplace le schéma JSON imposé aux
+// publications du sujet (corps : le schéma). `400` si le document n'est pas un schéma valide.
+pub async fn set_topic_schema_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Path(topic): Path<String>,
+    Json(schema): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, PublishRejection> {
+    match state.broker.set_topic_schema(&topic, &schema).await {
+        Ok(()) => Ok(Json(serde_json::json!({"status": "ok", "topic": topic}))),
+        Err(SchemaError::Invalid(reason)) => Err(PublishRejection::with_reason(
+            StatusCode::BAD_REQUEST,
+            reason,
+        )),
+        Err(SchemaError::Database(e)) => Err(read_error_status(e).into()),
+    }
+}
+
+// Handler pour GET `/admin/debug/state` : état en mémoire du broker (lecture seule).
+pub async fn debug_state_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+) -> Json<DebugState> {
+    Json(state.debug_state().await)
+}
+
+// Handler pour DELETE `/scheduled/{message_id}` : annule une livraison différée encore en attente.
+pub async fn cancel_scheduled_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Path(message_id): Path<String>,
+) -> StatusCode {
+    match state.broker.cancel_scheduled(&message_id).await {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => read_error_status(e),
+    }
+}
+
+// Handler pour POST `/unsubscribe` : retire un sujet des abonnements d'une connexion active,
+// sans la fermer. `404` si la connexion n'est pas abonnée à ce sujet.
+pub async fn unsubscribe_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Json(payload): Json<UnsubscribeRequest>,
+) -> StatusCode {
+    if state.unsubscribe(&payload.sid, &payload.topic).await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+// Handler pour GET `/consumers/{name}/backlog` : retard estimé du consommateur, par sujet.
+pub async fn consumer_backlog_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Path(name): Path<String>,
+) -> Json<Vec<TopicBacklog>> {
+    Json(state.broker.get_consumer_backlog(&name).await)
+}
+
 // Handler pour GET `/health` : vérifie l'état de santé du service.
+// Avec `DEEP_HEALTH_CHECK`, vérifie en plus que la base accepte les écritures.
 pub async fn health_check(
     State((state, _)): State<(AppState, SocketIo)>,
-) -> Result<Json<HealthStatus>, StatusCode> {
+) -> Result<Json<HealthStatus>, (StatusCode, Json<HealthStatus>)> {
     // Tente d'obtenir une connexion à la base de données.
-    match state.broker.db().acquire().await {
-        // Si réussi, le service est considéré comme sain.
-        Ok(_) => Ok(Json(HealthStatus {
-            status: "healthy".to_string(),
-            timestamp: current_timestamp(),
-        })),
-        // Si échec, le service est en mauvaise santé.
-        Err(e) => {
-            tracing::error!("Health check failed: DB acquire error: {}", e);
-            Err(StatusCode::SERVICE_UNAVAILABLE)
+    if let Err(e) = state.broker.db().acquire().await {
+        tracing::error!("Health check failed: DB acquire error: {}", e);
+        return Err(unhealthy("unavailable"));
+    }
+
+    // Sonde d'écriture optionnelle : coûte une transaction par appel.
+    if state.config.deep_health_check {
+        if let Err(e) = state.broker.probe_write().await {
+            let condition = write_failure_condition(&e);
+            tracing::error!(
+                "Health check failed: DB write probe error ({}): {}",
+                condition,
+                e
+            );
+            return Err(unhealthy(condition));
         }
     }
+
+    // Si tout a réussi, le service est considéré comme sain.
+    Ok(Json(HealthStatus {
+        status: "healthy".to_string(),
+        timestamp: current_timestamp(),
+        condition: None,
+    }))
+}
+
+// Réponse `503` détaillant la cause de l'échec.
+fn unhealthy(condition: &str) -> (StatusCode, Json<HealthStatus>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(HealthStatus {
+            status: "unhealthy".to_string(),
+            timestamp: current_timestamp(),
+            condition: Some(condition.to_string()),
+        }),
+    )
+}
+
+// Classe l'échec de la sonde d'écriture d'après le code d'erreur SQLite (code primaire = 8 bits de poids faible).
+fn write_failure_condition(error: &sqlx::Error) -> &'static str {
+    let code = match error {
+        sqlx::Error::Database(db_err) => db_err.code().and_then(|c| c.parse::<i32>().ok()),
+        _ => None,
+    };
+    match code.map(|c| c & 0xff) {
+        // SQLITE_READONLY
+        Some(8) => "read_only",
+        // SQLITE_FULL
+        Some(13) => "disk_full",
+        _ => "write_failed",
+    }
 }
 
 // Fonction utilitaire pour le timestamp.
@@ -205,17 +1204,28 @@ fn current_timestamp() -> f64 {
 }
 
 // Handler pour POST `/api/dashboard/login` : active le mode dashboard.
+// Si `DASHBOARD_PASSWORD` est défini, le corps `{ "password" }` doit le contenir, sinon `401`.
 pub async fn dashboard_login_handler(
     State((state, _)): State<(AppState, SocketIo)>,
-) -> Json<serde_json::Value> {
+    body: Option<Json<DashboardLoginRequest>>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if let Some(expected) = state.config.dashboard_password.as_deref() {
+        let authorized = body.is_some_and(|Json(login)| {
+            constant_time_eq(login.password.as_bytes(), expected.as_bytes())
+        });
+        if !authorized {
+            warn!("Dashboard login refused: wrong password");
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
     // `store` est une opération atomique pour définir la valeur du booléen.
     // `Ordering::Relaxed` est la contrainte de mémoire la plus faible, suffisante ici car il n'y a pas d'autre synchronisation qui en dépend.
     state.dashboard_enabled.store(true, Ordering::Relaxed);
     info!("Dashboard enabled");
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "status": "ok",
         "dashboard_enabled": true
-    }))
+    })))
 }
 
 // Handler pour POST `/api/dashboard/logout` : désactive le mode dashboard.
@@ -240,3 +1250,102 @@ pub async fn dashboard_status_handler(
         "dashboard_enabled": enabled
     }))
 }
+
+// Handler pour GET `/metrics` : métriques du broker au format texte Prometheus.
+pub async fn metrics_handler(State((state, _)): State<(AppState, SocketIo)>) -> Response {
+    let body = metrics::render_prometheus(&metrics::collect(&state).await);
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+        .into_response()
+}
+
+// Handler pour GET `/stats/latency` : latence publication -> `consumed` par sujet (p50, p95, max).
+pub async fn latency_stats_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+) -> Json<Vec<TopicLatency>> {
+    Json(state.metrics.latency_stats())
+}
+
+// Handler pour GET `/admin/emit-strategy` : retourne la stratégie d'émission courante.
+pub async fn get_emit_strategy_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "strategy": state.emit_strategy().as_str()
+    }))
+}
+
+// Handler pour POST `/admin/emit-strategy` : change la stratégie d'émission sans redémarrage.
+pub async fn set_emit_strategy_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Json(payload): Json<EmitStrategyRequest>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let strategy: EmitStrategy = payload
+        .strategy
+        .parse()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    state.set_emit_strategy(strategy);
+    info!("Emit strategy switched to {}", strategy.as_str());
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "strategy": strategy.as_str()
+    })))
+}
+
+// Remplace le `405` sans corps d'Axum (méthode non prise en charge sur une route connue) par une erreur
+// JSON listant les méthodes acceptées ; l'en-tête `Allow` posé par le routeur est conservé.
+pub async fn method_not_allowed_json(response: Response) -> Response {
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+    let allow = response.headers().get(header::ALLOW).cloned();
+    let allowed: Vec<&str> = allow
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let body = Json(serde_json::json!({
+        "error": "method_not_allowed",
+        "allowed": allowed,
+    }));
+    let mut json_response = (StatusCode::METHOD_NOT_ALLOWED, body).into_response();
+    if let Some(allow) = allow {
+        json_response.headers_mut().insert(header::ALLOW, allow);
+    }
+    json_response
+}
+
+// Handler pour POST `/clients/{sid}/request` : envoie le corps JSON au client WebSocket `sid`
+// dans une trame `request`, et retourne sa réponse. `404` si le client n'est pas connecté,
+// `504` s'il ne répond pas dans le délai `CLIENT_REQUEST_TIMEOUT_MS`.
+pub async fn client_request_handler(
+    State((state, _)): State<(AppState, SocketIo)>,
+    Path(sid): Path<String>,
+    Json(payload): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let timeout = std::time::Duration::from_millis(state.config.client_request_timeout_ms);
+    match state.connections.request(&sid, payload, timeout).await {
+        Ok(response) => Ok(Json(serde_json::json!({
+            "sid": sid,
+            "response": response
+        }))),
+        Err(RequestError::NotFound) => Err(StatusCode::NOT_FOUND),
+        Err(RequestError::Timeout) => {
+            warn!("Client {} did not answer request within {:?}", sid, timeout);
+            Err(StatusCode::GATEWAY_TIMEOUT)
+        }
+        // La connexion s'est fermée pendant l'attente : le client n'est plus joignable.
+        Err(RequestError::Disconnected) => Err(StatusCode::NOT_FOUND),
+    }
+}
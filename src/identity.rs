@@ -0,0 +1,59 @@
+// Identité `consumer` associée à une connexion (WebSocket brut ou Socket.IO).
+//
+// En mode `CONSUMER_IDENTITY=connection`, l'identité est établie une seule fois par connexion,
+// via une trame `identify` ou, à défaut, par le `consumer` du premier `subscribe`.
+// Elle est ensuite imposée à tous les événements de la connexion : une même connexion
+// ne peut donc plus se faire passer pour plusieurs consommateurs.
+use crate::config::IdentityMode;
+use std::sync::Mutex;
+
+pub struct ConnectionIdentity {
+    mode: IdentityMode,
+    consumer: Mutex<Option<String>>,
+}
+
+impl ConnectionIdentity {
+    pub fn new(mode: IdentityMode) -> Self {
+        Self {
+            mode,
+            consumer: Mutex::new(None),
+        }
+    }
+
+    // Traite une trame `identify` : fixe l'identité de la connexion.
+    // En mode `connection`, une identité déjà établie ne peut pas être remplacée par une autre.
+    pub fn identify(&self, consumer: &str) -> Result<String, &'static str> {
+        if consumer.is_empty() {
+            return Err("consumer is required");
+        }
+        let mut current = self.consumer.lock().unwrap();
+        match current.as_deref() {
+            Some(existing) if self.mode == IdentityMode::Connection && existing != consumer => {
+                Err("identity already established for this connection")
+            }
+            _ => {
+                *current = Some(consumer.to_string());
+                Ok(consumer.to_string())
+            }
+        }
+    }
+
+    // Détermine le `consumer` effectif d'un événement.
+    // - `declared` : le champ de l'événement est utilisé tel quel.
+    // - `connection` : l'identité de la connexion prime ; si elle n'existe pas encore,
+    //   le `consumer` déclaré l'établit. Retourne `None` si aucune identité n'est disponible.
+    pub fn resolve(&self, declared: &str) -> Option<String> {
+        if self.mode == IdentityMode::Declared {
+            return (!declared.is_empty()).then(|| declared.to_string());
+        }
+        let mut current = self.consumer.lock().unwrap();
+        if let Some(existing) = current.as_ref() {
+            return Some(existing.clone());
+        }
+        if declared.is_empty() {
+            return None;
+        }
+        *current = Some(declared.to_string());
+        Some(declared.to_string())
+    }
+}
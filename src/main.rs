@@ -1,33 +1,72 @@
 // Déclaration des modules qui composent l'application.
 // Chaque `mod` correspond à un fichier `.rs` du même nom.
+mod access_log;
 mod app_state;
+mod archive;
+mod auth;
 mod broker;
 mod cache;
+mod config;
 mod database;
+mod dedup;
 mod embedded;
+mod federation;
+mod graph_history;
+mod groups;
 mod handlers;
+mod identity;
+mod metrics;
 mod models;
+mod ndjson;
+mod pacing;
+mod pipeline;
+mod qos;
+mod rate_limit;
+mod registry;
+mod scheduler;
 mod socketio;
+mod topic_pattern;
+mod validator;
 mod websocket;
+mod wildcard;
+mod ws_deflate;
 
 // Importations des structures et fonctions nécessaires depuis les autres modules et bibliothèques.
+use access_log::AccessLog;
 use app_state::AppState;
+use archive::Archive; // Archivage à froid des messages purgés.
 use axum::{
-    routing::{get, post}, // Pour définir les routes HTTP GET et POST.
-    Router, // Le routeur Axum qui associe les chemins aux handlers.
+    extract::DefaultBodyLimit, // Pour limiter la taille des corps de requête.
+    middleware, // Pour appliquer des middlewares (ex: authentification admin) à un groupe de routes.
+    routing::{delete, get, post}, // Pour définir les routes HTTP GET, POST et DELETE.
+    Router,     // Le routeur Axum qui associe les chemins aux handlers.
 };
+use axum_server::tls_rustls::RustlsConfig; // Certificat et clé du listener HTTPS.
 use broker::Broker;
-use database::init_database;
-use embedded::serve_embedded; // Handler pour les fichiers statiques embarqués.
+use config::{Config, WildcardOrdering};
+use database::{dry_run_migrations, init_database};
+// Handler pour les fichiers statiques embarqués.
+use embedded::serve_embedded;
+use federation::Federation; // Relais des publications vers les brokers pairs.
+use futures_util::FutureExt; // `shared` : un même signal d'arrêt pour plusieurs serveurs.
 use handlers::{
-    clients_handler, consumptions_handler, dashboard_login_handler, dashboard_logout_handler,
-    dashboard_status_handler, graph_state_handler, health_check, messages_handler, publish_handler,
+    cancel_scheduled_handler, client_request_handler, clients_handler, consumer_backlog_handler,
+    consumptions_handler, dashboard_login_handler, dashboard_logout_handler,
+    dashboard_status_handler, dead_letters_handler, debug_state_handler, delete_messages_handler,
+    events_handler, events_stream_handler, export_handler, get_emit_strategy_handler,
+    graph_history_handler, graph_state_handler, health_check, import_handler,
+    latency_stats_handler, message_handler, messages_handler, method_not_allowed_json,
+    metrics_handler, publish_binary_handler, publish_handler, replay_handler,
+    set_emit_strategy_handler, set_topic_schema_handler, subscriptions_handler, topic_rate_handler,
+    topics_handler, unsubscribe_handler,
 };
+use pipeline::Pipeline; // Pipeline de transformation des publications.
 use socketioxide::SocketIo;
 use std::{net::SocketAddr, sync::Arc}; // Pour l'adresse du serveur et le partage de références thread-safe.
-use tokio::sync::broadcast; // Canal de diffusion pour les événements.
+use tokio::sync::{broadcast, mpsc}; // Canaux de diffusion et de file pour les événements.
 use tower_http::cors::CorsLayer; // Middleware pour gérer les requêtes Cross-Origin (CORS).
-use tracing::info; // Pour la journalisation.
+use tower_http::trace::TraceLayer; // Middleware de traçage des requêtes HTTP (journal d'accès).
+use tracing::{info, warn}; // Pour la journalisation.
 use websocket::ws_handler; // Handler pour la connexion WebSocket.
 
 // `#[tokio::main]` est une macro qui transforme la fonction `main` asynchrone
@@ -37,41 +76,160 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialise le système de journalisation `tracing`.
     tracing_subscriber::fmt::init();
 
-    // Récupère le chemin du fichier de base de données depuis une variable d'environnement.
-    // Si la variable n'est pas définie, utilise une base de données en mémoire (`:memory:`), idéal pour les tests.
-    let db_file = std::env::var("DATABASE_FILE").unwrap_or_else(|_| ":memory:".to_string());
+    // Lit la configuration depuis les variables d'environnement.
+    // Si ni `DATABASE_URL` ni `DATABASE_FILE` ne sont définies, utilise une base SQLite en mémoire (`:memory:`), idéal pour les tests.
+    let config = Arc::new(Config::from_env());
+
+    // Mode dry-run : liste les migrations en attente et s'arrête avant toute modification.
+    if config.migrate_dry_run {
+        dry_run_migrations(&config.database_url).await?;
+        return Ok(());
+    }
+
+    // Charge le certificat TLS avant tout le reste : une configuration incomplète ou illisible empêche le démarrage.
+    let tls = load_tls(&config).await?;
+    // De même pour le port d'administration : l'ignorer exposerait les routes d'administration sur le port public.
+    let admin_port = admin_port(&config)?;
 
     info!("Initializing database...");
     // Initialise la base de données (crée le fichier, applique les migrations, etc.).
-    let pool = init_database(&db_file).await?;
-
-    // Limite le nombre de connexions pour éviter de surcharger la base de données.
-    sqlx::query("PRAGMA max_connections = 10")
-        .execute(&pool)
-        .await
-        .ok();
+    let pool = init_database(&config.database_url).await?;
 
     // Crée un canal de diffusion (`broadcast`) pour les événements internes de l'application.
-    // `1000` est la capacité du canal.
-    let (event_tx, _) = broadcast::channel(1000);
+    // Chaque emplacement retient un événement (ou un message, pour les canaux par sujet) jusqu'à ce qu'il soit
+    // écrasé : une capacité plus grande absorbe mieux les rafales au prix de plus de mémoire.
+    info!(
+        "Broadcast channel capacities: {} events, {} messages per topic (memory grows with capacity x message size; \
+         slow receivers lag beyond it)",
+        config.event_channel_capacity, config.topic_channel_capacity
+    );
+    let (event_tx, _) = broadcast::channel(config.event_channel_capacity);
+    // File vers le forwarder wildcard ordonné, créée uniquement si le mode `per_topic` est demandé.
+    let (wildcard_tx, wildcard_rx) = if config.wildcard_ordering == WildcardOrdering::PerTopic {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Some(tx), Some(rx))
+    } else {
+        (None, None)
+    };
+
+    // Charge le pipeline de transformation ; une configuration invalide empêche le démarrage.
+    let pipeline = Pipeline::load(config.pipeline_config.as_deref())?;
+    if !pipeline.is_empty() {
+        info!("Message pipeline loaded from {:?}", config.pipeline_config);
+    }
+
+    // Ouvre l'archive des messages purgés ; une destination invalide empêche le démarrage.
+    let archive = Archive::open(config.archive_sink.as_deref()).await?;
+    if archive.is_some() {
+        info!("Purged messages archived to {:?}", config.archive_sink);
+    }
+
     // Crée le `Broker` et l'enveloppe dans un `Arc` pour le partager de manière sûre entre les threads.
-    let broker = Arc::new(Broker::new(pool, event_tx.clone()));
+    let broker = Arc::new(Broker::new(
+        pool,
+        event_tx.clone(),
+        config.clone(),
+        wildcard_tx,
+        pipeline,
+        archive,
+    ));
+    // Reprend la numérotation des sujets là où elle s'était arrêtée.
+    broker.load_topic_sequences().await;
+    broker.load_scheduled_count().await;
+    broker.load_recent_message_ids().await;
+    broker.load_topic_schemas().await;
+    if config.purge_stale_subscriptions {
+        broker.purge_stale_subscriptions().await;
+    }
 
     // Crée l'état global de l'application.
-    let state = AppState::new(broker);
+    let federation = Federation::start(&config);
+    let state = AppState::new(broker, config.clone(), federation);
+    if config.topic_cleanup {
+        state.spawn_topic_cleanup();
+    }
+    if config.client_stale_secs > 0 {
+        state.spawn_liveness_sweeper();
+    }
+    if config.graph_history_interval_secs > 0 {
+        graph_history::spawn_graph_history(state.clone());
+    }
+    // Export des métriques en push, activé par `METRICS_PUSH_URL`.
+    if let Some(url) = config.metrics_push_url.clone() {
+        metrics::spawn_push_exporter(
+            state.clone(),
+            url,
+            std::time::Duration::from_secs(config.metrics_push_interval_secs.max(1)),
+        );
+    }
+
+    if config.admin_token.is_none() {
+        warn!("ADMIN_TOKEN non défini: les routes /admin/* sont accessibles sans authentification");
+    }
+    if config.dashboard_password.is_none() {
+        warn!("DASHBOARD_PASSWORD non défini: le dashboard est activable sans mot de passe");
+    }
 
     // Crée la couche (`Layer`) et l'instance de Socket.IO.
-    let (io_layer, io) = SocketIo::new_layer();
+    // `max_payload` aligne la taille maximale des paquets sur `MAX_MESSAGE_BYTES`.
+    let (io_layer, io) = SocketIo::builder()
+        .max_payload(config.max_message_bytes as u64)
+        .req_path(format!("{}/socket.io", config.base_path))
+        .build_layer();
 
     // Configure les handlers pour les événements Socket.IO (connexion, abonnement, etc.).
     socketio::setup_socketio_handlers(io.clone(), state.clone());
 
+    // Éviction des seaux des producteurs inactifs (`PUBLISH_RATE_PER_SEC`).
+    if let Some(limiter) = &state.publish_limiter {
+        limiter.spawn_eviction();
+    }
+
+    // Livre les messages différés à leur échéance (y compris ceux en attente avant le redémarrage).
+    scheduler::spawn_scheduler(state.clone(), io.clone());
+
+    // Statistiques agrégées poussées au dashboard.
+    if config.stats_snapshot_interval_secs > 0 {
+        socketio::spawn_stats_snapshots(
+            io.clone(),
+            state.clone(),
+            std::time::Duration::from_secs(config.stats_snapshot_interval_secs),
+        );
+    }
+
+    // Lance le forwarder qui alimente la salle `__all__` en préservant l'ordre de chaque sujet.
+    if let Some(wildcard_rx) = wildcard_rx {
+        wildcard::spawn_merger(io.clone(), wildcard_rx);
+    }
+
     // --- Tâche de fond pour relayer les événements du Broker vers les clients Socket.IO ---
     // S'abonne au canal d'événements du Broker.
     let mut event_rx = event_tx.subscribe();
     let io_clone = io.clone();
     let state_clone = state.clone();
+    // `EVENT_BATCH_WINDOW_MS` : les événements d'une même fenêtre partent en une seule trame `events_batch`.
+    let batch_window = std::time::Duration::from_millis(config.event_batch_window_ms);
     tokio::spawn(async move {
+        if !batch_window.is_zero() {
+            while let Ok(first) = event_rx.recv().await {
+                let mut batch = vec![first];
+                let deadline = tokio::time::Instant::now() + batch_window;
+                while let Ok(Ok(event)) = tokio::time::timeout_at(deadline, event_rx.recv()).await {
+                    batch.push(event);
+                }
+                if state_clone
+                    .dashboard_enabled
+                    .load(std::sync::atomic::Ordering::Relaxed)
+                {
+                    if let Some(ns) = io_clone.of("/") {
+                        let events: Vec<&models::BroadcastEvent> =
+                            batch.iter().map(|e| e.as_ref()).collect();
+                        let _ = ns.emit("events_batch", &events).await;
+                    }
+                }
+            }
+            return;
+        }
         // Boucle infinie pour recevoir les événements.
         while let Ok(event) = event_rx.recv().await {
             // Ne relaie les événements que si le dashboard est activé.
@@ -91,44 +249,283 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Combine l'état de l'application et l'instance Socket.IO pour les injecter dans les handlers Axum.
     let app_state_with_io = (state.clone(), io);
 
+    // Routes d'administration, protégées par le middleware `require_admin`.
+    // `route_layer` n'applique le middleware qu'aux routes de ce sous-routeur.
+    let admin_routes = Router::new()
+        .route(
+            "/admin/emit-strategy",
+            get(get_emit_strategy_handler).post(set_emit_strategy_handler),
+        )
+        .route("/clients/{sid}/request", post(client_request_handler))
+        .route("/admin/debug/state", get(debug_state_handler))
+        .route("/topics/{topic}/schema", post(set_topic_schema_handler))
+        .route("/messages", delete(delete_messages_handler))
+        .route("/scheduled/{message_id}", delete(cancel_scheduled_handler))
+        .route("/unsubscribe", post(unsubscribe_handler))
+        .route("/export", get(export_handler))
+        .route("/import", post(import_handler))
+        .route_layer(middleware::from_fn_with_state(
+            app_state_with_io.clone(),
+            auth::require_admin,
+        ))
+        // Ajoutée après `route_layer` : suit les routes d'administration (et `ADMIN_PORT`) sans
+        // exiger `ADMIN_TOKEN`, pour les collecteurs Prometheus.
+        .route("/metrics", get(metrics_handler));
+
     // Construit le routeur principal de l'application.
-    let app = Router::new()
+    let routes = Router::new()
         // Définit les routes pour l'API REST.
-        .route("/publish", post(publish_handler))
+        // Seules les publications exigent la clé d'API (`PUBLISH_API_KEY`) ; dashboard et santé restent ouverts.
+        .route(
+            "/publish",
+            post(publish_handler).route_layer(middleware::from_fn_with_state(
+                app_state_with_io.clone(),
+                auth::require_publish_key,
+            )),
+        )
+        .route(
+            "/publish/binary",
+            post(publish_binary_handler).route_layer(middleware::from_fn_with_state(
+                app_state_with_io.clone(),
+                auth::require_publish_key,
+            )),
+        )
         .route("/clients", get(clients_handler))
+        .route("/subscriptions", get(subscriptions_handler))
         .route("/messages", get(messages_handler))
+        .route("/messages/{message_id}", get(message_handler))
+        .route("/replay", get(replay_handler))
         .route("/consumptions", get(consumptions_handler))
+        .route("/dead-letters", get(dead_letters_handler))
         .route("/graph/state", get(graph_state_handler))
+        .route("/graph/history", get(graph_history_handler))
+        .route("/events", get(events_handler))
+        .route("/events/stream", get(events_stream_handler))
+        .route("/consumers/{name}/backlog", get(consumer_backlog_handler))
+        .route("/topics", get(topics_handler))
+        .route("/topics/{topic}/rate", get(topic_rate_handler))
+        .route("/stats/latency", get(latency_stats_handler))
         .route("/health", get(health_check))
         // Route pour la connexion WebSocket brute.
         .route("/ws", get(ws_handler))
         // Routes pour la gestion du dashboard.
         .route("/dashboard/login", post(dashboard_login_handler))
         .route("/dashboard/logout", post(dashboard_logout_handler))
-        .route("/dashboard/status", get(dashboard_status_handler))
-        // Injecte l'état partagé dans tous les handlers.
-        .with_state(app_state_with_io)
+        .route("/dashboard/status", get(dashboard_status_handler));
+
+    // Routes d'administration : fusionnées au port public, ou servies seules sur `ADMIN_PORT`
+    // pour que l'accès à l'administration puisse être filtré séparément.
+    let (routes, admin_app) = match admin_port {
+        Some(admin_port) => {
+            let admin_app = with_base_path(
+                admin_routes.with_state(app_state_with_io.clone()),
+                &config.base_path,
+            );
+            (
+                routes,
+                Some((admin_port, common_layers(admin_app, &config))),
+            )
+        }
+        None => (routes.merge(admin_routes), None),
+    };
+
+    let routes = routes
         // `fallback` définit un handler pour toutes les requêtes qui ne correspondent à aucune autre route.
         // Utilisé ici pour servir les fichiers statiques (HTML, CSS, JS).
         .fallback(serve_embedded)
-        // Ajoute la couche Socket.IO au routeur.
-        .layer(io_layer)
-        // Ajoute la couche CORS pour autoriser les requêtes depuis n'importe quelle origine.
-        .layer(CorsLayer::permissive());
+        // Injecte l'état partagé dans tous les handlers.
+        .with_state(app_state_with_io);
+
+    if !config.base_path.is_empty() {
+        info!("Serving under base path {}", config.base_path);
+    }
+    // Ajoute la couche Socket.IO au routeur, sous les couches communes.
+    let app = common_layers(
+        with_base_path(routes, &config.base_path).layer(io_layer),
+        &config,
+    );
 
     // Définit l'adresse et le port d'écoute du serveur.
     let addr = SocketAddr::from(([0, 0, 0, 0], 5000));
-    info!("Server starting on {}", addr);
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    info!("Server starting on {} ({})", addr, scheme);
 
-    // Crée un listener TCP sur l'adresse spécifiée.
-    let listener = tokio::net::TcpListener::bind(addr).await?;
+    // L'arrêt gracieux (fin des requêtes en cours) est déclenché par Ctrl-C, SIGTERM ou
+    // l'inactivité prolongée (`AUTO_SHUTDOWN_IDLE_SECS`) ; le signal est partagé par les deux serveurs.
+    let shutdown = shutdown_signal(state.clone()).shared();
+
+    // Lance le serveur Axum public.
+    let public_server = serve(addr, app, tls.clone(), shutdown.clone());
+
+    match admin_app {
+        Some((admin_port, admin_app)) => {
+            let admin_addr = SocketAddr::from(([0, 0, 0, 0], admin_port));
+            info!("Admin server starting on {} ({})", admin_addr, scheme);
+            let admin_server = serve(admin_addr, admin_app, tls, shutdown);
+            tokio::try_join!(public_server, admin_server)?;
+        }
+        None => public_server.await?,
+    }
 
-    // Lance le serveur Axum.
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await?;
+    // Valide en base les commandes encore en file ou dans le batch courant avant de quitter.
+    state.broker.shutdown().await;
+    info!("Server stopped");
 
     Ok(())
 }
+
+// Certificat et clé PEM de `TLS_CERT_PATH` et `TLS_KEY_PATH` ; `None` (HTTP) si aucune des deux n'est définie.
+async fn load_tls(config: &Config) -> Result<Option<RustlsConfig>, Box<dyn std::error::Error>> {
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert), Some(key)) => {
+            let tls = RustlsConfig::from_pem_file(cert, key).await.map_err(|e| {
+                format!("cannot load TLS certificate {} / key {}: {}", cert, key, e)
+            })?;
+            info!("TLS enabled with certificate {}", cert);
+            Ok(Some(tls))
+        }
+        (None, None) => Ok(None),
+        (Some(_), None) => {
+            Err("TLS_CERT_PATH is set but TLS_KEY_PATH is not: both are required for TLS".into())
+        }
+        (None, Some(_)) => {
+            Err("TLS_KEY_PATH is set but TLS_CERT_PATH is not: both are required for TLS".into())
+        }
+    }
+}
+
+// Port de `ADMIN_PORT` ; `None` si la variable n'est pas définie, erreur si elle ne désigne pas un port (1-65535).
+fn admin_port(config: &Config) -> Result<Option<u16>, Box<dyn std::error::Error>> {
+    let Some(raw) = config.admin_port.as_deref() else {
+        return Ok(None);
+    };
+    match raw.trim().parse::<u16>() {
+        Ok(port) if port != 0 => Ok(Some(port)),
+        _ => Err(format!(
+            "invalid ADMIN_PORT '{}': expected a port number between 1 and 65535",
+            raw
+        )
+        .into()),
+    }
+}
+
+// Sert l'application sur `addr`, en HTTPS (`axum-server` avec rustls) si `tls` est fourni, en HTTP sinon.
+// Le routeur est le même dans les deux cas : WebSocket et Socket.IO passent aussi par le listener TLS.
+async fn serve<F>(
+    addr: SocketAddr,
+    app: Router,
+    tls: Option<RustlsConfig>,
+    shutdown: F,
+) -> std::io::Result<()>
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(tls) => {
+            // `axum-server` n'accepte pas de futur d'arrêt : le signal déclenche l'arrêt gracieux de son `Handle`.
+            let handle = axum_server::Handle::new();
+            let trigger = handle.clone();
+            tokio::spawn(async move {
+                shutdown.await;
+                trigger.graceful_shutdown(None);
+            });
+            axum_server::bind_rustls(addr, tls)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            axum::serve(listener, service)
+                .with_graceful_shutdown(shutdown)
+                .await
+        }
+    }
+}
+
+// `BASE_PATH` : l'application est servie sous ce préfixe (chemins vus sans préfixe par les handlers).
+fn with_base_path(routes: Router, base_path: &str) -> Router {
+    if base_path.is_empty() {
+        routes
+    } else {
+        Router::new().nest(base_path, routes)
+    }
+}
+
+// Couches partagées par le serveur public et celui d'administration.
+fn common_layers(app: Router, config: &Arc<Config>) -> Router {
+    app
+        // `405` des routes connues : corps JSON `{ error, allowed }` en plus de l'en-tête `Allow`.
+        .layer(middleware::map_response(method_not_allowed_json))
+        // Limite la taille des corps de requête (`MAX_MESSAGE_BYTES`).
+        .layer(DefaultBodyLimit::max(config.max_message_bytes))
+        // Ajoute la couche CORS pour autoriser les requêtes depuis n'importe quelle origine.
+        .layer(CorsLayer::permissive())
+        // Journal d'accès (couche la plus externe : couvre aussi Socket.IO et les fichiers statiques).
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(AccessLog::new(config.clone()))
+                .on_request(())
+                .on_response(AccessLog::new(config.clone()))
+                .on_body_chunk(())
+                .on_eos(())
+                .on_failure(()),
+        )
+}
+
+// Se résout à la première demande d'arrêt : Ctrl-C, SIGTERM (Unix) ou inactivité prolongée.
+// Les connexions ouvertes sont alors fermées, sans quoi l'arrêt gracieux attendrait leur fin.
+async fn shutdown_signal(state: AppState) {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Ctrl-C received, shutting down"),
+        _ = terminate => info!("SIGTERM received, shutting down"),
+        _ = idle_shutdown(state.clone()) => {}
+    }
+    state.connections.close_all("server shutting down").await;
+}
+
+// Se résout quand le broker n'a eu ni connexion ni publication pendant `AUTO_SHUTDOWN_IDLE_SECS`,
+// pour qu'un orchestrateur puisse le ramener à zéro instance ; jamais si l'option est désactivée.
+// Toutes les publications (JSON, binaires, différées, importées) datent la dernière activité du broker.
+async fn idle_shutdown(state: AppState) {
+    let idle_secs = state.config.auto_shutdown_idle_secs;
+    if idle_secs == 0 {
+        return std::future::pending().await;
+    }
+    let idle_limit = std::time::Duration::from_secs(idle_secs);
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        (idle_secs / 10).clamp(1, 60),
+    ));
+    // Dernière fois qu'une connexion était ouverte.
+    let mut connected_at = tokio::time::Instant::now();
+    loop {
+        interval.tick().await;
+        if state.connections.count().await > 0 {
+            connected_at = tokio::time::Instant::now();
+        } else if connected_at.elapsed().min(state.broker.publish_idle()) >= idle_limit {
+            info!(
+                "No connection nor publish for {}s (AUTO_SHUTDOWN_IDLE_SECS), shutting down",
+                idle_secs
+            );
+            return;
+        }
+    }
+}
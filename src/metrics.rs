@@ -0,0 +1,496 @@
+// Métriques du broker, exposées au format texte Prometheus (`GET /metrics`) et exportées en push
+// (`METRICS_PUSH_URL`) pour les piles de supervision qui reçoivent les métriques au lieu de les collecter.
+//
+// - `collect` rassemble compteurs et jauges depuis l'état en mémoire (aucune requête DB).
+// - `statsd://host:port` (ou `udp://`) : lignes StatsD envoyées en UDP, sujet en tag DogStatsD
+//   (`|#topic:...`). StatsD attend des incréments : un compteur est envoyé en différence depuis le push précédent.
+// - `http://` / `https://` : requête OTLP/HTTP JSON (`ExportMetricsServiceRequest`) postée telle quelle
+//   à l'URL (en général `.../v1/metrics`), compteurs cumulés depuis le démarrage.
+// Un échec d'envoi est journalisé ; le push suivant repart normalement.
+//
+// La latence publication -> `consumed` est suivie par sujet (`GET /stats/latency`) : l'heure de
+// publication des derniers messages est mémorisée, et une consommation d'un message inconnu
+// (oublié ou antérieur au démarrage) n'est pas comptée.
+use crate::app_state::AppState;
+use crate::models::TopicLatency;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tracing::{info, warn};
+
+// Taille maximale d'un datagramme StatsD (sous la MTU courante).
+const STATSD_MAX_PACKET: usize = 1400;
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+// Nombre de messages dont l'heure de publication est mémorisée pour le calcul de latence.
+const PUBLISH_TIMES_CAPACITY: usize = 100_000;
+// Bornes supérieures (ms) des intervalles de l'histogramme de latence ; au-delà, dernier intervalle.
+const LATENCY_BUCKETS_MS: [f64; 16] = [
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10_000.0,
+    30_000.0, 60_000.0, 300_000.0,
+];
+
+// Heures de publication des messages récents, les plus anciennes oubliées en premier.
+#[derive(Default)]
+struct PublishTimes {
+    times: HashMap<(String, String), f64>,
+    order: VecDeque<(String, String)>,
+}
+
+// Histogramme de latence d'un sujet : percentiles estimés par intervalle, maximum exact.
+struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    count: u64,
+    max_ms: f64,
+}
+
+impl LatencyHistogram {
+    fn new() -> Self {
+        Self {
+            buckets: [0; LATENCY_BUCKETS_MS.len() + 1],
+            count: 0,
+            max_ms: 0.0,
+        }
+    }
+
+    fn record(&mut self, latency_ms: f64) {
+        let index = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.buckets[index] += 1;
+        self.count += 1;
+        self.max_ms = self.max_ms.max(latency_ms);
+    }
+
+    // Borne supérieure de l'intervalle contenant le quantile `q`, plafonnée au maximum observé.
+    fn percentile(&self, q: f64) -> f64 {
+        let rank = (q * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = LATENCY_BUCKETS_MS
+                    .get(index)
+                    .copied()
+                    .unwrap_or(self.max_ms);
+                return bound.min(self.max_ms);
+            }
+        }
+        self.max_ms
+    }
+}
+
+// Compteurs globaux cumulés depuis le démarrage, incrémentés par le broker et les transports.
+#[derive(Default)]
+pub struct Metrics {
+    // Messages enregistrés (`save_message`).
+    pub messages_published: AtomicU64,
+    // Consommations enregistrées (`save_consumption`).
+    pub consumptions_recorded: AtomicU64,
+    // Connexions WebSocket brut et Socket.IO ouvertes.
+    pub connections_opened: AtomicU64,
+    // Abonnements à un sujet enregistrés (`register_subscription`).
+    pub subscriptions_registered: AtomicU64,
+    // Déconnexions de clients (`unregister_client`).
+    pub clients_disconnected: AtomicU64,
+    // `std::sync::Mutex` : sections critiques courtes, sans `await`.
+    publish_times: Mutex<PublishTimes>,
+    latencies: Mutex<BTreeMap<String, LatencyHistogram>>,
+}
+
+impl Metrics {
+    // Mémorise l'heure de publication d'un message (secondes Unix).
+    pub fn record_publish(&self, topic: &str, message_id: &str, published_at: f64) {
+        let key = (topic.to_string(), message_id.to_string());
+        let mut publish_times = self.publish_times.lock().unwrap();
+        if publish_times
+            .times
+            .insert(key.clone(), published_at)
+            .is_none()
+        {
+            publish_times.order.push_back(key);
+        }
+        if publish_times.order.len() > PUBLISH_TIMES_CAPACITY {
+            if let Some(oldest) = publish_times.order.pop_front() {
+                publish_times.times.remove(&oldest);
+            }
+        }
+    }
+
+    // Enregistre la latence d'une consommation, si l'heure de publication du message est connue.
+    pub fn record_consumption(&self, topic: &str, message_id: &str, consumed_at: f64) {
+        let published_at = self
+            .publish_times
+            .lock()
+            .unwrap()
+            .times
+            .get(&(topic.to_string(), message_id.to_string()))
+            .copied();
+        let Some(published_at) = published_at else {
+            return;
+        };
+        let latency_ms = ((consumed_at - published_at) * 1000.0).max(0.0);
+        self.latencies
+            .lock()
+            .unwrap()
+            .entry(topic.to_string())
+            .or_insert_with(LatencyHistogram::new)
+            .record(latency_ms);
+    }
+
+    // Latences publication -> consommation par sujet, depuis le démarrage.
+    pub fn latency_stats(&self) -> Vec<TopicLatency> {
+        self.latencies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(topic, histogram)| TopicLatency {
+                topic: topic.clone(),
+                count: histogram.count,
+                p50_ms: histogram.percentile(0.50),
+                p95_ms: histogram.percentile(0.95),
+                max_ms: histogram.max_ms,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    // Valeur cumulée depuis le démarrage.
+    Counter,
+    // Valeur instantanée.
+    Gauge,
+}
+
+pub struct Metric {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub value: f64,
+    // Sujet concerné, pour les métriques par sujet.
+    pub topic: Option<String>,
+}
+
+impl Metric {
+    fn gauge(name: &'static str, value: usize) -> Self {
+        Self {
+            name,
+            kind: MetricKind::Gauge,
+            value: value as f64,
+            topic: None,
+        }
+    }
+
+    fn total(name: &'static str, value: &AtomicU64) -> Self {
+        Self {
+            name,
+            kind: MetricKind::Counter,
+            value: value.load(Ordering::Relaxed) as f64,
+            topic: None,
+        }
+    }
+
+    fn counter(name: &'static str, topic: &str, value: f64) -> Self {
+        Self {
+            name,
+            kind: MetricKind::Counter,
+            value,
+            topic: Some(topic.to_string()),
+        }
+    }
+}
+
+// Instantané des métriques du broker.
+pub async fn collect(state: &AppState) -> Vec<Metric> {
+    let mut metrics = vec![
+        Metric::gauge("pubsub_connections", state.connections.count().await),
+        Metric::gauge("pubsub_subscribers", state.broker.subscriber_count().await),
+        Metric::gauge("pubsub_db_queue_depth", state.broker.db_queue_depth()),
+        Metric::gauge("pubsub_scheduled_messages", state.broker.scheduled_count()),
+        Metric::total("pubsub_messages_total", &state.metrics.messages_published),
+        Metric::total(
+            "pubsub_consumptions_total",
+            &state.metrics.consumptions_recorded,
+        ),
+        Metric::total(
+            "pubsub_connections_opened_total",
+            &state.metrics.connections_opened,
+        ),
+        Metric::total(
+            "pubsub_subscriptions_total",
+            &state.metrics.subscriptions_registered,
+        ),
+        Metric::total(
+            "pubsub_disconnections_total",
+            &state.metrics.clients_disconnected,
+        ),
+    ];
+
+    let seqs = state.broker.topic_sequences();
+    let counters = state.delivery_counters.read().unwrap().clone();
+    let topics: BTreeSet<&String> = seqs.keys().chain(counters.keys()).collect();
+    for topic in topics {
+        let published = seqs.get(topic).copied().unwrap_or(0);
+        metrics.push(Metric::counter(
+            "pubsub_messages_published_total",
+            topic,
+            published as f64,
+        ));
+        if let Some(counters) = counters.get(topic) {
+            metrics.push(Metric::counter(
+                "pubsub_messages_delivered_total",
+                topic,
+                counters.delivered.load(Ordering::Relaxed) as f64,
+            ));
+            metrics.push(Metric::counter(
+                "pubsub_messages_dropped_total",
+                topic,
+                counters.dropped.load(Ordering::Relaxed) as f64,
+            ));
+            metrics.push(Metric::counter(
+                "pubsub_emit_failures_total",
+                topic,
+                counters.emit_failures.load(Ordering::Relaxed) as f64,
+            ));
+        }
+    }
+    metrics
+}
+
+// Format texte d'exposition Prometheus : les séries d'une même métrique sont regroupées
+// derrière sa ligne `# TYPE`, comme l'exige le format.
+pub fn render_prometheus(metrics: &[Metric]) -> String {
+    let mut grouped: Vec<(&'static str, MetricKind, Vec<&Metric>)> = Vec::new();
+    for metric in metrics {
+        match grouped.iter_mut().find(|(name, _, _)| *name == metric.name) {
+            Some((_, _, series)) => series.push(metric),
+            None => grouped.push((metric.name, metric.kind, vec![metric])),
+        }
+    }
+
+    let mut out = String::new();
+    for (name, kind, series) in grouped {
+        let kind = match kind {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        };
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for metric in series {
+            match &metric.topic {
+                Some(topic) => {
+                    let topic = topic
+                        .replace('\\', "\\\\")
+                        .replace('"', "\\\"")
+                        .replace('\n', "\\n");
+                    let _ = writeln!(out, "{}{{topic=\"{}\"}} {}", name, topic, metric.value);
+                }
+                None => {
+                    let _ = writeln!(out, "{} {}", name, metric.value);
+                }
+            }
+        }
+    }
+    out
+}
+
+enum PushTarget {
+    StatsD {
+        socket: UdpSocket,
+        addr: String,
+        // Dernière valeur envoyée de chaque compteur, pour calculer l'incrément.
+        previous: HashMap<(&'static str, Option<String>), f64>,
+    },
+    Otlp {
+        client: reqwest::Client,
+        url: String,
+        start_nanos: u128,
+    },
+}
+
+impl PushTarget {
+    async fn open(url: &str) -> Result<Self, String> {
+        if let Some(addr) = url
+            .strip_prefix("statsd://")
+            .or_else(|| url.strip_prefix("udp://"))
+        {
+            let socket = UdpSocket::bind("0.0.0.0:0")
+                .await
+                .map_err(|e| e.to_string())?;
+            return Ok(Self::StatsD {
+                socket,
+                addr: addr.trim_end_matches('/').to_string(),
+                previous: HashMap::new(),
+            });
+        }
+        if url.starts_with("http://") || url.starts_with("https://") {
+            return Ok(Self::Otlp {
+                client: reqwest::Client::new(),
+                url: url.to_string(),
+                start_nanos: unix_nanos(),
+            });
+        }
+        Err("unsupported scheme, use statsd://, udp://, http:// or https://".to_string())
+    }
+
+    async fn push(&mut self, metrics: &[Metric]) -> Result<(), String> {
+        match self {
+            Self::StatsD {
+                socket,
+                addr,
+                previous,
+            } => {
+                let mut lines = Vec::with_capacity(metrics.len());
+                for metric in metrics {
+                    let (value, kind) = match metric.kind {
+                        MetricKind::Gauge => (metric.value, "g"),
+                        MetricKind::Counter => {
+                            let key = (metric.name, metric.topic.clone());
+                            let last = previous.insert(key, metric.value).unwrap_or(0.0);
+                            // Compteur remis à zéro (sujet nettoyé puis recréé) : tout est nouveau.
+                            let delta = if metric.value >= last {
+                                metric.value - last
+                            } else {
+                                metric.value
+                            };
+                            if delta == 0.0 {
+                                continue;
+                            }
+                            (delta, "c")
+                        }
+                    };
+                    let tags = metric
+                        .topic
+                        .as_deref()
+                        .map(|topic| format!("|#topic:{}", topic))
+                        .unwrap_or_default();
+                    lines.push(format!("{}:{}|{}{}", metric.name, value, kind, tags));
+                }
+                for packet in pack_lines(&lines) {
+                    socket
+                        .send_to(packet.as_bytes(), addr.as_str())
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+            Self::Otlp {
+                client,
+                url,
+                start_nanos,
+            } => {
+                let body = otlp_request(metrics, *start_nanos, unix_nanos());
+                client
+                    .post(url.as_str())
+                    .timeout(PUSH_TIMEOUT)
+                    .json(&body)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+// Regroupe les lignes StatsD en datagrammes de taille bornée.
+fn pack_lines(lines: &[String]) -> Vec<String> {
+    let mut packets = Vec::new();
+    let mut packet = String::new();
+    for line in lines {
+        if !packet.is_empty() && packet.len() + 1 + line.len() > STATSD_MAX_PACKET {
+            packets.push(std::mem::take(&mut packet));
+        }
+        if !packet.is_empty() {
+            packet.push('\n');
+        }
+        packet.push_str(line);
+    }
+    if !packet.is_empty() {
+        packets.push(packet);
+    }
+    packets
+}
+
+// Corps OTLP/HTTP JSON : une métrique par nom, un point par sujet.
+fn otlp_request(metrics: &[Metric], start_nanos: u128, now_nanos: u128) -> serde_json::Value {
+    let mut grouped: Vec<(&'static str, MetricKind, Vec<serde_json::Value>)> = Vec::new();
+    for metric in metrics {
+        let mut point = serde_json::json!({
+            "asDouble": metric.value,
+            "timeUnixNano": now_nanos.to_string(),
+            "attributes": metric.topic.as_ref().map_or_else(Vec::new, |topic| {
+                vec![serde_json::json!({"key": "topic", "value": {"stringValue": topic}})]
+            }),
+        });
+        if metric.kind == MetricKind::Counter {
+            point["startTimeUnixNano"] = start_nanos.to_string().into();
+        }
+        match grouped.iter_mut().find(|(name, _, _)| *name == metric.name) {
+            Some((_, _, points)) => points.push(point),
+            None => grouped.push((metric.name, metric.kind, vec![point])),
+        }
+    }
+
+    let metrics: Vec<serde_json::Value> = grouped
+        .into_iter()
+        .map(|(name, kind, points)| match kind {
+            MetricKind::Gauge => serde_json::json!({
+                "name": name,
+                "gauge": {"dataPoints": points},
+            }),
+            // `aggregationTemporality` 2 : cumulative.
+            MetricKind::Counter => serde_json::json!({
+                "name": name,
+                "sum": {"dataPoints": points, "aggregationTemporality": 2, "isMonotonic": true},
+            }),
+        })
+        .collect();
+
+    serde_json::json!({
+        "resourceMetrics": [{
+            "resource": {
+                "attributes": [{"key": "service.name", "value": {"stringValue": env!("CARGO_PKG_NAME")}}],
+            },
+            "scopeMetrics": [{
+                "scope": {"name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION")},
+                "metrics": metrics,
+            }],
+        }],
+    })
+}
+
+// Pousse les métriques toutes les `METRICS_PUSH_INTERVAL` secondes vers `METRICS_PUSH_URL`.
+pub fn spawn_push_exporter(state: AppState, url: String, every: Duration) {
+    tokio::spawn(async move {
+        let mut target = match PushTarget::open(&url).await {
+            Ok(target) => target,
+            Err(reason) => {
+                warn!(
+                    "Metrics push disabled, invalid METRICS_PUSH_URL {}: {}",
+                    url, reason
+                );
+                return;
+            }
+        };
+        info!("Pushing metrics to {} every {:?}", url, every);
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            let metrics = collect(&state).await;
+            if let Err(e) = target.push(&metrics).await {
+                warn!("Metrics push to {} failed: {}", url, e);
+            }
+        }
+    });
+}
+
+fn unix_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
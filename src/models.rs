@@ -1,6 +1,8 @@
 // Ce fichier définit les structures de données (modèles) utilisées dans l'application.
 // Elles sont utilisées pour la sérialisation/désérialisation JSON et pour typer les données en mémoire.
+use crate::topic_pattern::{is_topic_pattern, validate_pattern};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 
 // `#[derive(Debug, Clone, Serialize, Deserialize)]`:
 // - `Debug`: Permet d'afficher la structure avec `println!("{:?}", ...)`.
@@ -8,14 +10,65 @@ use serde::{Deserialize, Serialize};
 // - `Serialize`: Permet de convertir la structure en JSON.
 // - `Deserialize`: Permet de convertir du JSON en cette structure.
 
+// Type de contenu d'une publication qui n'en déclare pas.
+pub const DEFAULT_CONTENT_TYPE: &str = "application/json";
+
 // Représente une requête de publication reçue par l'API.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PublishRequest {
+    // Peut être omis si `TOPIC_FROM_FIELD` permet de le déduire du contenu du message.
+    #[serde(default)]
     pub topic: String,
     pub message_id: String,
     // `serde_json::Value`: Type flexible pour représenter n'importe quelle donnée JSON valide.
     pub message: serde_json::Value,
     pub producer: String,
+    // Horodatage (secondes) fourni par le producteur ; à défaut, l'heure de réception du serveur.
+    // Rejeté s'il s'écarte de plus de `MAX_CLOCK_SKEW_SECS` de l'heure du serveur.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<f64>,
+    // Numéro de séquence attribué par le serveur au sein du sujet.
+    // Ignoré en entrée, renseigné avant l'émission aux abonnés.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<i64>,
+    // Numéro de séquence propre au producteur, tous sujets confondus (`PRODUCER_SEQUENCES`).
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub producer_seq: Option<i64>,
+    // Livraison différée (secondes) : le message est retenu jusqu'à cette date s'il est dans le futur.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_at: Option<f64>,
+    // Clé métier, obligatoire sur les sujets à clé unique (`UNIQUE_KEY_TOPICS`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    // Type de contenu de `message` (ex: `application/octet-stream`), `application/json` par défaut.
+    // Contrôlé par `TOPIC_CONTENT_TYPES` et transmis tel quel aux abonnés.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    // Durée de vie (secondes) à partir de `timestamp` : passé ce délai, le message n'est plus diffusé
+    // et il est supprimé à la purge suivante.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<f64>,
+    // Message retenu (façon MQTT) : remis immédiatement aux abonnés suivants du sujet.
+    // Un corps vide avec `retain` efface la valeur retenue.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retain: bool,
+    // Namespace Socket.IO de destination (`/` par défaut), parmi `SOCKETIO_NAMESPACES`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    // Nœuds de fédération déjà traversés (`FEDERATION_NODE_ID`), vide pour une publication locale.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub via: Vec<String>,
+}
+
+// Confirmation légère d'une publication acceptée, émise sur le sujet `PUBLISH_CONFIRMATIONS_TOPIC`.
+// Reprend les métadonnées de la publication, sans son contenu.
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishConfirmation {
+    pub topic: String,
+    pub message_id: String,
+    pub producer: String,
+    pub seq: i64,
+    pub timestamp: f64,
 }
 
 // Informations sur un client connecté.
@@ -24,6 +77,19 @@ pub struct ClientInfo {
     pub consumer: String,
     pub topic: String,
     pub connected_at: f64,
+    // Aucune trame reçue depuis `CLIENT_STALE_SECS` : connexion possiblement à demi ouverte.
+    pub stale: bool,
+    // Étiquettes déclarées à l'abonnement (`SubscribeMessage::tags`).
+    pub tags: BTreeMap<String, String>,
+}
+
+// Abonnements d'une connexion (`GET /subscriptions`) : une entrée par SID, avec tous ses sujets.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionInfo {
+    pub sid: String,
+    pub consumer: String,
+    pub topics: Vec<String>,
+    pub connected_at: f64,
 }
 
 // Informations sur un message stocké.
@@ -34,6 +100,27 @@ pub struct MessageInfo {
     pub message: serde_json::Value,
     pub producer: String,
     pub timestamp: f64,
+    // Mêmes champs que la trame livrée en direct (absents des lignes antérieures à leur introduction).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producer_seq: Option<i64>,
+    // Date d'expiration des messages publiés avec `ttl_seconds`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<f64>,
+}
+
+// Message resté sans consommation au-delà de `DEAD_LETTER_GRACE_SECS` (`GET /dead-letters`).
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetterInfo {
+    pub topic: String,
+    pub message_id: String,
+    pub message: serde_json::Value,
+    pub producer: String,
+    // Horodatage de la publication.
+    pub published_at: f64,
+    // Horodatage du balayage qui l'a relevé.
+    pub detected_at: f64,
 }
 
 // Informations sur une consommation de message.
@@ -47,7 +134,7 @@ pub struct ConsumptionInfo {
 }
 
 // État complet du graphe pour l'affichage du tableau de bord.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GraphState {
     pub producers: Vec<String>,
     pub consumers: Vec<String>,
@@ -56,7 +143,7 @@ pub struct GraphState {
 }
 
 // Représente un lien dans le graphe (ex: producteur -> sujet).
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Link {
     pub source: String,
     pub target: String,
@@ -71,27 +158,446 @@ pub struct Link {
 pub struct HealthStatus {
     pub status: String,
     pub timestamp: f64,
+    // Cause d'un échec de la sonde d'écriture (`DEEP_HEALTH_CHECK`) :
+    // `read_only`, `disk_full` ou `write_failed` ; `unavailable` si aucune connexion n'est disponible.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
 }
 
 // Message WebSocket pour s'abonner à des sujets.
 #[derive(Debug, Deserialize)]
 pub struct SubscribeMessage {
+    // Optionnel en mode `CONSUMER_IDENTITY=connection`, où l'identité de la connexion prime.
+    #[serde(default)]
     pub consumer: String,
     pub topics: Vec<String>,
+    // Fenêtre de regroupement des livraisons (WebSocket) : si définie, les messages de chaque sujet
+    // sont accumulés pendant cette durée puis livrés en une seule trame `batch`.
+    #[serde(default)]
+    pub batch_window_ms: Option<u64>,
+    // Niveau de garantie de livraison (WebSocket) : 0 = best-effort (défaut), 1 = au moins une fois,
+    // avec renvoi jusqu'à l'accusé `consumed`. Les autres niveaux sont refusés.
+    #[serde(default)]
+    pub qos: u8,
+    // Débit d'envoi maximal de la connexion (WebSocket), en messages/s et en octets/s ;
+    // remplace `WS_MAX_SEND_RATE` / `WS_MAX_SEND_BYTES_PER_SEC`, 0 = pas de limite.
+    #[serde(default)]
+    pub max_rate: Option<u64>,
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+    // Étiquettes clé/valeur de la connexion (ex: `{"region": "eu"}`), exposées par `GET /clients`.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    // Groupe de consommateurs : chaque message des sujets n'est livré qu'à un membre du groupe,
+    // à tour de rôle, au lieu de l'être à tous les abonnés.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+// Limites des étiquettes d'une connexion.
+const MAX_TAGS: usize = 16;
+const MAX_TAG_KEY_LEN: usize = 64;
+const MAX_TAG_VALUE_LEN: usize = 256;
+
+impl SubscribeMessage {
+    // Retire les sujets en double (la première occurrence est gardée), pour qu'un même sujet
+    // ne soit pas abonné deux fois. Une liste vide ou un motif invalide (`orders.#.eu`) sont refusés.
+    pub fn validate_topics(&mut self) -> Result<(), &'static str> {
+        let mut seen = HashSet::new();
+        self.topics.retain(|topic| seen.insert(topic.clone()));
+        if self.topics.is_empty() {
+            return Err("topics must not be empty");
+        }
+        self.topics
+            .iter()
+            .try_for_each(|topic| validate_pattern(topic))
+    }
+
+    // Refuse les étiquettes trop nombreuses ou trop longues.
+    pub fn validate_tags(&self) -> Result<(), &'static str> {
+        if self.tags.len() > MAX_TAGS {
+            return Err("too many tags (max 16)");
+        }
+        for (key, value) in &self.tags {
+            if key.is_empty() || key.len() > MAX_TAG_KEY_LEN {
+                return Err("tag keys must be 1 to 64 bytes long");
+            }
+            if value.len() > MAX_TAG_VALUE_LEN {
+                return Err("tag values must be at most 256 bytes long");
+            }
+        }
+        Ok(())
+    }
+
+    // Un groupe porte un nom et des sujets concrets (ni motif, ni `*`), en QoS 0 uniquement.
+    pub fn validate_group(&self) -> Result<(), &'static str> {
+        let Some(group) = &self.group else {
+            return Ok(());
+        };
+        if group.is_empty() {
+            return Err("group must not be empty");
+        }
+        if self
+            .topics
+            .iter()
+            .any(|topic| topic == "*" || is_topic_pattern(topic))
+        {
+            return Err("consumer groups require concrete topics, not patterns");
+        }
+        if self.qos != 0 {
+            return Err("qos 1 is not supported with consumer groups");
+        }
+        Ok(())
+    }
 }
 
 // Message WebSocket confirmant la consommation d'un message.
 #[derive(Debug, Deserialize)]
 pub struct ConsumedMessage {
+    #[serde(default)]
     pub consumer: String,
     pub topic: String,
     pub message_id: String,
     pub message: serde_json::Value,
 }
 
+// Une confirmation d'une trame `consumed_batch`.
+#[derive(Debug, Deserialize)]
+pub struct ConsumedAck {
+    pub topic: String,
+    pub message_id: String,
+    pub message: serde_json::Value,
+}
+
+// Confirmation groupée de plusieurs consommations (événement `consumed_batch`).
+#[derive(Debug, Deserialize)]
+pub struct ConsumedBatchMessage {
+    #[serde(default)]
+    pub consumer: String,
+    pub acks: Vec<ConsumedAck>,
+}
+
+// Désabonnement d'un sujet sans fermer la connexion (événement `unsubscribe`).
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeMessage {
+    pub topic: String,
+}
+
+// Corps de `POST /unsubscribe` : retire un sujet des abonnements de la connexion `sid`.
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeRequest {
+    pub sid: String,
+    pub topic: String,
+}
+
+// Message WebSocket établissant l'identité `consumer` de la connexion.
+#[derive(Debug, Deserialize)]
+pub struct IdentifyMessage {
+    pub consumer: String,
+}
+
+// Message WebSocket demandant la relecture des messages d'un sujet publiés après `since`.
+#[derive(Debug, Deserialize)]
+pub struct ReplayMessage {
+    pub topic: String,
+    pub since: f64,
+}
+
+// Réponse d'un client WebSocket à une trame `request` du serveur.
+#[derive(Debug, Deserialize)]
+pub struct ResponseMessage {
+    pub request_id: String,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
 // Événement générique à diffuser via le `Broker`.
 #[derive(Debug, Clone, Serialize)]
 pub struct BroadcastEvent {
     pub event_type: String,
     pub data: serde_json::Value,
 }
+
+// Événement du broker persisté dans le journal d'audit (`GET /events`).
+#[derive(Debug, Clone, Serialize)]
+pub struct EventInfo {
+    pub event_type: String,
+    pub data: serde_json::Value,
+    pub timestamp: f64,
+}
+
+// Messages manqués d'un sujet (`GET /replay`), du plus ancien au plus récent.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayPage {
+    pub topic: String,
+    pub messages: Vec<MessageInfo>,
+    // Vrai si d'autres messages suivent au-delà de `REPLAY_MAX_MESSAGES` :
+    // relancer avec `since` égal à l'horodatage du dernier message reçu.
+    pub truncated: bool,
+}
+
+// Retard estimé d'un consommateur sur un sujet (`GET /consumers/{name}/backlog`).
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicBacklog {
+    pub topic: String,
+    // Dernier `seq` attribué sur le sujet.
+    pub head_seq: i64,
+    // Plus haut `seq` parmi les messages dont le consommateur a confirmé la consommation.
+    // `None` s'il n'en a confirmé aucun (ou si ces messages ont été purgés).
+    pub acked_seq: Option<i64>,
+    // Estimation du nombre de messages restant à consommer (`head_seq - acked_seq`).
+    pub backlog: i64,
+}
+
+// Abonnements d'une connexion, tels que vus par le cache en mémoire du broker.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionSnapshot {
+    pub consumer: String,
+    pub topics: Vec<String>,
+    pub connected_at: f64,
+}
+
+// Canal de diffusion actif d'un sujet.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicChannelInfo {
+    pub topic: String,
+    // Récepteurs du canal primaire (dont un par relais).
+    pub receivers: usize,
+    // Canaux relais créés au-delà de `TOPIC_SUBSCRIBER_CAP`.
+    pub relays: usize,
+}
+
+// Livraisons d'un sujet aux abonnés WebSocket (`GET /topics/{topic}/rate`), depuis le démarrage.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicDeliveryStats {
+    pub topic: String,
+    // Dernier numéro de séquence attribué (nombre de publications sur le sujet).
+    pub head_seq: i64,
+    // Messages transmis aux connexions abonnées (une fois par abonné).
+    pub delivered: u64,
+    // Messages perdus par des abonnés trop lents (`Lagged`), y compris par les relais.
+    pub dropped: u64,
+}
+
+// Instantané de l'état en mémoire (`GET /admin/debug/state`), qui peut diverger de la base.
+#[derive(Debug, Clone, Serialize)]
+pub struct DebugState {
+    // sid -> abonnements.
+    pub subscriptions: std::collections::HashMap<String, SubscriptionSnapshot>,
+    pub topic_channels: Vec<TopicChannelInfo>,
+    pub dashboard_enabled: bool,
+}
+
+// Débit d'un sujet entre deux instantanés de statistiques.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicRate {
+    pub topic: String,
+    // Dernier `seq` attribué.
+    pub seq: i64,
+    // Messages par seconde depuis l'instantané précédent.
+    pub rate: f64,
+}
+
+// Statistiques agrégées poussées au dashboard (événement Socket.IO `stats_snapshot`).
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsSnapshot {
+    pub timestamp: f64,
+    // Connexions actives (WebSocket brut et Socket.IO).
+    pub connections: usize,
+    // Connexions ayant au moins un abonnement.
+    pub subscribers: usize,
+    // Commandes en attente d'écriture en base.
+    pub db_queue_depth: usize,
+    // Livraisons différées en attente.
+    pub scheduled: usize,
+    // Sujets actifs depuis l'instantané précédent, par débit décroissant.
+    pub topics: Vec<TopicRate>,
+}
+
+// Nombre de consommateurs abonnés à un sujet (`GET /topics`). Les abonnés wildcard
+// (`*`, `__all__`) sont comptés à part, dans une entrée synthétique `*` marquée `wildcard`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicStat {
+    pub topic: String,
+    pub subscriber_count: usize,
+    pub wildcard: bool,
+}
+
+// Latence publication -> consommation d'un sujet (`GET /stats/latency`), en millisecondes.
+// Les percentiles sont estimés par intervalles d'histogramme (borne supérieure de l'intervalle).
+#[derive(Debug, Serialize)]
+pub struct TopicLatency {
+    pub topic: String,
+    // Consommations mesurées.
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+// Paramètres de requête de `GET /messages`.
+#[derive(Debug, Deserialize)]
+pub struct MessagesQuery {
+    // Ne garde que les messages de ce sujet.
+    pub topic: Option<String>,
+    // Nombre maximal de messages (100 par défaut, plafonné à 1000).
+    pub limit: Option<i64>,
+    // Curseur de pagination : ne garde que les messages antérieurs à cet horodatage.
+    pub before: Option<f64>,
+}
+
+// Table exportée par `GET /export`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportTable {
+    Messages,
+    Consumptions,
+}
+
+// Paramètres de requête de `GET /export`.
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub table: ExportTable,
+}
+
+// Ligne de `POST /import` : un message au format de `GET /export`. `seq` et `producer_seq` sont
+// ignorés (renumérotés à l'import) ; l'horodatage d'origine est conservé.
+#[derive(Debug, Deserialize)]
+pub struct ImportRecord {
+    pub topic: String,
+    pub message_id: String,
+    pub message: serde_json::Value,
+    pub producer: String,
+    pub timestamp: f64,
+    #[serde(default)]
+    pub expires_at: Option<f64>,
+}
+
+// Bilan de `POST /import`.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+// Paramètres de requête de `DELETE /messages`.
+#[derive(Debug, Deserialize)]
+pub struct DeleteMessagesQuery {
+    pub topic: String,
+}
+
+// Paramètres de requête de `GET /dead-letters`.
+#[derive(Debug, Deserialize)]
+pub struct DeadLettersQuery {
+    // Ne garde que les lettres mortes de ce sujet.
+    pub topic: Option<String>,
+    // Nombre maximal de lignes (100 par défaut, plafonné à 1000).
+    pub limit: Option<i64>,
+}
+
+// Paramètres de requête de `GET /replay`.
+#[derive(Debug, Deserialize)]
+pub struct ReplayQuery {
+    pub topic: String,
+    // Horodatage du dernier message reçu : seuls les messages publiés après sont relus.
+    pub since: f64,
+}
+
+// Paramètres de requête de `POST /publish/binary` : le corps de la requête est le contenu brut.
+#[derive(Debug, Deserialize)]
+pub struct BinaryPublishQuery {
+    pub topic: String,
+    pub message_id: String,
+    pub producer: String,
+}
+
+// Paramètres de requête de `GET /consumptions`.
+#[derive(Debug, Deserialize)]
+pub struct ConsumptionsQuery {
+    // Nombre maximal de consommations (100 par défaut, plafonné à 1000).
+    pub limit: Option<i64>,
+    // Curseur de pagination : ne garde que les consommations antérieures à cet horodatage.
+    pub before: Option<f64>,
+}
+
+// Paramètres de requête de `GET /graph/state`.
+#[derive(Debug, Deserialize)]
+pub struct GraphQuery {
+    // Ne garde que les sujets abonnés ou actifs récemment (`GRAPH_LIVE_WINDOW_SECS`).
+    #[serde(default)]
+    pub live: bool,
+}
+
+// Paramètres de requête de `GET /graph/history`.
+#[derive(Debug, Deserialize)]
+pub struct GraphHistoryQuery {
+    // Date (secondes Unix) de l'état demandé.
+    pub at: f64,
+}
+
+// Paramètres de requête de `GET /events`.
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    // Ne retourne que les événements strictement postérieurs à ce timestamp.
+    pub since: Option<f64>,
+    // Filtre sur le type d'événement (ex: `new_message`).
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+}
+
+// Paramètres de requête de `GET /events/stream`.
+#[derive(Debug, Deserialize)]
+pub struct EventStreamQuery {
+    // Types d'événements à transmettre, séparés par des virgules (ex: `new_message,new_client`) ; tous par défaut.
+    pub types: Option<String>,
+}
+
+// Requête de connexion au dashboard (`POST /dashboard/login`).
+#[derive(Debug, Deserialize)]
+pub struct DashboardLoginRequest {
+    #[serde(default)]
+    pub password: String,
+}
+
+// Requête de changement de stratégie d'émission (`POST /admin/emit-strategy`).
+#[derive(Debug, Deserialize)]
+pub struct EmitStrategyRequest {
+    pub strategy: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscribe(frame: serde_json::Value) -> SubscribeMessage {
+        serde_json::from_value(frame).unwrap()
+    }
+
+    #[test]
+    fn empty_topics_are_rejected() {
+        let mut msg = subscribe(serde_json::json!({"consumer": "c", "topics": []}));
+        assert_eq!(msg.validate_topics(), Err("topics must not be empty"));
+    }
+
+    #[test]
+    fn duplicate_topics_are_subscribed_once() {
+        let mut msg = subscribe(serde_json::json!({"consumer": "c", "topics": ["a", "a"]}));
+        assert_eq!(msg.validate_topics(), Ok(()));
+        assert_eq!(msg.topics, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn duplicates_keep_first_occurrence_order() {
+        let mut msg =
+            subscribe(serde_json::json!({"consumer": "c", "topics": ["b", "a", "b", "c", "a"]}));
+        assert_eq!(msg.validate_topics(), Ok(()));
+        assert_eq!(msg.topics, vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let mut msg =
+            subscribe(serde_json::json!({"consumer": "c", "topics": ["a", "orders.#.eu"]}));
+        assert!(msg.validate_topics().is_err());
+    }
+}
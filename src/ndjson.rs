@@ -0,0 +1,65 @@
+// Découpage d'un corps NDJSON en lignes, au fil des morceaux reçus (`POST /import`).
+//
+// Le corps n'est jamais chargé en entier : seule la ligne en cours est gardée en mémoire. Une ligne
+// plus longue que `max_line_bytes` est abandonnée (et comptée) sans être accumulée jusqu'à sa fin.
+pub struct LineSplitter {
+    // Début de la ligne en cours, reçu dans les morceaux précédents.
+    pending: Vec<u8>,
+    // La ligne en cours a dépassé la limite : ses octets sont ignorés jusqu'au prochain saut de ligne.
+    discarding: bool,
+    max_line_bytes: usize,
+    // Lignes abandonnées car trop longues.
+    pub oversized: usize,
+}
+
+impl LineSplitter {
+    pub fn new(max_line_bytes: usize) -> Self {
+        Self {
+            pending: Vec::new(),
+            discarding: false,
+            max_line_bytes,
+            oversized: 0,
+        }
+    }
+
+    // Ajoute un morceau du corps et retourne les lignes qu'il complète (lignes vides exclues).
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        let mut lines = Vec::new();
+        let mut segments = chunk.split(|&byte| byte == b'\n').peekable();
+        while let Some(segment) = segments.next() {
+            self.append(segment);
+            // Le dernier segment n'est pas suivi d'un saut de ligne : la ligne continue au morceau suivant.
+            if segments.peek().is_none() {
+                break;
+            }
+            if let Some(line) = self.take_line() {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    // Dernière ligne du corps, s'il ne se termine pas par un saut de ligne.
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        self.take_line()
+    }
+
+    fn append(&mut self, segment: &[u8]) {
+        if self.discarding {
+            return;
+        }
+        if self.pending.len() + segment.len() > self.max_line_bytes {
+            self.pending = Vec::new();
+            self.discarding = true;
+            self.oversized += 1;
+            return;
+        }
+        self.pending.extend_from_slice(segment);
+    }
+
+    fn take_line(&mut self) -> Option<Vec<u8>> {
+        self.discarding = false;
+        let line = std::mem::take(&mut self.pending);
+        (!line.trim_ascii().is_empty()).then_some(line)
+    }
+}
@@ -0,0 +1,68 @@
+// Limitation du débit d'envoi d'une connexion WebSocket (`WS_MAX_SEND_RATE`, `WS_MAX_SEND_BYTES_PER_SEC`).
+//
+// La tâche d'envoi espace les trames de données pour ne pas dépasser le débit configuré : une rafale
+// sur un sujet chaud est lissée au lieu de saturer un lien lent (mobile, IoT). Les trames en attente
+// s'accumulent dans le canal interne jusqu'à `WS_SEND_BUFFER`, au-delà les plus anciennes sont abandonnées.
+// Les limites sont fixées par connexion et peuvent être redéfinies par une trame `subscribe`.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Limites courantes d'une connexion ; 0 = pas de limite.
+pub struct SendLimits {
+    messages_per_sec: AtomicU64,
+    bytes_per_sec: AtomicU64,
+}
+
+impl SendLimits {
+    pub fn new(messages_per_sec: u64, bytes_per_sec: u64) -> Self {
+        Self {
+            messages_per_sec: AtomicU64::new(messages_per_sec),
+            bytes_per_sec: AtomicU64::new(bytes_per_sec),
+        }
+    }
+
+    // Redéfinit les limites fournies, les autres restent inchangées.
+    pub fn update(&self, messages_per_sec: Option<u64>, bytes_per_sec: Option<u64>) {
+        if let Some(limit) = messages_per_sec {
+            self.messages_per_sec.store(limit, Ordering::Relaxed);
+        }
+        if let Some(limit) = bytes_per_sec {
+            self.bytes_per_sec.store(limit, Ordering::Relaxed);
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.messages_per_sec.load(Ordering::Relaxed) > 0
+            || self.bytes_per_sec.load(Ordering::Relaxed) > 0
+    }
+
+    // Temps d'émission « consommé » par une trame de `bytes` octets.
+    fn cost(&self, bytes: usize) -> Duration {
+        let per_message = match self.messages_per_sec.load(Ordering::Relaxed) {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(1.0 / rate as f64),
+        };
+        let per_bytes = match self.bytes_per_sec.load(Ordering::Relaxed) {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(bytes as f64 / rate as f64),
+        };
+        per_message.max(per_bytes)
+    }
+}
+
+// Cadence d'envoi : chaque trame réserve le créneau suivant, la suivante attend sa fin.
+#[derive(Default)]
+pub struct Pacer {
+    ready_at: Option<Instant>,
+}
+
+impl Pacer {
+    // Attend que la trame de `bytes` octets puisse partir sans dépasser les limites.
+    pub async fn wait(&mut self, limits: &SendLimits, bytes: usize) {
+        if let Some(ready_at) = self.ready_at {
+            tokio::time::sleep_until(ready_at).await;
+        }
+        self.ready_at = Some(Instant::now() + limits.cost(bytes));
+    }
+}
@@ -0,0 +1,147 @@
+// Pipeline de transformation des messages publiés, appliqué avant stockage et émission.
+//
+// Configuré de façon déclarative par un fichier JSON (`PIPELINE_CONFIG`) :
+//
+// {
+//   "global": [ { "type": "inject_timestamp", "field": "received_at" } ],
+//   "topics": {
+//     "orders": [
+//       { "type": "require_fields", "fields": ["order_id"] },
+//       { "type": "redact", "fields": ["card.number"] },
+//       { "type": "rewrite_topic", "to": "orders.v2" }
+//     ]
+//   }
+// }
+//
+// Les processeurs `global` s'exécutent d'abord, puis ceux du sujet d'origine de la publication,
+// dans l'ordre déclaré. Sans fichier, le pipeline est vide et ne modifie rien.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+// Processeurs intégrés. Les chemins de champs utilisent la notation pointée (ex: `meta.user.email`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Processor {
+    // Remplace la valeur des champs existants par `replacement`.
+    Redact {
+        fields: Vec<String>,
+        #[serde(default = "default_replacement")]
+        replacement: serde_json::Value,
+    },
+    // Ajoute (ou écrase) un champ contenant l'horodatage de réception, en secondes.
+    InjectTimestamp {
+        field: String,
+    },
+    // Publie le message sur un autre sujet.
+    RewriteTopic {
+        to: String,
+    },
+    // Rejette la publication si l'un des champs est absent ou `null`.
+    RequireFields {
+        fields: Vec<String>,
+    },
+}
+
+fn default_replacement() -> serde_json::Value {
+    serde_json::Value::String("[REDACTED]".to_string())
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Pipeline {
+    #[serde(default)]
+    global: Vec<Processor>,
+    #[serde(default)]
+    topics: HashMap<String, Vec<Processor>>,
+}
+
+impl Pipeline {
+    // Charge le pipeline depuis `path`, ou retourne un pipeline vide si aucun fichier n'est configuré.
+    // Une erreur de lecture ou de syntaxe est fatale : ignorer une règle de masquage serait pire qu'un refus de démarrer.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("lecture du pipeline {} impossible: {}", path, e))?;
+        serde_json::from_str(&raw).map_err(|e| format!("pipeline {} invalide: {}", path, e))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.global.is_empty() && self.topics.is_empty()
+    }
+
+    // Applique les processeurs à la publication ; `Err` contient la raison d'un rejet.
+    pub fn apply(&self, topic: &mut String, message: &mut serde_json::Value) -> Result<(), String> {
+        let topic_processors = self
+            .topics
+            .get(topic.as_str())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for processor in self.global.iter().chain(topic_processors) {
+            match processor {
+                Processor::Redact {
+                    fields,
+                    replacement,
+                } => {
+                    for path in fields {
+                        if let Some(value) = field_mut(message, path) {
+                            *value = replacement.clone();
+                        }
+                    }
+                }
+                Processor::InjectTimestamp { field } => {
+                    let now = SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs_f64();
+                    insert_field(message, field, serde_json::json!(now))?;
+                }
+                Processor::RewriteTopic { to } => *topic = to.clone(),
+                Processor::RequireFields { fields } => {
+                    if let Some(missing) = fields.iter().find(|path| {
+                        path.split('.')
+                            .try_fold(&*message, |value, key| value.get(key))
+                            .is_none_or(|value| value.is_null())
+                    }) {
+                        return Err(format!("champ requis manquant: {}", missing));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Accès mutable à un champ existant, au chemin pointé `path`.
+fn field_mut<'a>(
+    message: &'a mut serde_json::Value,
+    path: &str,
+) -> Option<&'a mut serde_json::Value> {
+    path.split('.')
+        .try_fold(message, |value, key| value.get_mut(key))
+}
+
+// Écrit `value` au chemin pointé `path`, en créant les objets intermédiaires manquants.
+// Échoue si le message (ou un parent du champ) n'est pas un objet JSON.
+fn insert_field(
+    message: &mut serde_json::Value,
+    path: &str,
+    value: serde_json::Value,
+) -> Result<(), String> {
+    let mut current = message;
+    let mut keys = path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let object = current
+            .as_object_mut()
+            .ok_or_else(|| format!("impossible d'écrire {}: le parent n'est pas un objet", path))?;
+        if keys.peek().is_none() {
+            object.insert(key.to_string(), value);
+            return Ok(());
+        }
+        current = object
+            .entry(key.to_string())
+            .or_insert_with(|| serde_json::json!({}));
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+// Suivi des livraisons QoS 1 (« au moins une fois ») d'une connexion WebSocket.
+//
+// - QoS 0 : diffusion best-effort, aucun suivi (comportement historique).
+// - QoS 1 : chaque message livré reste « en vol » jusqu'à la trame `consumed` correspondante
+//   (même `topic` et `message_id`). Sans accusé dans `QOS_ACK_TIMEOUT_MS`, il est renvoyé,
+//   au plus `QOS_MAX_REDELIVERIES` fois, puis abandonné avec un avertissement.
+// - `PERSIST_DELIVERY_ACKS` : l'état de chaque livraison QoS 1 (`delivered`, puis `acked`) est persisté
+//   par consommateur (voir `DeliveryLog`). Un message déjà acquitté n'est plus relivré à ce consommateur
+//   (republication, relais fédéré) ; un message livré mais jamais acquitté (connexion perdue, renvois
+//   épuisés) est renvoyé au prochain abonnement QoS 1 du consommateur au même sujet. Le consommateur
+//   voit donc chaque message au moins une fois, et plus jamais après l'avoir acquitté.
+use crate::broker::Broker;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// Niveaux de QoS acceptés dans `SubscribeMessage.qos`.
+pub const QOS_AT_MOST_ONCE: u8 = 0;
+pub const QOS_AT_LEAST_ONCE: u8 = 1;
+
+struct InFlight {
+    frame: String,
+    deadline: Instant,
+    redeliveries: u32,
+}
+
+pub struct InFlightTracker {
+    ack_timeout: Duration,
+    max_redeliveries: u32,
+    // (topic, message_id) -> livraison en attente d'accusé.
+    // `std::sync::Mutex` : sections critiques courtes, jamais à cheval sur un `await`.
+    pending: Mutex<HashMap<(String, String), InFlight>>,
+}
+
+impl InFlightTracker {
+    pub fn new(ack_timeout: Duration, max_redeliveries: u32) -> Self {
+        Self {
+            ack_timeout,
+            max_redeliveries,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Enregistre une livraison QoS 1. Les trames sans `topic`/`message_id` ne peuvent pas être
+    // acquittées : elles ne sont pas suivies.
+    pub fn track(&self, frame: &str) {
+        let Some(key) = frame_key(frame) else {
+            return;
+        };
+        self.pending.lock().unwrap().insert(
+            key,
+            InFlight {
+                frame: frame.to_string(),
+                deadline: Instant::now() + self.ack_timeout,
+                redeliveries: 0,
+            },
+        );
+    }
+
+    // Retire la livraison acquittée par une trame `consumed`.
+    pub fn ack(&self, topic: &str, message_id: &str) {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(&(topic.to_string(), message_id.to_string()));
+    }
+
+    // Retourne les trames à renvoyer (accusé en retard) et abandonne celles qui ont épuisé leurs tentatives.
+    pub fn due(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        let mut frames = Vec::new();
+        pending.retain(|(topic, message_id), in_flight| {
+            if in_flight.deadline > now {
+                return true;
+            }
+            if in_flight.redeliveries >= self.max_redeliveries {
+                warn!(
+                    "QoS 1: message {} on {} dropped after {} redeliveries without ack",
+                    message_id, topic, in_flight.redeliveries
+                );
+                return false;
+            }
+            in_flight.redeliveries += 1;
+            in_flight.deadline = now + self.ack_timeout;
+            frames.push(in_flight.frame.clone());
+            true
+        });
+        frames
+    }
+
+    // Intervalle de vérification des échéances.
+    pub fn tick(&self) -> Duration {
+        (self.ack_timeout / 4).max(Duration::from_millis(50))
+    }
+}
+
+// Journal persistant des livraisons QoS 1 d'un consommateur (`PERSIST_DELIVERY_ACKS`).
+#[derive(Clone)]
+pub struct DeliveryLog {
+    broker: Arc<Broker>,
+    consumer: String,
+}
+
+impl DeliveryLog {
+    pub fn new(broker: Arc<Broker>, consumer: String) -> Self {
+        Self { broker, consumer }
+    }
+
+    // Prépare la livraison de `frame` : retourne `false` si le consommateur l'a déjà acquittée
+    // (elle ne doit pas être envoyée), sinon l'enregistre comme `delivered`.
+    pub async fn deliver(&self, frame: &str) -> bool {
+        let Some((topic, message_id)) = frame_key(frame) else {
+            return true;
+        };
+        if self
+            .broker
+            .delivery_acked(&self.consumer, &topic, &message_id)
+            .await
+        {
+            return false;
+        }
+        self.broker
+            .record_delivery(&self.consumer, &topic, &message_id, frame);
+        true
+    }
+}
+
+// Identifie une livraison par (`topic`, `message_id`) ; `None` pour les trames qui n'en portent pas.
+fn frame_key(frame: &str) -> Option<(String, String)> {
+    let value = serde_json::from_str::<serde_json::Value>(frame).ok()?;
+    Some((
+        value.get("topic")?.as_str()?.to_string(),
+        value.get("message_id")?.as_str()?.to_string(),
+    ))
+}
@@ -0,0 +1,94 @@
+// Limitation du débit de publication par producteur (`PUBLISH_RATE_PER_SEC`).
+//
+// Chaque producteur (champ `producer` de la publication) dispose d'un seau de jetons : une seconde
+// de débit en capacité, rechargée en continu. Une publication sans jeton disponible est refusée
+// (`429`) avec le délai avant le prochain jeton, pour qu'un producteur emballé n'affame pas les autres.
+// Les seaux pleins des producteurs inactifs sont évincés périodiquement.
+use crate::config::Config;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// Période de l'éviction des seaux inactifs.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+pub struct ProducerRateLimiter {
+    // Jetons rechargés par seconde.
+    rate: f64,
+    // Jetons au plus dans un seau (une seconde de débit, au moins un).
+    capacity: f64,
+    // `std::sync::Mutex` : sections courtes, sans `await`.
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl ProducerRateLimiter {
+    // Retourne `None` si la limite est désactivée (`PUBLISH_RATE_PER_SEC` à 0).
+    pub fn from_config(config: &Config) -> Option<Arc<Self>> {
+        let rate = config.publish_rate_per_sec;
+        if !rate.is_finite() || rate <= 0.0 {
+            return None;
+        }
+        info!(
+            "Publish rate limit enabled: {} messages/s per producer",
+            rate
+        );
+        Some(Arc::new(Self {
+            rate,
+            capacity: rate.max(1.0),
+            buckets: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    // Consomme un jeton du producteur ; sinon retourne l'attente avant le prochain jeton.
+    pub fn check(&self, producer: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(producer.to_string())
+            .or_insert_with(|| Bucket {
+                tokens: self.capacity,
+                updated_at: now,
+            });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.capacity);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+
+    // Oublie les producteurs dont le seau s'est entièrement rechargé : les recréer plus tard
+    // à pleine capacité ne change rien à leur budget.
+    fn evict_idle(&self) -> usize {
+        let now = Instant::now();
+        let refill = Duration::from_secs_f64(self.capacity / self.rate);
+        let mut buckets = self.buckets.lock().unwrap();
+        let before = buckets.len();
+        buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < refill);
+        before - buckets.len()
+    }
+
+    // Lance l'éviction périodique des seaux inactifs.
+    pub fn spawn_eviction(self: &Arc<Self>) {
+        let limiter = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+            loop {
+                interval.tick().await;
+                let evicted = limiter.evict_idle();
+                if evicted > 0 {
+                    info!("Publish rate limit: evicted {} idle producers", evicted);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,289 @@
+// Registre des connexions actives (WebSocket brut et Socket.IO).
+//
+// Associe chaque `sid` à un moyen d'atteindre sa connexion et au `consumer` qu'elle représente.
+// Cela permet au serveur d'adresser une trame à un client précis, d'attendre sa réponse
+// (corrélée par `request_id`), et d'appliquer la politique de connexions en double.
+use crate::config::DuplicatePolicy;
+use axum::extract::ws::{close_code, CloseFrame, Message};
+use socketioxide::extract::SocketRef;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::task::JoinHandle;
+use tracing::info;
+use uuid::Uuid;
+
+// Échec d'une requête serveur -> client.
+#[derive(Debug)]
+pub enum RequestError {
+    // Aucune connexion WebSocket active pour ce `sid`.
+    NotFound,
+    // Le client n'a pas répondu dans le délai imparti.
+    Timeout,
+    // La connexion s'est fermée avant la réponse.
+    Disconnected,
+}
+
+// Tâches de relais d'une connexion WebSocket brute, une par sujet abonné.
+pub type TopicTasks = Arc<RwLock<HashMap<String, JoinHandle<()>>>>;
+
+// Moyen d'atteindre une connexion selon son transport.
+#[derive(Clone)]
+pub enum ConnectionHandle {
+    // Canal interne d'une connexion WebSocket brute (consommé par sa tâche d'envoi)
+    // et ses tâches d'abonnement.
+    WebSocket(mpsc::UnboundedSender<Message>, TopicTasks),
+    // Socket d'un client Socket.IO.
+    SocketIo(SocketRef),
+}
+
+impl ConnectionHandle {
+    // Notifie le client puis ferme sa connexion.
+    pub fn close(&self, reason: &str) {
+        match self {
+            Self::WebSocket(sender, _) => {
+                let frame = serde_json::json!({"event": "evicted", "reason": reason});
+                let _ = sender.send(Message::Text(frame.to_string().into()));
+                let _ = sender.send(Message::Close(Some(CloseFrame {
+                    code: close_code::POLICY,
+                    reason: reason.to_string().into(),
+                })));
+            }
+            Self::SocketIo(socket) => {
+                let _ = socket.emit("evicted", &serde_json::json!({"reason": reason}));
+                let _ = socket.clone().disconnect();
+            }
+        }
+    }
+
+    // Arrête la livraison de `topic` à la connexion : arrêt de sa tâche de relais (WebSocket brut)
+    // ou sortie de la salle du sujet (Socket.IO, `__all__` pour `*`).
+    pub async fn leave(&self, topic: &str) {
+        match self {
+            Self::WebSocket(_, topic_tasks) => {
+                if let Some(task) = topic_tasks.write().await.remove(topic) {
+                    task.abort();
+                }
+            }
+            Self::SocketIo(socket) => {
+                let room = if topic == "*" { "__all__" } else { topic };
+                socket.leave(room.to_string());
+            }
+        }
+    }
+}
+
+struct ConnectionEntry {
+    handle: ConnectionHandle,
+    // Consommateur revendiqué par la connexion (après `identify`/`subscribe`).
+    consumer: Option<String>,
+    // Réception de la dernière trame (ms depuis l'epoch), pour détecter les connexions à demi ouvertes.
+    last_activity: Arc<AtomicU64>,
+}
+
+type PendingRequest = (String, oneshot::Sender<serde_json::Value>);
+
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    // sid -> connexion.
+    connections: RwLock<HashMap<String, ConnectionEntry>>,
+    // request_id -> (sid destinataire, émetteur de la réponse attendue).
+    // `std::sync::Mutex` : accès courts, jamais à cheval sur un `await`.
+    pending: Mutex<HashMap<String, PendingRequest>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Enregistre une connexion à son ouverture. Retourne son horodatage d'activité,
+    // que la connexion met à jour à chaque trame reçue (voir `record_activity`).
+    pub async fn register(&self, sid: String, handle: ConnectionHandle) -> Arc<AtomicU64> {
+        let last_activity = Arc::new(AtomicU64::new(current_millis()));
+        self.connections.write().await.insert(
+            sid,
+            ConnectionEntry {
+                handle,
+                consumer: None,
+                last_activity: last_activity.clone(),
+            },
+        );
+        last_activity
+    }
+
+    // Retire une connexion à sa fermeture.
+    pub async fn unregister(&self, sid: &str) {
+        self.connections.write().await.remove(sid);
+    }
+
+    // Identifiants des connexions actives.
+    pub async fn sids(&self) -> HashSet<String> {
+        self.connections.read().await.keys().cloned().collect()
+    }
+
+    // Connexions WebSocket brutes silencieuses depuis plus de `idle_ms`. Les connexions Socket.IO
+    // ne sont pas concernées : leur heartbeat Engine.IO détecte déjà les pairs disparus.
+    pub async fn idle_websockets(&self, idle_ms: u64) -> HashSet<String> {
+        let now = current_millis();
+        self.connections
+            .read()
+            .await
+            .iter()
+            .filter(|(_, entry)| {
+                matches!(entry.handle, ConnectionHandle::WebSocket(..))
+                    && now.saturating_sub(entry.last_activity.load(Ordering::Relaxed)) > idle_ms
+            })
+            .map(|(sid, _)| sid.clone())
+            .collect()
+    }
+
+    // Moyen d'atteindre la connexion `sid`, si elle est active.
+    pub async fn handle(&self, sid: &str) -> Option<ConnectionHandle> {
+        self.connections
+            .read()
+            .await
+            .get(sid)
+            .map(|entry| entry.handle.clone())
+    }
+
+    // Ferme toutes les connexions (arrêt du serveur), pour que l'arrêt gracieux n'attende pas
+    // indéfiniment les WebSocket ouverts.
+    pub async fn close_all(&self, reason: &str) {
+        let handles: Vec<ConnectionHandle> = self
+            .connections
+            .read()
+            .await
+            .values()
+            .map(|entry| entry.handle.clone())
+            .collect();
+        for handle in handles {
+            handle.close(reason);
+        }
+    }
+
+    // Nombre de connexions actives (WebSocket brut et Socket.IO).
+    pub async fn count(&self) -> usize {
+        self.connections.read().await.len()
+    }
+
+    // Associe `consumer` à la connexion `sid` en appliquant la politique de doublons.
+    // - `allow` : aucune vérification.
+    // - `replace` : les autres connexions du même consommateur sont fermées.
+    // - `reject` : retourne `false` si une autre connexion porte déjà ce consommateur.
+    pub async fn claim_consumer(&self, sid: &str, consumer: &str, policy: DuplicatePolicy) -> bool {
+        let mut connections = self.connections.write().await;
+
+        if policy != DuplicatePolicy::Allow {
+            let duplicates: Vec<String> = connections
+                .iter()
+                .filter(|(other, entry)| {
+                    other.as_str() != sid && entry.consumer.as_deref() == Some(consumer)
+                })
+                .map(|(other, _)| other.clone())
+                .collect();
+
+            if !duplicates.is_empty() {
+                if policy == DuplicatePolicy::Reject {
+                    return false;
+                }
+                for other in duplicates {
+                    if let Some(entry) = connections.get_mut(&other) {
+                        info!(
+                            "Evicting connection {} of {} (replaced by {})",
+                            other, consumer, sid
+                        );
+                        // L'ancienne connexion ne représente plus ce consommateur.
+                        entry.consumer = None;
+                        entry.handle.close("replaced by a newer connection");
+                    }
+                }
+            }
+        }
+
+        if let Some(entry) = connections.get_mut(sid) {
+            entry.consumer = Some(consumer.to_string());
+        }
+        true
+    }
+
+    // Envoie `payload` au client WebSocket `sid` dans une trame `request` et attend sa trame `response`.
+    pub async fn request(
+        &self,
+        sid: &str,
+        payload: serde_json::Value,
+        timeout: std::time::Duration,
+    ) -> Result<serde_json::Value, RequestError> {
+        let sender = match self.connections.read().await.get(sid) {
+            Some(ConnectionEntry {
+                handle: ConnectionHandle::WebSocket(sender, _),
+                ..
+            }) => sender.clone(),
+            _ => return Err(RequestError::NotFound),
+        };
+
+        let request_id = Uuid::new_v4().to_string();
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(request_id.clone(), (sid.to_string(), reply_tx));
+
+        let frame = serde_json::json!({
+            "event": "request",
+            "request_id": request_id,
+            "payload": payload,
+        });
+        if sender
+            .send(Message::Text(frame.to_string().into()))
+            .is_err()
+        {
+            self.pending.lock().unwrap().remove(&request_id);
+            return Err(RequestError::Disconnected);
+        }
+
+        let result = tokio::time::timeout(timeout, reply_rx).await;
+        // Nettoie l'entrée en attente dans tous les cas (timeout compris).
+        self.pending.lock().unwrap().remove(&request_id);
+        match result {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(RequestError::Disconnected),
+            Err(_) => Err(RequestError::Timeout),
+        }
+    }
+
+    // Transmet la réponse du client `sid` à la requête correspondante.
+    // Seule la connexion destinataire peut répondre à une requête.
+    // Retourne `false` si aucune requête de ce client n'attend ce `request_id` (inconnu ou déjà expiré).
+    pub fn complete(&self, sid: &str, request_id: &str, response: serde_json::Value) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending
+            .get(request_id)
+            .is_none_or(|(target, _)| target != sid)
+        {
+            return false;
+        }
+        match pending.remove(request_id) {
+            Some((_, reply_tx)) => reply_tx.send(response).is_ok(),
+            None => false,
+        }
+    }
+}
+
+// Note la réception d'une trame sur la connexion.
+pub fn record_activity(last_activity: &AtomicU64) {
+    last_activity.store(current_millis(), Ordering::Relaxed);
+}
+
+// Durée (ms) depuis la dernière trame reçue.
+pub fn idle_millis(last_activity: &AtomicU64) -> u64 {
+    current_millis().saturating_sub(last_activity.load(Ordering::Relaxed))
+}
+
+fn current_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
@@ -0,0 +1,57 @@
+// Planificateur des livraisons différées (`deliver_at` de `/publish`).
+//
+// Les messages en attente vivent dans la table `scheduled_messages` : ils survivent donc à un
+// redémarrage, et ceux dont l'échéance est passée pendant l'arrêt sont livrés dès la reprise.
+// À l'échéance, le message suit le chemin normal de publication (`deliver_message`).
+use crate::app_state::AppState;
+use crate::handlers::deliver_message;
+use socketioxide::SocketIo;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+// Attente maximale entre deux vérifications, même sans échéance connue.
+const MAX_IDLE: Duration = Duration::from_secs(60);
+// Attente avant une nouvelle tentative après une erreur de lecture.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+pub fn spawn_scheduler(state: AppState, io: SocketIo) {
+    tokio::spawn(async move {
+        loop {
+            match state.broker.take_due_scheduled().await {
+                Ok(due) => {
+                    for payload in due {
+                        let message_id = payload.message_id.clone();
+                        match deliver_message(&state, &io, payload).await {
+                            Ok(_) => info!("Scheduled message {} delivered", message_id),
+                            Err(status) => warn!(
+                                "Scheduled message {} dropped at delivery: {}",
+                                message_id, status
+                            ),
+                        }
+                    }
+                }
+                Err(e) => error!("Erreur lors de la lecture des messages différés: {}", e),
+            }
+
+            // Dort jusqu'à la prochaine échéance, ou jusqu'à l'enregistrement d'un nouveau message.
+            let wait = match state.broker.next_scheduled_at().await {
+                Ok(Some(next)) => {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs_f64();
+                    Duration::from_secs_f64((next - now).clamp(0.0, MAX_IDLE.as_secs_f64()))
+                }
+                Ok(None) => MAX_IDLE,
+                Err(e) => {
+                    error!("Erreur lors de la lecture des messages différés: {}", e);
+                    RETRY_DELAY
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = state.broker.scheduled_changed() => {}
+            }
+        }
+    });
+}
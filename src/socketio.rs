@@ -1,92 +1,358 @@
 // Importations de l'état de l'application, des modèles de message, et des composants Socket.IO.
 use crate::app_state::AppState;
-use crate::models::{ConsumedMessage, SubscribeMessage};
+use crate::identity::ConnectionIdentity;
+use crate::models::{
+    ConsumedBatchMessage, ConsumedMessage, IdentifyMessage, SubscribeMessage, UnsubscribeMessage,
+};
+use crate::qos::QOS_AT_MOST_ONCE;
+use crate::registry::ConnectionHandle;
+use crate::websocket::max_lifetime;
 use socketioxide::extract::{Data, SocketRef};
-use tracing::info;
+use socketioxide::SocketIo;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
 
-// Configure tous les gestionnaires d'événements pour le namespace par défaut ("/") de Socket.IO.
-pub fn setup_socketio_handlers(io: socketioxide::SocketIo, state: AppState) {
-    // `io.ns` définit la logique pour un namespace spécifique. Ici, le namespace racine.
-    io.ns("/", move |socket: SocketRef| {
-        // Ce code est exécuté chaque fois qu'un nouveau client se connecte.
+// Événements acceptés sur le namespace racine, annoncés dans l'événement `welcome`.
+const SUPPORTED_EVENTS: &[&str] = &[
+    "identify",
+    "subscribe",
+    "unsubscribe",
+    "consumed",
+    "consumed_batch",
+];
+
+// Revendique `consumer` pour ce socket selon `DUPLICATE_CONNECTION_POLICY`.
+// En cas de refus, émet une erreur, déconnecte le client et retourne `false`.
+async fn claim_consumer(state: &AppState, socket: &SocketRef, consumer: &str) -> bool {
+    if state
+        .connections
+        .claim_consumer(
+            &socket.id.to_string(),
+            consumer,
+            state.config.duplicate_connection_policy,
+        )
+        .await
+    {
+        return true;
+    }
+    warn!(
+        "Socket.IO client {} rejected: consumer {} already connected",
+        socket.id, consumer
+    );
+    let _ = socket.emit(
+        "error",
+        &serde_json::json!({"reason": "consumer already connected"}),
+    );
+    let _ = socket.clone().disconnect();
+    false
+}
+
+// Configure tous les gestionnaires d'événements pour le namespace par défaut ("/") de Socket.IO,
+// ainsi que pour chaque namespace de `SOCKETIO_NAMESPACES` (même protocole, salles distinctes).
+pub fn setup_socketio_handlers(io: SocketIo, state: AppState) {
+    let namespaces: Vec<String> = std::iter::once("/".to_string())
+        .chain(state.config.socketio_namespaces.iter().cloned())
+        .collect();
+    for namespace in namespaces {
         let state = state.clone();
-        info!("Socket.IO client connected: {}", socket.id);
-
-        // --- Gestionnaire pour l'événement "subscribe" ---
-        let state_clone = state.clone();
-        socket.on(
-            "subscribe",
-            // `Data<T>` est un extracteur qui désérialise le payload de l'événement en type `T`.
-            move |socket: SocketRef, Data::<SubscribeMessage>(data)| {
-                let state = state_clone.clone();
-                let sid = socket.id.to_string();
-
-                // Le bloc `async move` permet d'utiliser `await` à l'intérieur du handler.
-                async move {
-                    info!(
-                        "Subscribing {} (SID: {}) to topics: {:?}",
-                        data.consumer, sid, data.topics
-                    );
-
-                    // Boucle sur chaque sujet demandé dans le message d'abonnement.
-                    for topic in &data.topics {
-                        // Enregistre l'abonnement dans le Broker (qui le sauvegardera en DB et en cache).
+        // `io.ns` définit la logique pour un namespace spécifique.
+        io.ns(namespace, move |socket: SocketRef| {
+            // Ce code est exécuté chaque fois qu'un nouveau client se connecte.
+            let state = state.clone();
+            info!("Socket.IO client connected: {}", socket.id);
+
+            // Identité `consumer` propre à cette connexion, partagée par tous ses handlers.
+            let identity = Arc::new(ConnectionIdentity::new(state.config.consumer_identity));
+
+            // Annonce les capacités et limites du serveur dès la connexion.
+            let _ = socket.emit("welcome", &state.welcome_payload(SUPPORTED_EVENTS));
+
+            // Rend le socket joignable via le registre des connexions (politique de doublons).
+            let registry = state.connections.clone();
+            let handle = ConnectionHandle::SocketIo(socket.clone());
+            let sid = socket.id.to_string();
+            tokio::spawn(async move { registry.register(sid, handle).await });
+            state
+                .metrics
+                .connections_opened
+                .fetch_add(1, Ordering::Relaxed);
+
+            // Durée de vie maximale (`MAX_CONNECTION_LIFETIME_SECS`) : le client est invité à se reconnecter.
+            if state.config.max_connection_lifetime_secs > 0 {
+                let state_clone = state.clone();
+                let socket_clone = socket.clone();
+                tokio::spawn(async move {
+                    max_lifetime(&state_clone).await;
+                    if socket_clone.connected() {
+                        info!(
+                            "Socket.IO client {} reached its maximum lifetime, closing",
+                            socket_clone.id
+                        );
+                        let _ = socket_clone.emit(
+                            "reconnect_suggested",
+                            &serde_json::json!({"reason": "maximum connection lifetime reached"}),
+                        );
+                        let _ = socket_clone.disconnect();
+                    }
+                });
+            }
+
+            // --- Gestionnaire pour l'événement "identify" ---
+            let state_clone = state.clone();
+            let identity_clone = identity.clone();
+            socket.on(
+                "identify",
+                move |socket: SocketRef, Data::<IdentifyMessage>(data)| {
+                    let state = state_clone.clone();
+                    let identity = identity_clone.clone();
+                    async move {
+                        match identity.identify(&data.consumer) {
+                            Ok(consumer) => {
+                                if !claim_consumer(&state, &socket, &consumer).await {
+                                    return;
+                                }
+                                info!("Socket.IO client {} identified as {}", socket.id, consumer);
+                                let _ = socket.emit(
+                                    "identified",
+                                    &serde_json::json!({"consumer": consumer}),
+                                );
+                            }
+                            Err(reason) => {
+                                let _ = socket.emit("error", &serde_json::json!({"reason": reason}));
+                            }
+                        }
+                    }
+                },
+            );
+
+            // --- Gestionnaire pour l'événement "subscribe" ---
+            let state_clone = state.clone();
+            let identity_clone = identity.clone();
+            socket.on(
+                "subscribe",
+                // `Data<T>` est un extracteur qui désérialise le payload de l'événement en type `T`.
+                move |socket: SocketRef, Data::<SubscribeMessage>(mut data)| {
+                    let state = state_clone.clone();
+                    let identity = identity_clone.clone();
+                    let sid = socket.id.to_string();
+
+                    // Le bloc `async move` permet d'utiliser `await` à l'intérieur du handler.
+                    async move {
+                        // Applique l'identité de la connexion (ou celle déclarée, selon le mode).
+                        let Some(consumer) = identity.resolve(&data.consumer) else {
+                            let _ = socket.emit(
+                                "error",
+                                &serde_json::json!({"reason": "consumer identity required, send identify first"}),
+                            );
+                            return;
+                        };
+                        if let Err(reason) = data
+                            .validate_topics()
+                            .and_then(|_| data.validate_tags())
+                            .and_then(|_| data.validate_group())
+                        {
+                            let _ = socket.emit("error", &serde_json::json!({"reason": reason}));
+                            return;
+                        }
+                        // Le suivi QoS 1 (accusés et renvois) n'existe que sur le WebSocket brut (`/ws`).
+                        if data.qos != QOS_AT_MOST_ONCE {
+                            let _ = socket.emit(
+                                "error",
+                                &serde_json::json!({"reason": "only qos 0 is supported over Socket.IO, use /ws for qos 1"}),
+                            );
+                            return;
+                        }
+                        // Sujets réservés (`__all__`, `RESERVED_TOPIC_PREFIXES`) : tout l'abonnement est refusé.
+                        let reserved: Vec<&String> = data
+                            .topics
+                            .iter()
+                            .filter(|topic| state.config.is_reserved_subscription(topic))
+                            .collect();
+                        if !reserved.is_empty() {
+                            let _ = socket.emit(
+                                "error",
+                                &serde_json::json!({"reason": "reserved topics", "topics": reserved}),
+                            );
+                            return;
+                        }
+                        // Sujets jamais publiés (`REJECT_UNKNOWN_TOPIC_SUBSCRIBE`) : tout l'abonnement est refusé.
+                        let unknown = state.broker.unknown_topics(&data.topics);
+                        if !unknown.is_empty() {
+                            warn!("Subscription of {} rejected, unknown topics: {:?}", sid, unknown);
+                            let _ = socket.emit(
+                                "error",
+                                &serde_json::json!({"reason": "unknown topics", "topics": unknown}),
+                            );
+                            return;
+                        }
+                        if !claim_consumer(&state, &socket, &consumer).await {
+                            return;
+                        }
+                        data.consumer = consumer;
+
+                        info!(
+                            "Subscribing {} (SID: {}) to topics: {:?}",
+                            data.consumer, sid, data.topics
+                        );
+
+                        // Boucle sur chaque sujet demandé dans le message d'abonnement.
+                        for topic in &data.topics {
+                            // Enregistre l'abonnement dans le Broker (qui le sauvegardera en DB et en cache).
+                            state
+                                .broker
+                                .register_subscription(
+                                    sid.clone(),
+                                    data.consumer.clone(),
+                                    topic.clone(),
+                                    &data.tags,
+                                )
+                                .await;
+
+                            // Membre d'un groupe : servi à tour de rôle par la publication, hors de la salle du sujet.
+                            if let Some(group) = &data.group {
+                                state.consumer_groups.join(topic, group, &sid);
+                                socket.leave(topic.clone());
+                                continue;
+                            }
+                            state.consumer_groups.leave(&sid, topic);
+
+                            // Utilise le système de "salles" (rooms) de Socket.IO pour gérer la diffusion.
+                            if topic == "*" {
+                                // Abonnement "wildcard" : le client reçoit tous les messages.
+                                // On le fait quitter toutes les autres salles et rejoindre une salle spéciale "__all__".
+                                socket.leave_all();
+                                socket.join("__all__");
+                                info!(
+                                    "{} subscribed to ALL topics via wildcard '*'",
+                                    data.consumer
+                                );
+                            } else {
+                                // Abonnement à un sujet spécifique : le client rejoint la salle correspondant au nom du sujet.
+                                socket.join(topic.clone());
+                                // Remet la dernière valeur retenue du sujet, s'il en a une.
+                                if let Some(retained) = state.broker.retained_message(topic).await {
+                                    let _ = socket.emit("message", &retained);
+                                }
+                            }
+                        }
+
+                        // Publie l'état complet des abonnements de la connexion.
+                        state.broker.confirm_subscription(&sid).await;
+
+                        // Envoie une confirmation d'abonnement au client.
+                        let _ = socket.emit("subscribed", &serde_json::json!({"status": "ok"}));
+                    }
+                },
+            );
+
+            // --- Gestionnaire pour l'événement "unsubscribe" ---
+            // Retire un seul sujet, les autres abonnements de la connexion restent actifs.
+            let state_clone = state.clone();
+            socket.on(
+                "unsubscribe",
+                move |socket: SocketRef, Data::<UnsubscribeMessage>(data)| {
+                    let state = state_clone.clone();
+                    async move {
+                        let sid = socket.id.to_string();
+                        if state.unsubscribe(&sid, &data.topic).await {
+                            let _ = socket.emit(
+                                "unsubscribed",
+                                &serde_json::json!({"topic": data.topic}),
+                            );
+                        } else {
+                            let _ = socket.emit(
+                                "error",
+                                &serde_json::json!({"reason": "not subscribed to this topic", "topic": data.topic}),
+                            );
+                        }
+                    }
+                },
+            );
+
+            // --- Gestionnaire pour l'événement "consumed" ---
+            let state_clone2 = state.clone();
+            let identity_clone = identity.clone();
+            socket.on(
+                "consumed",
+                move |socket: SocketRef, Data::<ConsumedMessage>(data)| {
+                    let state = state_clone2.clone();
+                    let identity = identity_clone.clone();
+                    async move {
+                        let Some(consumer) = identity.resolve(&data.consumer) else {
+                            let _ = socket.emit(
+                                "error",
+                                &serde_json::json!({"reason": "consumer identity required, send identify first"}),
+                            );
+                            return;
+                        };
+                        // Quand un client confirme avoir consommé un message, on sauvegarde cette information.
                         state
                             .broker
-                            .register_subscription(
-                                sid.clone(),
-                                data.consumer.clone(),
-                                topic.clone(),
-                            )
+                            .save_consumption(consumer, data.topic, data.message_id, data.message)
                             .await;
+                    }
+                },
+            );
 
-                        // Utilise le système de "salles" (rooms) de Socket.IO pour gérer la diffusion.
-                        if topic == "*" {
-                            // Abonnement "wildcard" : le client reçoit tous les messages.
-                            // On le fait quitter toutes les autres salles et rejoindre une salle spéciale "__all__".
-                            socket.leave_all();
-                            socket.join("__all__");
-                            info!(
-                                "{} subscribed to ALL topics via wildcard '*'",
-                                data.consumer
+            // --- Gestionnaire pour l'événement "consumed_batch" ---
+            // Plusieurs confirmations en un seul événement, validées ensemble par le worker DB.
+            let state_clone_batch = state.clone();
+            let identity_clone = identity.clone();
+            socket.on(
+                "consumed_batch",
+                move |socket: SocketRef, Data::<ConsumedBatchMessage>(data)| {
+                    let state = state_clone_batch.clone();
+                    let identity = identity_clone.clone();
+                    async move {
+                        let Some(consumer) = identity.resolve(&data.consumer) else {
+                            let _ = socket.emit(
+                                "error",
+                                &serde_json::json!({"reason": "consumer identity required, send identify first"}),
                             );
-                        } else {
-                            // Abonnement à un sujet spécifique : le client rejoint la salle correspondant au nom du sujet.
-                            socket.join(topic.clone());
-                        }
+                            return;
+                        };
+                        state.broker.save_consumptions_batch(&consumer, data.acks);
                     }
+                },
+            );
 
-                    // Envoie une confirmation d'abonnement au client.
-                    let _ = socket.emit("subscribed", &serde_json::json!({"status": "ok"}));
-                }
-            },
-        );
-
-        // --- Gestionnaire pour l'événement "consumed" ---
-        let state_clone2 = state.clone();
-        socket.on(
-            "consumed",
-            move |_socket: SocketRef, Data::<ConsumedMessage>(data)| {
-                let state = state_clone2.clone();
+            // --- Gestionnaire pour la déconnexion ---
+            let state_clone3 = state.clone();
+            socket.on_disconnect(move |socket: SocketRef| {
+                let state = state_clone3.clone();
                 async move {
-                    // Quand un client confirme avoir consommé un message, on sauvegarde cette information.
-                    state
-                        .broker
-                        .save_consumption(data.consumer, data.topic, data.message_id, data.message)
-                        .await;
+                    info!("Socket.IO client disconnected: {}", socket.id);
+                    // Notifie le Broker que le client est parti pour nettoyer les abonnements.
+                    state.broker.unregister_client(&socket.id.to_string()).await;
+                    state.consumer_groups.leave_all(&socket.id.to_string());
+                    state.connections.unregister(&socket.id.to_string()).await;
                 }
-            },
-        );
-
-        // --- Gestionnaire pour la déconnexion ---
-        let state_clone3 = state.clone();
-        socket.on_disconnect(move |socket: SocketRef| {
-            let state = state_clone3.clone();
-            async move {
-                info!("Socket.IO client disconnected: {}", socket.id);
-                // Notifie le Broker que le client est parti pour nettoyer les abonnements.
-                state.broker.unregister_client(&socket.id.to_string()).await;
-            }
+            });
         });
+    }
+}
+
+// Pousse périodiquement un événement `stats_snapshot` (statistiques agrégées) aux clients Socket.IO,
+// tant que le dashboard est activé : le panneau de statistiques reste à jour sans interrogation.
+pub fn spawn_stats_snapshots(io: SocketIo, state: AppState, every: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        let mut previous = state.broker.topic_sequences();
+        let mut last_tick = Instant::now();
+        loop {
+            ticker.tick().await;
+            let seqs = state.broker.topic_sequences();
+            let elapsed_secs = last_tick.elapsed().as_secs_f64();
+            last_tick = Instant::now();
+            if state.dashboard_enabled.load(Ordering::Relaxed) {
+                let snapshot = state.stats_snapshot(&seqs, &previous, elapsed_secs).await;
+                if let Some(ns) = io.of("/") {
+                    let _ = ns.emit("stats_snapshot", &snapshot).await;
+                }
+            }
+            previous = seqs;
+        }
     });
 }
@@ -0,0 +1,124 @@
+// Motifs hiérarchiques de sujets, à la manière de MQTT, segments séparés par `.` :
+//
+// - `*` remplace exactement un segment (`orders.*` : `orders.eu`, pas `orders` ni `orders.eu.paris`) ;
+// - `#`, seulement en dernier segment, remplace zéro ou plusieurs segments
+//   (`orders.#` : `orders`, `orders.eu`, `orders.eu.paris`).
+//
+// Un segment vide est un segment comme un autre : `orders.` a deux segments (`orders` et ``),
+// il est donc couvert par `orders.*`. Le sujet `*` seul garde son sens historique (tous les sujets,
+// salle `__all__`) et n'est pas traité comme un motif.
+
+const SEPARATOR: char = '.';
+
+// Le sujet est un motif hiérarchique (au moins un segment `*` ou `#`), hors wildcard global `*`.
+pub fn is_topic_pattern(topic: &str) -> bool {
+    topic != "*"
+        && topic
+            .split(SEPARATOR)
+            .any(|segment| segment == "*" || segment == "#")
+}
+
+// Refuse un motif dont le `#` n'est pas le dernier segment.
+pub fn validate_pattern(pattern: &str) -> Result<(), &'static str> {
+    let segments: Vec<&str> = pattern.split(SEPARATOR).collect();
+    let last = segments.len() - 1;
+    if segments
+        .iter()
+        .enumerate()
+        .any(|(index, segment)| *segment == "#" && index != last)
+    {
+        return Err("'#' is only allowed as the last topic segment");
+    }
+    Ok(())
+}
+
+// Le sujet concret `topic` correspond au motif `pattern`.
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let mut topic_segments = topic.split(SEPARATOR);
+    for segment in pattern.split(SEPARATOR) {
+        match segment {
+            "#" => return true,
+            "*" => {
+                if topic_segments.next().is_none() {
+                    return false;
+                }
+            }
+            literal => {
+                if topic_segments.next() != Some(literal) {
+                    return false;
+                }
+            }
+        }
+    }
+    topic_segments.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_topics_match_only_themselves() {
+        assert!(topic_matches("orders", "orders"));
+        assert!(topic_matches("orders.eu", "orders.eu"));
+        assert!(!topic_matches("orders", "orders.eu"));
+        assert!(!topic_matches("orders.eu", "orders"));
+        assert!(!topic_matches("orders.eu", "orders.us"));
+    }
+
+    #[test]
+    fn star_matches_exactly_one_segment() {
+        assert!(topic_matches("orders.*", "orders.eu"));
+        assert!(!topic_matches("orders.*", "orders"));
+        assert!(!topic_matches("orders.*", "orders.eu.paris"));
+        assert!(topic_matches("*.eu", "orders.eu"));
+        assert!(topic_matches("orders.*.paris", "orders.eu.paris"));
+        assert!(!topic_matches("orders.*.paris", "orders.eu.lyon"));
+    }
+
+    #[test]
+    fn hash_matches_zero_or_more_segments() {
+        assert!(topic_matches("orders.#", "orders"));
+        assert!(topic_matches("orders.#", "orders.eu"));
+        assert!(topic_matches("orders.#", "orders.eu.paris"));
+        assert!(!topic_matches("orders.#", "invoices.eu"));
+        assert!(topic_matches("#", "orders.eu"));
+        assert!(topic_matches("orders.*.#", "orders.eu"));
+        assert!(!topic_matches("orders.*.#", "orders"));
+    }
+
+    #[test]
+    fn empty_segments_are_segments() {
+        // `orders.` : segments `orders` et ``.
+        assert!(topic_matches("orders.*", "orders."));
+        assert!(topic_matches("orders.#", "orders."));
+        assert!(!topic_matches("orders", "orders."));
+        assert!(topic_matches("orders.", "orders."));
+        // `orders..eu` : le segment vide du milieu est couvert par `*`.
+        assert!(topic_matches("orders.*.eu", "orders..eu"));
+        assert!(topic_matches("*.orders", ".orders"));
+    }
+
+    #[test]
+    fn detects_patterns() {
+        assert!(is_topic_pattern("orders.*"));
+        assert!(is_topic_pattern("orders.#"));
+        assert!(is_topic_pattern("*.eu"));
+        assert!(!is_topic_pattern("orders"));
+        assert!(!is_topic_pattern("orders.eu"));
+        // Wildcard global historique, pas un motif.
+        assert!(!is_topic_pattern("*"));
+        // `*` et `#` ne sont des jokers que comme segments entiers.
+        assert!(!is_topic_pattern("orders*"));
+        assert!(!is_topic_pattern("orders.#eu"));
+    }
+
+    #[test]
+    fn hash_only_allowed_last() {
+        assert!(validate_pattern("orders.#").is_ok());
+        assert!(validate_pattern("#").is_ok());
+        assert!(validate_pattern("orders.*.eu").is_ok());
+        assert!(validate_pattern("orders.#.eu").is_err());
+        assert!(validate_pattern("#.eu").is_err());
+    }
+}
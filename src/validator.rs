@@ -0,0 +1,115 @@
+// Validation externe des publications (`VALIDATION_WEBHOOK_URL`).
+//
+// - Chaque publication est postée au validateur (`topic`, `message_id`, `message`, `producer`)
+//   avant d'être enregistrée.
+// - Réponse 2xx : la publication est acceptée ; si le corps JSON contient un champ `message`,
+//   il remplace le message publié (transformation).
+// - Autre statut : la publication est refusée avec la raison donnée par le validateur
+//   (champ `reason` du corps JSON, ou le corps brut).
+// - Validateur injoignable ou trop lent (`VALIDATION_WEBHOOK_TIMEOUT_MS`) : la publication est refusée,
+//   sauf avec `VALIDATION_WEBHOOK_FAIL_OPEN`, où elle passe sans validation.
+use crate::config::Config;
+use crate::models::PublishRequest;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+// Longueur maximale de la raison de refus reprise du corps de la réponse.
+const MAX_REASON_LEN: usize = 500;
+
+// Issue de la validation d'une publication.
+pub enum Verdict {
+    // Publication acceptée, avec le message de remplacement éventuel.
+    Accepted(Option<serde_json::Value>),
+    // Publication refusée par le validateur, avec sa raison.
+    Rejected(String),
+    // Validateur indisponible et politique `fail-closed`.
+    Unavailable,
+}
+
+pub struct ValidationWebhook {
+    client: reqwest::Client,
+    url: String,
+    fail_open: bool,
+}
+
+impl ValidationWebhook {
+    // Retourne `None` si aucun validateur n'est configuré.
+    pub fn from_config(config: &Config) -> Option<Arc<Self>> {
+        let url = config.validation_webhook_url.clone()?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.validation_webhook_timeout_ms))
+            .build()
+            .expect("client HTTP du validateur");
+        info!(
+            "Publish validation webhook enabled: {} (fail-{})",
+            url,
+            if config.validation_webhook_fail_open {
+                "open"
+            } else {
+                "closed"
+            }
+        );
+        Some(Arc::new(Self {
+            client,
+            url,
+            fail_open: config.validation_webhook_fail_open,
+        }))
+    }
+
+    pub async fn validate(&self, payload: &PublishRequest) -> Verdict {
+        let body = serde_json::json!({
+            "topic": payload.topic,
+            "message_id": payload.message_id,
+            "message": payload.message,
+            "producer": payload.producer,
+        });
+        let response = match self.client.post(&self.url).json(&body).send().await {
+            Ok(response) => response,
+            Err(e) => return self.unavailable(e.to_string()),
+        };
+
+        let status = response.status();
+        let text = match response.text().await {
+            Ok(text) => text,
+            Err(e) => return self.unavailable(e.to_string()),
+        };
+        let reply = serde_json::from_str::<serde_json::Value>(&text).ok();
+
+        if status.is_success() {
+            return Verdict::Accepted(
+                reply.and_then(|mut reply| reply.get_mut("message").map(serde_json::Value::take)),
+            );
+        }
+        let mut reason = reply
+            .as_ref()
+            .and_then(|reply| reply.get("reason"))
+            .and_then(|reason| reason.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                if text.trim().is_empty() {
+                    format!("rejected by validator ({})", status)
+                } else {
+                    text.trim().to_string()
+                }
+            });
+        if reason.len() > MAX_REASON_LEN {
+            let mut end = MAX_REASON_LEN;
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            reason.truncate(end);
+        }
+        Verdict::Rejected(reason)
+    }
+
+    // Validateur injoignable : accepte sans transformation (`fail-open`) ou refuse (`fail-closed`).
+    fn unavailable(&self, error: String) -> Verdict {
+        warn!("Validation webhook {} unavailable: {}", self.url, error);
+        if self.fail_open {
+            Verdict::Accepted(None)
+        } else {
+            Verdict::Unavailable
+        }
+    }
+}
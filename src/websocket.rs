@@ -1,38 +1,185 @@
 // Importations nécessaires pour l'état, les modèles, Axum, les WebSockets, et la synchronisation.
-use crate::app_state::AppState;
-use crate::models::{ConsumedMessage, SubscribeMessage};
+use crate::app_state::{AppState, DeliveryCounters};
+use crate::identity::ConnectionIdentity;
+use crate::models::{
+    BinaryPublishQuery, ConsumedBatchMessage, ConsumedMessage, IdentifyMessage, ReplayMessage,
+    ResponseMessage, SubscribeMessage, UnsubscribeMessage,
+};
+use crate::pacing::{Pacer, SendLimits};
+use crate::qos::{DeliveryLog, InFlightTracker, QOS_AT_LEAST_ONCE};
+use crate::registry::{idle_millis, record_activity, ConnectionHandle, TopicTasks};
+use crate::topic_pattern::is_topic_pattern;
+use crate::ws_deflate::{self, DeflateSocket, InflateStream};
 use axum::{
-    extract::{ws::WebSocketUpgrade, State},
-    response::Response,
+    body::{Body, Bytes},
+    extract::{
+        ws::{close_code, CloseFrame, Message},
+        ConnectInfo, Request, State,
+    },
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
 };
 use futures_util::{SinkExt, StreamExt}; // Traits pour envoyer et recevoir sur des flux (streams).
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock}; // Canal MPSC pour la communication interne et RwLock pour l'accès concurrent.
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, Notify, RwLock}; // Canal MPSC pour la communication interne et RwLock pour l'accès concurrent.
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::{Role, WebSocketConfig};
+use tokio_tungstenite::WebSocketStream;
 use tracing::{info, warn};
 use uuid::Uuid; // Pour générer des identifiants uniques.
 
+// Événements acceptés par ce point de terminaison, annoncés dans la trame `welcome`.
+const SUPPORTED_EVENTS: &[&str] = &[
+    "identify",
+    "subscribe",
+    "unsubscribe",
+    "consumed",
+    "consumed_batch",
+    "response",
+    "flush",
+    "replay",
+];
+
+// Nombre maximum de messages par trame `batch` : au-delà, le lot est livré sans attendre la fin de la fenêtre.
+const MAX_BATCH_SIZE: usize = 100;
+
 // Handler Axum pour le point de terminaison `/ws`.
+// La mise à niveau est faite ici plutôt qu'avec `WebSocketUpgrade`, qui ne permet ni de répondre à
+// l'offre `permessage-deflate` du client ni d'intercaler la décompression (voir `ws_deflate`).
 pub async fn ws_handler(
-    // `WebSocketUpgrade` est un extracteur qui permet de transformer une requête HTTP en connexion WebSocket.
-    ws: WebSocketUpgrade,
+    // Adresse du client, pour le journal de connexion.
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     State((state, _)): State<(crate::app_state::AppState, socketioxide::SocketIo)>,
+    mut request: Request,
 ) -> Response {
-    // `on_upgrade` finalise la mise à niveau et fournit un `socket` WebSocket, qui est ensuite passé à notre logique de gestion.
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let key = match upgrade_key(request.method(), request.headers()) {
+        Ok(key) => key,
+        Err(status) => return status.into_response(),
+    };
+    let Some(on_upgrade) = request
+        .extensions_mut()
+        .remove::<hyper::upgrade::OnUpgrade>()
+    else {
+        return StatusCode::UPGRADE_REQUIRED.into_response();
+    };
+    // Compression négociée seulement si le client la propose, et jamais avec `WS_COMPRESSION=false`.
+    let compress = state.config.ws_compression
+        && ws_deflate::offer_accepted(
+            request
+                .headers()
+                .get_all(header::SEC_WEBSOCKET_EXTENSIONS)
+                .iter()
+                .filter_map(|value| value.to_str().ok()),
+        );
+
+    // La connexion n'est disponible qu'une fois la réponse `101` envoyée au client.
+    tokio::spawn(async move {
+        let upgraded = match on_upgrade.await {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                warn!("WebSocket upgrade failed (peer: {}): {}", peer, e);
+                return;
+            }
+        };
+        let max_message_bytes = state.config.max_message_bytes;
+        let stream = InflateStream::new(
+            hyper_util::rt::TokioIo::new(upgraded),
+            compress,
+            max_message_bytes,
+        );
+        let config = WebSocketConfig::default().max_message_size(Some(max_message_bytes));
+        let socket = WebSocketStream::from_raw_socket(stream, Role::Server, Some(config)).await;
+        handle_socket(socket, compress, state, peer).await;
+    });
+
+    let mut response = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(
+            header::SEC_WEBSOCKET_ACCEPT,
+            derive_accept_key(key.as_bytes()),
+        );
+    if compress {
+        response = response.header(
+            header::SEC_WEBSOCKET_EXTENSIONS,
+            ws_deflate::ACCEPTED_EXTENSION,
+        );
+    }
+    response.body(Body::empty()).unwrap()
+}
+
+// Vérifie qu'une requête est une demande de mise à niveau WebSocket valide et renvoie sa clé
+// `Sec-WebSocket-Key` (mêmes refus que `WebSocketUpgrade`).
+fn upgrade_key(method: &Method, headers: &HeaderMap) -> Result<String, StatusCode> {
+    let header_has = |name: header::HeaderName, token: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    };
+    if method != Method::GET {
+        return Err(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    if !header_has(header::CONNECTION, "upgrade")
+        || !header_has(header::UPGRADE, "websocket")
+        || !header_has(header::SEC_WEBSOCKET_VERSION, "13")
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    headers
+        .get(header::SEC_WEBSOCKET_KEY)
+        .and_then(|key| key.to_str().ok())
+        .map(str::to_owned)
+        .ok_or(StatusCode::BAD_REQUEST)
 }
 
 // Gère le cycle de vie complet d'une connexion WebSocket individuelle.
-async fn handle_socket(socket: axum::extract::ws::WebSocket, state: AppState) {
+// `compress` : extension `permessage-deflate` négociée à la mise à niveau.
+async fn handle_socket(socket: DeflateSocket, compress: bool, state: AppState, peer: SocketAddr) {
     // Génère un ID de session unique pour ce client WebSocket.
     let sid = Uuid::new_v4().to_string();
+    // La requête d'upgrade figure au journal d'accès ; la connexion est journalisée avec son SID,
+    // puis à la déconnexion avec sa durée.
+    let opened_at = Instant::now();
+    info!("WebSocket connection opened (SID: {}, peer: {})", sid, peer);
     // Sépare le socket en un `sender` (pour écrire) et un `receiver` (pour lire).
     let (mut ws_sender, mut ws_receiver) = socket.split();
+    // Identité `consumer` de cette connexion (voir `CONSUMER_IDENTITY`).
+    let identity = ConnectionIdentity::new(state.config.consumer_identity);
 
     // Crée un canal MPSC (multi-producer, single-consumer) interne.
     // C'est un pattern clé ici : plusieurs tâches (abonnements aux topics, broadcast global)
     // peuvent envoyer des messages dans ce canal (`internal_tx`), et une seule tâche (`send_task`)
     // les consomme pour les écrire sur le WebSocket. Cela évite les accès concurrents au `ws_sender`.
-    let (internal_tx, mut internal_rx) = mpsc::unbounded_channel::<String>();
+    // Le canal transporte des `Message` complets pour pouvoir aussi y faire passer des trames de contrôle (ex: `Pong`).
+    let (internal_tx, mut internal_rx) = mpsc::unbounded_channel::<Message>();
+    // Première trame envoyée au client : capacités et limites du serveur.
+    let mut welcome = state.welcome_payload(SUPPORTED_EVENTS);
+    welcome["event"] = "welcome".into();
+    let _ = internal_tx.send(Message::Text(welcome.to_string().into()));
+    // Rend la connexion adressable par le serveur (requêtes serveur -> client).
+    // Stocke les handles des tâches d'abonnement aux topics (une par sujet) pour pouvoir les arrêter plus tard.
+    let topic_tasks: TopicTasks = Arc::new(RwLock::new(HashMap::new()));
+    let topic_tasks_clone = topic_tasks.clone();
+
+    let last_activity = state
+        .connections
+        .register(
+            sid.clone(),
+            ConnectionHandle::WebSocket(internal_tx.clone(), topic_tasks.clone()),
+        )
+        .await;
+    state
+        .metrics
+        .connections_opened
+        .fetch_add(1, Ordering::Relaxed);
 
     // --- Tâche de Broadcast Global ---
     // S'abonne au canal d'événements global du Broker.
@@ -42,7 +189,7 @@ async fn handle_socket(socket: axum::extract::ws::WebSocket, state: AppState) {
         // Écoute les événements et les transfère au canal interne du client.
         while let Ok(event) = event_rx.recv().await {
             if let Ok(msg) = serde_json::to_string(event.as_ref()) {
-                if internal_tx_clone.send(msg).is_err() {
+                if internal_tx_clone.send(Message::Text(msg.into())).is_err() {
                     // Si l'envoi échoue, le client est probablement déconnecté, on arrête la tâche.
                     break;
                 }
@@ -50,19 +197,72 @@ async fn handle_socket(socket: axum::extract::ws::WebSocket, state: AppState) {
         }
     });
 
-    // Stocke les handles des tâches d'abonnement aux topics pour pouvoir les arrêter plus tard.
-    let topic_tasks: Arc<RwLock<Vec<tokio::task::JoinHandle<()>>>> =
-        Arc::new(RwLock::new(Vec::new()));
-    let topic_tasks_clone = topic_tasks.clone();
+    // Livraisons QoS 1 en attente d'accusé (`consumed`) et tâche de renvoi, démarrée au premier abonnement QoS 1.
+    let in_flight = Arc::new(InFlightTracker::new(
+        Duration::from_millis(state.config.qos_ack_timeout_ms),
+        state.config.qos_max_redeliveries,
+    ));
+    let mut redelivery_task: Option<tokio::task::JoinHandle<()>> = None;
+
+    // Débit d'envoi maximal de la connexion, redéfinissable par `subscribe`.
+    let send_limits = Arc::new(SendLimits::new(
+        state.config.ws_max_send_rate,
+        state.config.ws_max_send_bytes_per_sec,
+    ));
+    let send_task_limits = send_limits.clone();
+    let send_buffer = state.config.ws_send_buffer;
+    let send_task_sid = sid.clone();
+
+    // `Ping` périodiques du serveur (`WS_PING_INTERVAL_SECS`) : sans `Pong` dans `WS_PONG_TIMEOUT_SECS`,
+    // la connexion est considérée comme morte (pair à demi ouvert).
+    let heartbeat = Arc::new(Heartbeat::default());
+    let send_task_heartbeat = heartbeat.clone();
+    let mut ping_interval = (state.config.ws_ping_interval_secs > 0).then(|| {
+        let period = Duration::from_secs(state.config.ws_ping_interval_secs);
+        tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+    });
 
     // --- Tâche d'Envoi (Sender) ---
     // Tâche dédiée à l'envoi de messages au client WebSocket.
-    let send_task = tokio::spawn(async move {
-        // Lit en continu depuis le canal interne.
-        while let Some(msg) = internal_rx.recv().await {
+    let mut send_task = tokio::spawn(async move {
+        let mut pacer = Pacer::default();
+        let mut dropped = 0u64;
+        // Lit en continu depuis le canal interne, en intercalant les `Ping` du serveur.
+        loop {
+            let msg = tokio::select! {
+                msg = internal_rx.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = next_ping(&mut ping_interval) => {
+                    // Un seul `Ping` en attente de réponse à la fois.
+                    if !send_task_heartbeat.ping_due() {
+                        continue;
+                    }
+                    Message::Ping(Default::default())
+                }
+            };
+            // Seules les trames de données sont cadencées ; les trames de contrôle partent aussitôt.
+            if let Message::Text(text) = &msg {
+                if send_task_limits.enabled() {
+                    // File pleine : la trame la plus ancienne est abandonnée.
+                    if internal_rx.len() >= send_buffer {
+                        dropped += 1;
+                        continue;
+                    }
+                    if dropped > 0 {
+                        warn!(
+                            "Connection {}: {} frames dropped while rate limited",
+                            send_task_sid, dropped
+                        );
+                        dropped = 0;
+                    }
+                    pacer.wait(&send_task_limits, text.len()).await;
+                }
+            }
             // Envoie le message au client via le WebSocket.
             if ws_sender
-                .send(axum::extract::ws::Message::Text(msg.into()))
+                .send(ws_deflate::outgoing(msg, compress))
                 .await
                 .is_err()
             {
@@ -72,121 +272,834 @@ async fn handle_socket(socket: axum::extract::ws::WebSocket, state: AppState) {
         }
     });
 
+    // Durée de vie maximale de la connexion (`MAX_CONNECTION_LIFETIME_SECS`) : à l'échéance,
+    // le client est invité à se reconnecter (et donc à se ré-authentifier).
+    let lifetime = max_lifetime(&state);
+    tokio::pin!(lifetime);
+
+    // Vrai si le client a fermé la connexion proprement (trame `Close`).
+    let mut clean_close = false;
+    let flush_timeout = Duration::from_millis(state.config.ws_flush_timeout_ms);
+
     // --- Boucle de Réception (Receiver) ---
     // Boucle principale qui attend les messages entrants du client.
-    while let Some(msg) = ws_receiver.next().await {
-        let msg = if let Ok(msg) = msg {
-            msg
-        } else {
-            // Erreur de réception, probablement une déconnexion.
-            break;
+    loop {
+        let msg = tokio::select! {
+            msg = ws_receiver.next() => match msg.map(|msg| msg.map(ws_deflate::incoming)) {
+                Some(Ok(Some(msg))) => msg,
+                Some(Ok(None)) => continue,
+                // Fin du flux ou erreur de réception, probablement une déconnexion.
+                _ => break,
+            },
+            _ = heartbeat.pong_overdue(state.config.ws_pong_timeout_secs) => {
+                info!("Connection {} did not answer ping within {}s, closing", sid, state.config.ws_pong_timeout_secs);
+                let _ = internal_tx.send(Message::Close(Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "pong timeout".into(),
+                })));
+                break;
+            }
+            _ = heartbeat_expired(state.config.client_expiry_secs, &last_activity) => {
+                info!("Connection {} silent for {}s, closing", sid, state.config.client_expiry_secs);
+                let _ = internal_tx.send(Message::Close(Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "heartbeat expired".into(),
+                })));
+                break;
+            }
+            _ = &mut lifetime => {
+                info!("Connection {} reached its maximum lifetime, closing", sid);
+                let frame = serde_json::json!({
+                    "event": "reconnect_suggested",
+                    "reason": "maximum connection lifetime reached",
+                });
+                let _ = internal_tx.send(Message::Text(frame.to_string().into()));
+                let _ = internal_tx.send(Message::Close(Some(CloseFrame {
+                    code: close_code::NORMAL,
+                    reason: "maximum connection lifetime reached".into(),
+                })));
+                break;
+            }
+        };
+
+        // Toute trame reçue (y compris `Ping`/`Pong`) prouve que le client est vivant.
+        record_activity(&last_activity);
+
+        let text = match msg {
+            Message::Text(text) => text,
+            // Répond explicitement aux `Ping` avec le même payload, comme l'exige la RFC 6455.
+            Message::Ping(payload) => {
+                let _ = internal_tx.send(Message::Pong(payload));
+                continue;
+            }
+            // Réponse à un `Ping` du serveur (un `Pong` non sollicité, autorisé par le protocole, est sans effet).
+            Message::Pong(_) => {
+                heartbeat.pong_received();
+                continue;
+            }
+            // Le client ferme proprement la connexion : on sort de la boucle pour nettoyer.
+            Message::Close(frame) => {
+                info!("Client sent close frame (SID: {}): {:?}", sid, frame);
+                clean_close = true;
+                break;
+            }
+            // Le protocole de ce endpoint est textuel (JSON) : les trames binaires sont refusées explicitement.
+            Message::Binary(data) => {
+                warn!("Binary frame rejected (SID: {}, {} bytes)", sid, data.len());
+                let _ = internal_tx.send(error_frame(
+                    "binary frames are not supported, send JSON text frames",
+                ));
+                continue;
+            }
         };
 
-        if let axum::extract::ws::Message::Text(text) = msg {
-            // Tente de parser le message texte en JSON.
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&text) {
-                // Recherche un champ "event" pour déterminer le type de message (pattern similaire à Socket.IO).
-                if let Some(event_type) = parsed.get("event").and_then(|v| v.as_str()) {
-                    match event_type {
-                        "subscribe" => {
-                            if let Ok(sub_msg) =
-                                serde_json::from_value::<SubscribeMessage>(parsed.clone())
+        // Ignore les trames vides (ou uniquement composées d'espaces) sans tenter de les parser.
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        // Tente de parser le message texte en JSON.
+        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&text) {
+            // Recherche un champ "event" pour déterminer le type de message (pattern similaire à Socket.IO).
+            if let Some(event_type) = parsed.get("event").and_then(|v| v.as_str()) {
+                match event_type {
+                    "identify" => {
+                        if let Ok(identify_msg) =
+                            serde_json::from_value::<IdentifyMessage>(parsed.clone())
+                        {
+                            match identity.identify(&identify_msg.consumer) {
+                                Ok(consumer) => {
+                                    if !claim_consumer(&state, &sid, &consumer, &internal_tx).await
+                                    {
+                                        break;
+                                    }
+                                    info!("Connection {} identified as {}", sid, consumer);
+                                    let frame = serde_json::json!({
+                                        "event": "identified",
+                                        "consumer": consumer,
+                                    });
+                                    let _ =
+                                        internal_tx.send(Message::Text(frame.to_string().into()));
+                                }
+                                Err(reason) => {
+                                    let _ = internal_tx.send(error_frame(reason));
+                                }
+                            }
+                        }
+                    }
+                    "subscribe" => {
+                        if let Ok(mut sub_msg) =
+                            serde_json::from_value::<SubscribeMessage>(parsed.clone())
+                        {
+                            // Applique l'identité de la connexion (ou celle déclarée, selon le mode).
+                            let Some(consumer) = identity.resolve(&sub_msg.consumer) else {
+                                let _ = internal_tx.send(error_frame(
+                                    "consumer identity required, send identify first",
+                                ));
+                                continue;
+                            };
+                            if !claim_consumer(&state, &sid, &consumer, &internal_tx).await {
+                                break;
+                            }
+                            sub_msg.consumer = consumer;
+
+                            if let Err(reason) = sub_msg
+                                .validate_topics()
+                                .and_then(|_| sub_msg.validate_tags())
+                                .and_then(|_| sub_msg.validate_group())
                             {
-                                info!(
-                                    "Subscribing {} (SID: {}) to topics: {:?}",
-                                    sub_msg.consumer, sid, sub_msg.topics
+                                let _ = internal_tx.send(error_frame(reason));
+                                continue;
+                            }
+                            if sub_msg.qos > QOS_AT_LEAST_ONCE {
+                                let _ = internal_tx
+                                    .send(error_frame("unsupported qos level, use 0 or 1"));
+                                continue;
+                            }
+                            // Sujets réservés (`__all__`, `RESERVED_TOPIC_PREFIXES`) : tout l'abonnement est refusé.
+                            let reserved: Vec<&String> = sub_msg
+                                .topics
+                                .iter()
+                                .filter(|topic| state.config.is_reserved_subscription(topic))
+                                .collect();
+                            if !reserved.is_empty() {
+                                let frame = serde_json::json!({
+                                    "event": "error",
+                                    "reason": "reserved topics",
+                                    "topics": reserved,
+                                });
+                                let _ = internal_tx.send(Message::Text(frame.to_string().into()));
+                                continue;
+                            }
+                            // Sujets jamais publiés (`REJECT_UNKNOWN_TOPIC_SUBSCRIBE`) : tout l'abonnement est refusé.
+                            let unknown = state.broker.unknown_topics(&sub_msg.topics);
+                            if !unknown.is_empty() {
+                                warn!(
+                                    "Subscription of {} rejected, unknown topics: {:?}",
+                                    sid, unknown
                                 );
+                                let frame = serde_json::json!({
+                                    "event": "error",
+                                    "reason": "unknown topics",
+                                    "topics": unknown,
+                                });
+                                let _ = internal_tx.send(Message::Text(frame.to_string().into()));
+                                continue;
+                            }
+                            // QoS 1 : les livraisons sont suivies et renvoyées jusqu'à leur accusé.
+                            let tracker = (sub_msg.qos == QOS_AT_LEAST_ONCE).then(|| {
+                                if redelivery_task.is_none() {
+                                    redelivery_task = Some(spawn_redelivery(
+                                        in_flight.clone(),
+                                        internal_tx.clone(),
+                                    ));
+                                }
+                                in_flight.clone()
+                            });
+
+                            // État de livraison persistant du consommateur (`PERSIST_DELIVERY_ACKS`, QoS 1).
+                            let delivery_log = (tracker.is_some()
+                                && state.config.persist_delivery_acks)
+                                .then(|| {
+                                    DeliveryLog::new(state.broker.clone(), sub_msg.consumer.clone())
+                                });
+                            // Renvoie d'abord ce qui avait été livré sans accusé avant une reconnexion.
+                            if let (Some(tracker), Some(_)) = (&tracker, &delivery_log) {
+                                for frame in state
+                                    .broker
+                                    .pending_deliveries(&sub_msg.consumer, &sub_msg.topics)
+                                    .await
+                                {
+                                    tracker.track(&frame);
+                                    let _ = internal_tx.send(Message::Text(frame.into()));
+                                }
+                            }
+
+                            // Limites d'envoi propres à cet abonné (appliquées à toute la connexion).
+                            send_limits.update(sub_msg.max_rate, sub_msg.max_bytes_per_sec);
+
+                            info!(
+                                "Subscribing {} (SID: {}) to topics: {:?} (qos {})",
+                                sub_msg.consumer, sid, sub_msg.topics, sub_msg.qos
+                            );
+
+                            for topic in &sub_msg.topics {
+                                // Enregistre l'abonnement dans le Broker.
+                                state
+                                    .broker
+                                    .register_subscription(
+                                        sid.clone(),
+                                        sub_msg.consumer.clone(),
+                                        topic.clone(),
+                                        &sub_msg.tags,
+                                    )
+                                    .await;
+
+                                // Membre d'un groupe : servi à tour de rôle par la publication, sans
+                                // tâche de relais du canal du sujet.
+                                if let Some(group) = &sub_msg.group {
+                                    state.consumer_groups.join(topic, group, &sid);
+                                    if let Some(previous) =
+                                        topic_tasks_clone.write().await.remove(topic)
+                                    {
+                                        previous.abort();
+                                    }
+                                    continue;
+                                }
+                                state.consumer_groups.leave(&sid, topic);
+
+                                // Crée ou récupère un canal de diffusion pour ce topic spécifique.
+                                // Au-delà de `TOPIC_SUBSCRIBER_CAP`, le récepteur est servi par un relais.
+                                let mut rx = state.subscribe_topic(topic).await;
 
-                                for topic in &sub_msg.topics {
-                                    // Enregistre l'abonnement dans le Broker.
-                                    state
-                                        .broker
-                                        .register_subscription(
-                                            sid.clone(),
-                                            sub_msg.consumer.clone(),
-                                            topic.clone(),
+                                // Remet d'abord la dernière valeur retenue du sujet, s'il en a une.
+                                if let Some(retained) = state.broker.retained_message(topic).await {
+                                    if let Ok(frame) = serde_json::to_string(&retained) {
+                                        let _ = internal_tx.send(Message::Text(frame.into()));
+                                    }
+                                }
+
+                                // Crée une tâche dédiée pour cet abonnement de topic.
+                                let internal_tx_for_topic = internal_tx.clone();
+                                let topic_name = topic.clone();
+                                let batch_window = sub_msg
+                                    .batch_window_ms
+                                    .filter(|ms| *ms > 0)
+                                    .map(Duration::from_millis);
+                                let tracker = tracker.clone();
+                                let delivery_log = delivery_log.clone();
+                                let counters = state.delivery_counters(topic);
+                                let task = tokio::spawn(async move {
+                                    // Livraison groupée si le client l'a demandée.
+                                    if let Some(window) = batch_window {
+                                        forward_batched(
+                                            rx,
+                                            internal_tx_for_topic,
+                                            topic_name,
+                                            window,
+                                            tracker,
+                                            delivery_log,
+                                            counters,
                                         )
                                         .await;
-
-                                    // Crée ou récupère un canal de diffusion pour ce topic spécifique.
-                                    let mut rx = {
-                                        let mut channels = state.topic_channels.write().await;
-                                        let tx = channels
-                                            .entry(topic.clone())
-                                            .or_insert_with(|| {
-                                                tokio::sync::broadcast::channel(1000).0
-                                            })
-                                            .clone();
-                                        tx.subscribe()
-                                    };
-
-                                    // Crée une tâche dédiée pour cet abonnement de topic.
-                                    let internal_tx_for_topic = internal_tx.clone();
-                                    let topic_name = topic.clone();
-                                    let task = tokio::spawn(async move {
-                                        loop {
-                                            match rx.recv().await {
-                                                Ok(msg) => {
-                                                    // Transfère le message du topic au canal interne du client.
-                                                    if internal_tx_for_topic.send(msg).is_err() {
-                                                        break;
-                                                    }
+                                        return;
+                                    }
+                                    loop {
+                                        match rx.recv().await {
+                                            Ok(msg) => {
+                                                // Déjà acquitté par ce consommateur : pas de nouvelle livraison.
+                                                if !deliverable(delivery_log.as_ref(), &msg).await {
+                                                    continue;
                                                 }
-                                                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
-                                                    // Le client est trop lent et a manqué des messages.
-                                                    warn!(
-                                                        "Topic {} lagged by {} messages",
-                                                        topic_name, n
-                                                    );
+                                                if let Some(tracker) = &tracker {
+                                                    tracker.track(&msg);
                                                 }
-                                                Err(tokio::sync::broadcast::error::RecvError::Closed) => {
-                                                    // Le canal du topic a été fermé.
+                                                // Transfère le message du topic au canal interne du client.
+                                                if internal_tx_for_topic
+                                                    .send(Message::Text(msg.into()))
+                                                    .is_err()
+                                                {
+                                                    break;
+                                                }
+                                                counters.delivered.fetch_add(1, Ordering::Relaxed);
+                                            }
+                                            Err(
+                                                tokio::sync::broadcast::error::RecvError::Lagged(n),
+                                            ) => {
+                                                // Le client est trop lent et a manqué des messages : il en est averti.
+                                                warn!(
+                                                    "Topic {} lagged by {} messages",
+                                                    topic_name, n
+                                                );
+                                                counters.dropped.fetch_add(n, Ordering::Relaxed);
+                                                if internal_tx_for_topic
+                                                    .send(lag_frame(&topic_name, n))
+                                                    .is_err()
+                                                {
                                                     break;
                                                 }
                                             }
+                                            Err(
+                                                tokio::sync::broadcast::error::RecvError::Closed,
+                                            ) => {
+                                                // Le canal du topic a été fermé.
+                                                break;
+                                            }
                                         }
-                                    });
+                                    }
+                                });
 
-                                    // Ajoute la nouvelle tâche à la liste pour le nettoyage futur.
-                                    let mut tasks = topic_tasks_clone.write().await;
-                                    tasks.push(task);
+                                // Ajoute la nouvelle tâche pour le nettoyage futur ; un réabonnement remplace la précédente.
+                                let mut tasks = topic_tasks_clone.write().await;
+                                if let Some(previous) = tasks.insert(topic.clone(), task) {
+                                    previous.abort();
                                 }
                             }
+
+                            // Publie l'état complet des abonnements de la connexion.
+                            state.broker.confirm_subscription(&sid).await;
                         }
-                        "consumed" => {
-                            if let Ok(consumed_msg) =
-                                serde_json::from_value::<ConsumedMessage>(parsed.clone())
-                            {
-                                // Sauvegarde la confirmation de consommation.
-                                state
-                                    .broker
-                                    .save_consumption(
-                                        consumed_msg.consumer,
-                                        consumed_msg.topic,
-                                        consumed_msg.message_id,
-                                        consumed_msg.message,
-                                    )
-                                    .await;
+                    }
+                    "unsubscribe" => {
+                        // Retire un seul sujet : sa tâche de relais est arrêtée, les autres abonnements
+                        // de la connexion restent actifs.
+                        if let Ok(unsub_msg) =
+                            serde_json::from_value::<UnsubscribeMessage>(parsed.clone())
+                        {
+                            let frame = if state.unsubscribe(&sid, &unsub_msg.topic).await {
+                                serde_json::json!({
+                                    "event": "unsubscribed",
+                                    "topic": unsub_msg.topic,
+                                })
+                            } else {
+                                serde_json::json!({
+                                    "event": "error",
+                                    "reason": "not subscribed to this topic",
+                                    "topic": unsub_msg.topic,
+                                })
+                            };
+                            let _ = internal_tx.send(Message::Text(frame.to_string().into()));
+                        }
+                    }
+                    "response" => {
+                        // Réponse à une requête émise par le serveur (`POST /clients/{sid}/request`).
+                        if let Ok(response_msg) =
+                            serde_json::from_value::<ResponseMessage>(parsed.clone())
+                        {
+                            if !state.connections.complete(
+                                &sid,
+                                &response_msg.request_id,
+                                response_msg.payload,
+                            ) {
+                                warn!(
+                                    "Unexpected response {} from SID {} (unknown or expired request)",
+                                    response_msg.request_id, sid
+                                );
+                            }
+                        }
+                    }
+                    "consumed" => {
+                        if let Ok(consumed_msg) =
+                            serde_json::from_value::<ConsumedMessage>(parsed.clone())
+                        {
+                            let Some(consumer) = identity.resolve(&consumed_msg.consumer) else {
+                                let _ = internal_tx.send(error_frame(
+                                    "consumer identity required, send identify first",
+                                ));
+                                continue;
+                            };
+                            // Acquitte une éventuelle livraison QoS 1.
+                            in_flight.ack(&consumed_msg.topic, &consumed_msg.message_id);
+                            if state.config.persist_delivery_acks {
+                                state.broker.ack_delivery(
+                                    &consumer,
+                                    &consumed_msg.topic,
+                                    &consumed_msg.message_id,
+                                );
+                            }
+                            // Sauvegarde la confirmation de consommation.
+                            state
+                                .broker
+                                .save_consumption(
+                                    consumer,
+                                    consumed_msg.topic,
+                                    consumed_msg.message_id,
+                                    consumed_msg.message,
+                                )
+                                .await;
+                        }
+                    }
+                    "consumed_batch" => {
+                        if let Ok(batch_msg) =
+                            serde_json::from_value::<ConsumedBatchMessage>(parsed.clone())
+                        {
+                            let Some(consumer) = identity.resolve(&batch_msg.consumer) else {
+                                let _ = internal_tx.send(error_frame(
+                                    "consumer identity required, send identify first",
+                                ));
+                                continue;
+                            };
+                            for ack in &batch_msg.acks {
+                                in_flight.ack(&ack.topic, &ack.message_id);
+                                if state.config.persist_delivery_acks {
+                                    state.broker.ack_delivery(
+                                        &consumer,
+                                        &ack.topic,
+                                        &ack.message_id,
+                                    );
+                                }
                             }
+                            state
+                                .broker
+                                .save_consumptions_batch(&consumer, batch_msg.acks);
                         }
-                        _ => {}
+                    }
+                    "replay" => {
+                        if let Ok(replay_msg) =
+                            serde_json::from_value::<ReplayMessage>(parsed.clone())
+                        {
+                            replay(&state, &replay_msg, &internal_tx, flush_timeout).await;
+                        }
+                    }
+                    "flush" => {
+                        // Attend la validation en base de toutes les consommations déjà reçues.
+                        let committed = state.broker.flush_writes(flush_timeout).await;
+                        let frame = serde_json::json!({
+                            "event": "flushed",
+                            "committed": committed,
+                        });
+                        let _ = internal_tx.send(Message::Text(frame.to_string().into()));
+                    }
+                    // Événement inconnu (faute de frappe, client d'une autre version) : signalé au client.
+                    unknown => {
+                        let frame = serde_json::json!({
+                            "event": "error",
+                            "reason": "unknown event",
+                            "received": unknown,
+                        });
+                        let _ = internal_tx.send(Message::Text(frame.to_string().into()));
                     }
                 }
+            } else {
+                let _ = internal_tx.send(error_frame("missing event field"));
             }
+        } else {
+            let _ = internal_tx.send(error_frame("invalid JSON"));
         }
     }
 
-    // --- Nettoyage --- 
+    // --- Nettoyage ---
     // Ce code est exécuté lorsque la boucle de réception se termine (client déconnecté).
-    info!("Client disconnecting (SID: {})", sid);
-    // Désenregistre le client du Broker.
+    info!(
+        "Client disconnecting (SID: {}, connected for {:.1}s)",
+        sid,
+        opened_at.elapsed().as_secs_f64()
+    );
+    // Sur fermeture propre, les derniers `consumed` sont validés avant le nettoyage (`WS_FLUSH_ON_DISCONNECT`).
+    if clean_close
+        && state.config.ws_flush_on_disconnect
+        && !state.broker.flush_writes(flush_timeout).await
+    {
+        warn!(
+            "Pending consumptions of SID {} not committed before disconnect",
+            sid
+        );
+    }
+    // Désenregistre le client du Broker, de ses groupes et du registre des connexions.
     state.broker.unregister_client(&sid).await;
+    state.consumer_groups.leave_all(&sid);
+    state.connections.unregister(&sid).await;
     // Arrête toutes les tâches de fond associées à ce client pour libérer les ressources.
     broadcast_task.abort();
-    send_task.abort();
-
-    let tasks = topic_tasks.write().await;
-    for task in tasks.iter() {
+    if let Some(task) = redelivery_task {
         task.abort();
     }
+    {
+        let tasks = topic_tasks.write().await;
+        for task in tasks.values() {
+            task.abort();
+        }
+    }
+
+    // Laisse la tâche d'envoi vider les dernières trames (erreur, fermeture) avant de l'arrêter.
+    drop(internal_tx);
+    if tokio::time::timeout(Duration::from_secs(1), &mut send_task)
+        .await
+        .is_err()
+    {
+        send_task.abort();
+    }
+}
+
+// Livre une publication binaire aux connexions WebSocket abonnées au sujet : une trame texte
+// d'en-tête `{"event": "binary", topic, message_id, producer, timestamp, size}` suivie d'une trame
+// binaire portant le contenu. Les clients Socket.IO ne sont pas servis. Retourne le nombre de
+// connexions atteintes.
+pub async fn deliver_binary(
+    state: &AppState,
+    request: &BinaryPublishQuery,
+    payload: Bytes,
+    timestamp: f64,
+) -> usize {
+    let header = serde_json::json!({
+        "event": "binary",
+        "topic": request.topic,
+        "message_id": request.message_id,
+        "producer": request.producer,
+        "timestamp": timestamp,
+        "size": payload.len(),
+    })
+    .to_string();
+    let mut delivered = 0;
+    for sid in state.broker.subscribed_sids(&request.topic).await {
+        if let Some(ConnectionHandle::WebSocket(tx, _)) = state.connections.handle(&sid).await {
+            // Les deux trames passent par le même canal : l'ordre en-tête puis contenu est garanti.
+            if tx.send(Message::Text(header.clone().into())).is_ok()
+                && tx.send(Message::Binary(payload.clone())).is_ok()
+            {
+                delivered += 1;
+            }
+        }
+    }
+    state
+        .delivery_counters(&request.topic)
+        .delivered
+        .fetch_add(delivered as u64, Ordering::Relaxed);
+    delivered
+}
+
+// Se résout quand la connexion a atteint `MAX_CONNECTION_LIFETIME_SECS` ; jamais si la limite est désactivée.
+pub async fn max_lifetime(state: &AppState) {
+    match state.config.max_connection_lifetime_secs {
+        0 => std::future::pending().await,
+        secs => tokio::time::sleep(Duration::from_secs(secs)).await,
+    }
+}
+
+// Se résout quand la connexion n'a reçu aucune trame depuis `CLIENT_EXPIRY_SECS` ; jamais si la limite est désactivée.
+// Un pair à demi ouvert (réseau coupé sans fermeture TCP) est ainsi déconnecté sans attendre le système.
+async fn heartbeat_expired(expiry_secs: u64, last_activity: &AtomicU64) {
+    if expiry_secs == 0 {
+        return std::future::pending().await;
+    }
+    let expiry_ms = expiry_secs * 1000;
+    loop {
+        let idle = idle_millis(last_activity);
+        if idle >= expiry_ms {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(expiry_ms - idle)).await;
+    }
+}
+
+// Suivi des `Ping` envoyés par le serveur et de leur `Pong`.
+#[derive(Default)]
+struct Heartbeat {
+    // Horodatage (ms) du `Ping` en attente de réponse, 0 si aucun.
+    ping_sent_at: AtomicU64,
+    // Réveille `pong_overdue` à l'envoi d'un `Ping`.
+    ping_sent: Notify,
+}
+
+impl Heartbeat {
+    // Marque un nouveau `Ping` comme envoyé ; faux si le précédent attend encore son `Pong`.
+    fn ping_due(&self) -> bool {
+        if self.ping_sent_at.load(Ordering::Relaxed) != 0 {
+            return false;
+        }
+        record_activity(&self.ping_sent_at);
+        self.ping_sent.notify_one();
+        true
+    }
+
+    fn pong_received(&self) {
+        self.ping_sent_at.store(0, Ordering::Relaxed);
+    }
+
+    // Se résout quand un `Ping` reste sans `Pong` depuis `timeout_secs` ; jamais si la limite est désactivée.
+    async fn pong_overdue(&self, timeout_secs: u64) {
+        if timeout_secs == 0 {
+            return std::future::pending().await;
+        }
+        let timeout_ms = timeout_secs * 1000;
+        loop {
+            if self.ping_sent_at.load(Ordering::Relaxed) == 0 {
+                self.ping_sent.notified().await;
+                continue;
+            }
+            let waited = idle_millis(&self.ping_sent_at);
+            if waited >= timeout_ms {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(timeout_ms - waited)).await;
+        }
+    }
+}
+
+// Prochaine échéance de `Ping` ; jamais si `WS_PING_INTERVAL_SECS` vaut 0.
+async fn next_ping(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+// Relit les messages d'un sujet publiés après `since` (au plus `REPLAY_MAX_MESSAGES`), marqués
+// `"replayed": true`, puis envoie `{"event": "replay_done", "topic", "count", "truncated"}`.
+// Seuls les messages déjà numérotés au moment de la demande sont relus : les suivants arrivent
+// par le flux en direct (si la connexion est abonnée au sujet), sans être livrés deux fois.
+// Ils peuvent en revanche s'intercaler entre les messages relus, qui sont toujours plus anciens.
+async fn replay(
+    state: &AppState,
+    request: &ReplayMessage,
+    internal_tx: &mpsc::UnboundedSender<Message>,
+    flush_timeout: Duration,
+) {
+    if request.topic.is_empty() || is_topic_pattern(&request.topic) || !request.since.is_finite() {
+        let _ = internal_tx.send(error_frame("replay requires a topic and a since timestamp"));
+        return;
+    }
+    let head = state.broker.topic_head_seq(&request.topic);
+    // Les publications déjà numérotées doivent être en base pour être relues.
+    state.broker.flush_writes(flush_timeout).await;
+    let max = state.config.replay_max_messages;
+    let messages = match state
+        .broker
+        .get_messages_since(&request.topic, request.since, max + 1)
+        .await
+    {
+        Ok(messages) => messages,
+        Err(_) => {
+            let _ = internal_tx.send(error_frame("replay failed, try again later"));
+            return;
+        }
+    };
+    // Publiés après la demande (ou sur un sujet alors jamais publié) : laissés au flux en direct.
+    let after_request = |seq: i64| head.is_none_or(|head| seq > head);
+    let mut truncated = messages.len() as i64 > max;
+    let mut count = 0;
+    for message in messages.into_iter().take(max as usize) {
+        if message.seq.is_some_and(after_request) {
+            truncated = false;
+            break;
+        }
+        let Ok(mut frame) = serde_json::to_value(&message) else {
+            continue;
+        };
+        frame["replayed"] = true.into();
+        if internal_tx
+            .send(Message::Text(frame.to_string().into()))
+            .is_err()
+        {
+            return;
+        }
+        count += 1;
+    }
+    let done = serde_json::json!({
+        "event": "replay_done",
+        "topic": request.topic,
+        "count": count,
+        "truncated": truncated,
+    });
+    let _ = internal_tx.send(Message::Text(done.to_string().into()));
+}
+
+// Revendique `consumer` pour cette connexion selon `DUPLICATE_CONNECTION_POLICY`.
+// En cas de refus, envoie une trame d'erreur suivie d'une trame de fermeture et retourne `false`.
+async fn claim_consumer(
+    state: &AppState,
+    sid: &str,
+    consumer: &str,
+    internal_tx: &mpsc::UnboundedSender<Message>,
+) -> bool {
+    if state
+        .connections
+        .claim_consumer(sid, consumer, state.config.duplicate_connection_policy)
+        .await
+    {
+        return true;
+    }
+    warn!(
+        "Connection {} rejected: consumer {} already connected",
+        sid, consumer
+    );
+    let _ = internal_tx.send(error_frame("consumer already connected"));
+    let _ = internal_tx.send(Message::Close(Some(CloseFrame {
+        code: close_code::POLICY,
+        reason: "consumer already connected".into(),
+    })));
+    false
+}
+
+// Construit une trame d'erreur structurée à renvoyer au client.
+fn error_frame(reason: &str) -> Message {
+    let frame = serde_json::json!({
+        "event": "error",
+        "reason": reason,
+    });
+    Message::Text(frame.to_string().into())
+}
+
+// Trame de contrôle signalant à l'abonné les `missed` messages du sujet perdus faute d'avoir suivi
+// (canal du sujet saturé, voir `TOPIC_CHANNEL_CAPACITY`). Comme les autres trames de contrôle, elle porte
+// un champ `event`, absent des messages livrés.
+fn lag_frame(topic: &str, missed: u64) -> Message {
+    let frame = serde_json::json!({
+        "event": "lag",
+        "topic": topic,
+        "missed": missed,
+    });
+    Message::Text(frame.to_string().into())
+}
+
+// Variante de la tâche d'abonnement qui regroupe les messages d'un sujet.
+// Le premier message ouvre une fenêtre de `window` ; le lot est livré à son expiration
+// ou dès qu'il atteint `MAX_BATCH_SIZE`, sous la forme `{"event": "batch", "topic", "messages": [...]}`.
+async fn forward_batched(
+    mut rx: broadcast::Receiver<String>,
+    internal_tx: mpsc::UnboundedSender<Message>,
+    topic: String,
+    window: Duration,
+    tracker: Option<Arc<InFlightTracker>>,
+    delivery_log: Option<DeliveryLog>,
+    counters: Arc<DeliveryCounters>,
+) {
+    let mut batch = Vec::with_capacity(MAX_BATCH_SIZE);
+    loop {
+        // Attend sans limite le premier message du prochain lot.
+        match rx.recv().await {
+            Ok(msg) => {
+                if !deliverable(delivery_log.as_ref(), &msg).await {
+                    continue;
+                }
+                batch.push(batch_entry(msg, tracker.as_deref()));
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("Topic {} lagged by {} messages", topic, n);
+                counters.dropped.fetch_add(n, Ordering::Relaxed);
+                if internal_tx.send(lag_frame(&topic, n)).is_err() {
+                    return;
+                }
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+
+        let deadline = tokio::time::Instant::now() + window;
+        let mut closed = false;
+        while batch.len() < MAX_BATCH_SIZE {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Ok(msg)) => {
+                    if deliverable(delivery_log.as_ref(), &msg).await {
+                        batch.push(batch_entry(msg, tracker.as_deref()));
+                    }
+                }
+                Ok(Err(broadcast::error::RecvError::Lagged(n))) => {
+                    warn!("Topic {} lagged by {} messages", topic, n);
+                    counters.dropped.fetch_add(n, Ordering::Relaxed);
+                    let _ = internal_tx.send(lag_frame(&topic, n));
+                }
+                Ok(Err(broadcast::error::RecvError::Closed)) => {
+                    closed = true;
+                    break;
+                }
+                // Fenêtre expirée.
+                Err(_) => break,
+            }
+        }
+
+        let count = batch.len() as u64;
+        let frame = serde_json::json!({
+            "event": "batch",
+            "topic": topic,
+            "messages": std::mem::take(&mut batch),
+        });
+        if internal_tx
+            .send(Message::Text(frame.to_string().into()))
+            .is_err()
+        {
+            return;
+        }
+        counters.delivered.fetch_add(count, Ordering::Relaxed);
+        if closed {
+            return;
+        }
+    }
+}
+
+// Avec `PERSIST_DELIVERY_ACKS`, écarte les messages déjà acquittés et enregistre les autres comme livrés.
+async fn deliverable(delivery_log: Option<&DeliveryLog>, msg: &str) -> bool {
+    match delivery_log {
+        Some(log) => log.deliver(msg).await,
+        None => true,
+    }
+}
+
+// Les messages des canaux de sujet sont des chaînes JSON : on les ré-imbrique tels quels dans le lot.
+// En QoS 1, chaque message du lot est suivi individuellement (et renvoyé seul si besoin).
+fn batch_entry(msg: String, tracker: Option<&InFlightTracker>) -> serde_json::Value {
+    if let Some(tracker) = tracker {
+        tracker.track(&msg);
+    }
+    serde_json::from_str(&msg).unwrap_or(serde_json::Value::String(msg))
+}
+
+// Renvoie périodiquement les livraisons QoS 1 dont l'accusé n'est pas arrivé à temps.
+fn spawn_redelivery(
+    in_flight: Arc<InFlightTracker>,
+    internal_tx: mpsc::UnboundedSender<Message>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(in_flight.tick());
+        loop {
+            interval.tick().await;
+            for frame in in_flight.due() {
+                if internal_tx.send(Message::Text(frame.into())).is_err() {
+                    return;
+                }
+            }
+        }
+    })
 }
@@ -0,0 +1,73 @@
+// Forwarder ordonné pour les abonnés wildcard (`*`, salle `__all__`).
+//
+// Garantie fournie lorsque `WILDCARD_ORDERING=per_topic` :
+// - Pour un sujet donné, les abonnés wildcard reçoivent les messages strictement par `seq` croissant,
+//   sans trou ni inversion (le `seq` est attribué et mis en file sous le même verrou dans le `Broker`).
+// - Entre sujets différents, aucun ordre global n'est garanti : les sujets ayant des messages en attente
+//   sont servis en round-robin, un message par sujet et par tour, si bien qu'un sujet très bavard
+//   ne peut pas retarder indéfiniment un sujet plus calme.
+// - Cette garantie ne s'applique qu'au flux wildcard ; la livraison par salle de sujet reste inchangée.
+use socketioxide::SocketIo;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::mpsc;
+use tracing::info;
+
+// Un message en attente de livraison aux abonnés wildcard.
+pub struct WildcardItem {
+    pub topic: String,
+    // Trame déjà construite (même forme que l'émission `message` par sujet, `seq` inclus).
+    pub frame: serde_json::Value,
+}
+
+// Lance la tâche de fusion qui consomme les messages mis en file par le `Broker`
+// et les émet dans la salle `__all__` en respectant la garantie décrite ci-dessus.
+pub fn spawn_merger(io: SocketIo, mut rx: mpsc::UnboundedReceiver<WildcardItem>) {
+    tokio::spawn(async move {
+        info!("Wildcard merger started (per-topic ordering)");
+        // Files d'attente par sujet (FIFO, donc déjà triées par `seq`).
+        let mut queues: HashMap<String, VecDeque<serde_json::Value>> = HashMap::new();
+        // Ordre de service des sujets ayant des messages en attente.
+        let mut ready: VecDeque<String> = VecDeque::new();
+
+        loop {
+            // Si rien n'est en attente, on bloque jusqu'au prochain message.
+            if ready.is_empty() {
+                match rx.recv().await {
+                    Some(item) => enqueue(&mut queues, &mut ready, item),
+                    None => break,
+                }
+            }
+            // Récupère tout ce qui est déjà arrivé sans bloquer, pour que le round-robin voie tous les sujets.
+            while let Ok(item) = rx.try_recv() {
+                enqueue(&mut queues, &mut ready, item);
+            }
+
+            // Sert un seul message du sujet en tête, puis le remet en fin de tour s'il en reste.
+            if let Some(topic) = ready.pop_front() {
+                let frame = queues.get_mut(&topic).and_then(|q| q.pop_front());
+                if queues.get(&topic).is_some_and(|q| !q.is_empty()) {
+                    ready.push_back(topic);
+                } else {
+                    queues.remove(&topic);
+                }
+
+                if let (Some(frame), Some(ns)) = (frame, io.of("/")) {
+                    let _ = ns.to("__all__").emit("message", &frame).await;
+                }
+            }
+        }
+    });
+}
+
+// Ajoute un message à la file de son sujet et inscrit le sujet dans le tour s'il n'y était pas.
+fn enqueue(
+    queues: &mut HashMap<String, VecDeque<serde_json::Value>>,
+    ready: &mut VecDeque<String>,
+    item: WildcardItem,
+) {
+    let queue = queues.entry(item.topic.clone()).or_default();
+    if queue.is_empty() {
+        ready.push_back(item.topic);
+    }
+    queue.push_back(item.frame);
+}
@@ -0,0 +1,455 @@
+// Extension WebSocket `permessage-deflate` (RFC 7692) sur `/ws` (`WS_COMPRESSION`).
+//
+// tungstenite ne sait pas compresser les trames : l'offre du client est acceptée lors de l'upgrade,
+// les messages sortants sont compressés ici puis remis à tungstenite sous forme de trames brutes
+// (bit RSV1 positionné), et les messages entrants sont décompressés par `InflateStream`, placé entre
+// la connexion et tungstenite, qui ne voit passer que des trames ordinaires. Les deux sens travaillent
+// sans reprise de contexte (`*_no_context_takeover`) : chaque message est compressé indépendamment.
+use axum::body::Bytes;
+use axum::extract::ws::{CloseFrame, Message, Utf8Bytes};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::{Frame, FrameHeader};
+use tokio_tungstenite::tungstenite::{self as ts};
+use tokio_tungstenite::WebSocketStream;
+
+// Valeur de `Sec-WebSocket-Extensions` renvoyée au client qui propose l'extension.
+pub const ACCEPTED_EXTENSION: &str =
+    "permessage-deflate; server_no_context_takeover; client_no_context_takeover";
+
+// Taille à partir de laquelle un message sortant est compressé : en deçà, le gain ne couvre pas le coût.
+const MIN_COMPRESSED_BYTES: usize = 512;
+
+// Fin d'un bloc deflate vidé (`Z_SYNC_FLUSH`), retirée à l'envoi et rajoutée à la réception.
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+// Connexion WebSocket de `/ws`, décompressée à la lecture.
+pub type DeflateSocket = WebSocketStream<InflateStream<TokioIo<Upgraded>>>;
+
+// Vrai si l'une des offres `Sec-WebSocket-Extensions` du client est une offre `permessage-deflate`
+// acceptable : sans paramètre inconnu, et sans réduire la fenêtre du serveur (flate2 compresse sur 15 bits).
+pub fn offer_accepted<'a>(headers: impl IntoIterator<Item = &'a str>) -> bool {
+    headers
+        .into_iter()
+        .flat_map(|header| header.split(','))
+        .any(|offer| {
+            let mut params = offer.split(';').map(str::trim);
+            params.next() == Some("permessage-deflate") && params.all(param_accepted)
+        })
+}
+
+fn param_accepted(param: &str) -> bool {
+    let (name, value) = match param.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+        None => (param, None),
+    };
+    match name {
+        "server_no_context_takeover" | "client_no_context_takeover" => value.is_none(),
+        // La fenêtre du client est sans effet : la décompression accepte toutes les tailles.
+        "client_max_window_bits" => {
+            value.is_none_or(|bits| matches!(bits.parse::<u8>(), Ok(8..=15)))
+        }
+        "server_max_window_bits" => value == Some("15"),
+        _ => false,
+    }
+}
+
+// Convertit un message à envoyer en message tungstenite ; les messages de données assez grands
+// sont compressés lorsque l'extension a été négociée.
+pub fn outgoing(msg: Message, compress: bool) -> ts::Message {
+    match msg {
+        Message::Text(text) => data_frame(Bytes::from(text), Data::Text, compress),
+        Message::Binary(data) => data_frame(data, Data::Binary, compress),
+        Message::Ping(data) => ts::Message::Ping(data),
+        Message::Pong(data) => ts::Message::Pong(data),
+        Message::Close(frame) => ts::Message::Close(frame.map(|frame| ts::protocol::CloseFrame {
+            code: frame.code.into(),
+            reason: frame.reason.as_str().into(),
+        })),
+    }
+}
+
+// Convertit un message reçu par tungstenite ; `None` pour une trame brute, qui n'est jamais lue.
+pub fn incoming(msg: ts::Message) -> Option<Message> {
+    Some(match msg {
+        ts::Message::Text(text) => Message::Text(Utf8Bytes::from(text.as_str())),
+        ts::Message::Binary(data) => Message::Binary(data),
+        ts::Message::Ping(data) => Message::Ping(data),
+        ts::Message::Pong(data) => Message::Pong(data),
+        ts::Message::Close(frame) => Message::Close(frame.map(|frame| CloseFrame {
+            code: frame.code.into(),
+            reason: frame.reason.as_str().into(),
+        })),
+        ts::Message::Frame(_) => return None,
+    })
+}
+
+fn data_frame(payload: Bytes, opcode: Data, compress: bool) -> ts::Message {
+    let deflated = (compress && payload.len() >= MIN_COMPRESSED_BYTES)
+        .then(|| deflate(&payload))
+        .flatten();
+    let frame = match deflated {
+        Some(deflated) => {
+            let mut frame = Frame::message(deflated, OpCode::Data(opcode), true);
+            frame.header_mut().rsv1 = true;
+            frame
+        }
+        None => Frame::message(payload, OpCode::Data(opcode), true),
+    };
+    ts::Message::Frame(frame)
+}
+
+// Compresse un message complet ; `None` en cas d'échec, le message part alors non compressé.
+fn deflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut compress = Compress::new(Compression::default(), false);
+    let mut out = Vec::with_capacity(data.len() / 2 + 64);
+    loop {
+        let consumed = compress.total_in() as usize;
+        compress
+            .compress_vec(&data[consumed..], &mut out, FlushCompress::Sync)
+            .ok()?;
+        if compress.total_in() as usize == data.len() && out.len() < out.capacity() {
+            break;
+        }
+        out.reserve(out.capacity().max(64));
+    }
+    if out.ends_with(&DEFLATE_TAIL) {
+        out.truncate(out.len() - DEFLATE_TAIL.len());
+    }
+    Some(out)
+}
+
+// Décompresse un message reçu, en refusant un résultat de plus de `limit` octets.
+fn inflate(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    let input = [data, &DEFLATE_TAIL].concat();
+    let mut decompress = Decompress::new(false);
+    let mut out = Vec::with_capacity((data.len() * 4).min(limit + 1));
+    loop {
+        let (read, written) = (decompress.total_in(), decompress.total_out());
+        let status = decompress
+            .decompress_vec(&input[read as usize..], &mut out, FlushDecompress::Sync)
+            .map_err(invalid_data)?;
+        if out.len() > limit {
+            return Err(invalid_data(
+                "decompressed message exceeds MAX_MESSAGE_BYTES",
+            ));
+        }
+        let finished = status == Status::StreamEnd || decompress.total_in() as usize == input.len();
+        if out.len() < out.capacity() {
+            if finished {
+                return Ok(out);
+            }
+            if (read, written) == (decompress.total_in(), decompress.total_out()) {
+                return Err(invalid_data("truncated compressed message"));
+            }
+        } else {
+            out.reserve(out.len().max(1024).min(limit + 1 - out.len()));
+        }
+    }
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// Flux placé entre la connexion et tungstenite : un message compressé (bit RSV1, éventuellement
+// fragmenté) est décompressé et rendu sous la forme d'une seule trame ordinaire ; les autres trames
+// passent telles quelles. Sans extension négociée, le flux est transparent.
+pub struct InflateStream<S> {
+    inner: S,
+    enabled: bool,
+    max_message_bytes: usize,
+    // Octets reçus, pas encore découpés en trames complètes.
+    input: Vec<u8>,
+    // Octets prêts à être lus par tungstenite, à partir de `output_pos`.
+    output: Vec<u8>,
+    output_pos: usize,
+    // Message compressé fragmenté en cours de réassemblage (type, charge utile démasquée).
+    pending: Option<(Data, Vec<u8>)>,
+    eof: bool,
+}
+
+impl<S> InflateStream<S> {
+    pub fn new(inner: S, enabled: bool, max_message_bytes: usize) -> Self {
+        Self {
+            inner,
+            enabled,
+            max_message_bytes,
+            input: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            pending: None,
+            eof: false,
+        }
+    }
+
+    // Traite toutes les trames complètes reçues.
+    fn process_input(&mut self) -> io::Result<()> {
+        let mut offset = 0;
+        while let Some(len) = self.process_frame(offset)? {
+            offset += len;
+        }
+        self.input.drain(..offset);
+        Ok(())
+    }
+
+    // Traite la trame commençant à `offset` et renvoie sa longueur ; `None` si elle n'est pas
+    // encore entièrement reçue.
+    fn process_frame(&mut self, offset: usize) -> io::Result<Option<usize>> {
+        let mut cursor = Cursor::new(&self.input[offset..]);
+        let Some((header, length)) = FrameHeader::parse(&mut cursor).map_err(invalid_data)? else {
+            return Ok(None);
+        };
+        if length > self.max_message_bytes as u64 {
+            return Err(invalid_data("frame exceeds MAX_MESSAGE_BYTES"));
+        }
+        let start = offset + cursor.position() as usize;
+        let end = start + length as usize;
+        if self.input.len() < end {
+            return Ok(None);
+        }
+
+        let opcode = match header.opcode {
+            OpCode::Data(Data::Continue) if self.pending.is_some() => Data::Continue,
+            OpCode::Data(_) if self.pending.is_some() => {
+                return Err(invalid_data(
+                    "data frame inside a fragmented compressed message",
+                ));
+            }
+            OpCode::Data(opcode) if header.rsv1 => opcode,
+            // Trame de contrôle ou message non compressé.
+            _ => {
+                self.output.extend_from_slice(&self.input[offset..end]);
+                return Ok(Some(end - offset));
+            }
+        };
+
+        let (opcode, mut message) = self.pending.take().unwrap_or((opcode, Vec::new()));
+        let mut payload = self.input[start..end].to_vec();
+        if let Some(mask) = header.mask {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+        message.extend_from_slice(&payload);
+        if message.len() > self.max_message_bytes {
+            return Err(invalid_data("message exceeds MAX_MESSAGE_BYTES"));
+        }
+        if !header.is_final {
+            self.pending = Some((opcode, message));
+            return Ok(Some(end - offset));
+        }
+
+        // La trame rendue garde le masquage de la trame reçue (masque nul) : tungstenite exige
+        // des trames masquées du client et non masquées du serveur.
+        let inflated = inflate(&message, self.max_message_bytes)?;
+        let frame = Frame::from_payload(
+            FrameHeader {
+                opcode: OpCode::Data(opcode),
+                mask: header.mask.map(|_| [0; 4]),
+                ..FrameHeader::default()
+            },
+            inflated.into(),
+        );
+        frame.format(&mut self.output).map_err(invalid_data)?;
+        Ok(Some(end - offset))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for InflateStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.enabled {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        loop {
+            if this.output_pos < this.output.len() {
+                let len = (this.output.len() - this.output_pos).min(buf.remaining());
+                buf.put_slice(&this.output[this.output_pos..this.output_pos + len]);
+                this.output_pos += len;
+                if this.output_pos == this.output.len() {
+                    this.output.clear();
+                    this.output_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if this.eof {
+                return Poll::Ready(Ok(()));
+            }
+            let mut chunk = [0; 8192];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf))?;
+            if chunk_buf.filled().is_empty() {
+                this.eof = true;
+                continue;
+            }
+            this.input.extend_from_slice(chunk_buf.filled());
+            this.process_input()?;
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for InflateStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    const LIMIT: usize = 2 * 1024 * 1024;
+
+    fn large_payload() -> String {
+        (0..20_000)
+            .map(|i| format!("{{\"seq\":{i},\"topic\":\"orders.eu\",\"message\":\"order {i}\"}}\n"))
+            .collect()
+    }
+
+    async fn socket(io: DuplexStream, role: Role) -> WebSocketStream<InflateStream<DuplexStream>> {
+        WebSocketStream::from_raw_socket(InflateStream::new(io, true, LIMIT), role, None).await
+    }
+
+    async fn receive(socket: &mut WebSocketStream<InflateStream<DuplexStream>>) -> Message {
+        incoming(socket.next().await.unwrap().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn offers_are_accepted_unless_the_server_window_is_reduced() {
+        assert!(offer_accepted(["permessage-deflate"]));
+        assert!(offer_accepted([
+            "permessage-deflate; client_max_window_bits"
+        ]));
+        assert!(offer_accepted([
+            "x-webkit-deflate-frame, permessage-deflate; server_no_context_takeover"
+        ]));
+        assert!(offer_accepted([
+            "permessage-deflate; server_max_window_bits=10",
+            "permessage-deflate"
+        ]));
+        assert!(!offer_accepted([
+            "permessage-deflate; server_max_window_bits=10"
+        ]));
+        assert!(!offer_accepted(["permessage-deflate; unknown_param"]));
+        assert!(!offer_accepted(["x-webkit-deflate-frame"]));
+        assert!(!offer_accepted([]));
+    }
+
+    #[test]
+    fn only_large_data_messages_are_compressed() {
+        let ts::Message::Frame(frame) = outgoing(Message::Text(large_payload().into()), true)
+        else {
+            panic!("data messages are sent as raw frames");
+        };
+        assert!(frame.header().rsv1);
+        assert!(frame.payload().len() < large_payload().len() / 4);
+
+        let ts::Message::Frame(frame) = outgoing(Message::Text("small".into()), true) else {
+            panic!("data messages are sent as raw frames");
+        };
+        assert!(!frame.header().rsv1);
+
+        let ts::Message::Frame(frame) = outgoing(Message::Text(large_payload().into()), false)
+        else {
+            panic!("data messages are sent as raw frames");
+        };
+        assert!(!frame.header().rsv1);
+    }
+
+    #[tokio::test]
+    async fn large_payload_round_trips_compressed() {
+        let (client_io, server_io) = duplex(64 * 1024);
+        let (mut client, mut server) = tokio::join!(
+            socket(client_io, Role::Client),
+            socket(server_io, Role::Server)
+        );
+        let payload = large_payload();
+
+        let (sent, received) = tokio::join!(
+            client.send(outgoing(Message::Text(payload.clone().into()), true)),
+            receive(&mut server)
+        );
+        sent.unwrap();
+        assert_eq!(received, Message::Text(payload.clone().into()));
+
+        let (sent, received) = tokio::join!(
+            server.send(outgoing(Message::Binary(payload.clone().into()), true)),
+            receive(&mut client)
+        );
+        sent.unwrap();
+        assert_eq!(received, Message::Binary(payload.into()));
+    }
+
+    #[tokio::test]
+    async fn fragmented_compressed_message_is_reassembled() {
+        let (mut client_io, server_io) = duplex(64 * 1024);
+        let mut server = socket(server_io, Role::Server).await;
+        let payload = large_payload();
+        let deflated = deflate(payload.as_bytes()).unwrap();
+        let (first, second) = deflated.split_at(deflated.len() / 2);
+
+        let mut raw = Vec::new();
+        let header = |opcode, rsv1, is_final| FrameHeader {
+            is_final,
+            rsv1,
+            opcode: OpCode::Data(opcode),
+            mask: Some([1, 2, 3, 4]),
+            ..FrameHeader::default()
+        };
+        Frame::from_payload(header(Data::Text, true, false), first.to_vec().into())
+            .format(&mut raw)
+            .unwrap();
+        Frame::from_payload(header(Data::Continue, false, true), second.to_vec().into())
+            .format(&mut raw)
+            .unwrap();
+
+        let (written, received) = tokio::join!(client_io.write_all(&raw), receive(&mut server));
+        written.unwrap();
+        assert_eq!(received, Message::Text(payload.into()));
+    }
+
+    #[test]
+    fn inflated_messages_are_bounded() {
+        let deflated = deflate(&vec![b'a'; 64 * 1024]).unwrap();
+        assert!(inflate(&deflated, 64 * 1024).is_ok());
+        assert!(inflate(&deflated, 1024).is_err());
+    }
+}
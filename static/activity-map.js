@@ -1,5 +1,5 @@
 document.addEventListener("DOMContentLoaded", () => {
-    const socket = io();
+    const socket = io({path: `${window.BASE_PATH}/socket.io`});
     const producersCol = document.getElementById('producers-col');
     const topicsCol = document.getElementById('topics-col');
     const consumersCol = document.getElementById('consumers-col');
@@ -131,7 +131,7 @@ document.addEventListener("DOMContentLoaded", () => {
      */
     async function initializeActivityMap() {
         try {
-            const response = await fetch('/graph/state');
+            const response = await fetch(`${window.BASE_PATH}/graph/state`);
             const state = await response.json();
 
             console.log('Initial graph state:', state);
@@ -155,6 +155,8 @@ document.addEventListener("DOMContentLoaded", () => {
         }
     }
 
+    replayEventsBatch(socket);
+
     socket.on('connect', () => {
         console.log('Connected to activity stream.');
         // Load initial state when connected
@@ -8,7 +8,7 @@
 /* export */
 function createGraph(config) {
     // --- Socket.io and D3 initialization ---
-    const socket = io();
+    const socket = io({path: `${window.BASE_PATH}/socket.io`});
     const svg = d3.select(config.svgSelector);
     const width = svg.node().getBoundingClientRect().width;
     const height = svg.node().getBoundingClientRect().height;
@@ -151,7 +151,7 @@ function createGraph(config) {
     // --- Initialization and WebSockets ---
 
     async function initializeGraph() {
-        const response = await fetch('/graph/state');
+        const response = await fetch(`${window.BASE_PATH}/graph/state`);
         const state = await response.json();
 
         // Remove loading text if present
@@ -188,6 +188,7 @@ function createGraph(config) {
     }
 
     socket.on('connect', () => console.log('Connected to activity stream.'));
+    replayEventsBatch(socket);
     socket.on('new_message', (data) => handleWebSocketEvent({...data, type: 'publish'}));
     socket.on('new_consumption', (data) => handleWebSocketEvent({...data, type: 'consume'}));
     socket.on('new_client', (data) => handleWebSocketEvent({...data, type: 'consume'})); // Treated as new_consumption
@@ -57,6 +57,7 @@ document.addEventListener("DOMContentLoaded", () => {
         }
 
         socket = io({
+            path: `${window.BASE_PATH}/socket.io`,
             reconnection: true,
             reconnectionAttempts: Infinity,
             reconnectionDelay: 2000
@@ -78,10 +79,18 @@ document.addEventListener("DOMContentLoaded", () => {
         });
 
         socket.on("disconnect", () => console.log("Disconnected from server."));
+        replayEventsBatch(socket);
         socket.on("new_message", () => refreshMessages());
         socket.on("new_client", () => refreshClients());
+        socket.on("client_unsubscribed", () => refreshClients());
         socket.on("client_disconnected", () => refreshClients());
+        // Authoritative number of subscribed connections (distinct SIDs).
+        socket.on("client_count", ({count}) => {
+            document.getElementById("clientCount").textContent = count;
+        });
         socket.on("new_consumption", () => refreshConsumptions());
+        socket.on("delivery_failed", (data) =>
+            console.warn(`Live delivery failed: ${data.message_id} on ${data.topic} (room ${data.room}): ${data.error}`));
         socket.on("consumed", (data) => {
             console.log(`Consumed by handler: ${data.consumer} - Topic: ${data.topic} - Message ID: ${data.message_id}`);
             refreshConsumptions();
@@ -101,7 +110,7 @@ document.addEventListener("DOMContentLoaded", () => {
         const msg = new TextMessage(messageText, producer, uuidv4());
         const payload = msg.toPayload(topic);
 
-        fetch("/publish", {
+        fetch(`${window.BASE_PATH}/publish`, {
             method: "POST",
             headers: {"Content-Type": "application/json"},
             body: JSON.stringify(payload)
@@ -129,7 +138,7 @@ document.addEventListener("DOMContentLoaded", () => {
         const tbody = document.querySelector("#clientsTable tbody");
         tbody.innerHTML = '<tr><td colspan="3" class="text-center text-muted">En attente...</td></tr>';
 
-        fetch("/clients")
+        fetch(`${window.BASE_PATH}/clients`)
             .then(r => r.json())
             .then(clients => {
                 tbody.innerHTML = "";
@@ -156,7 +165,7 @@ document.addEventListener("DOMContentLoaded", () => {
         const tbody = document.querySelector("#messagesTable tbody");
         tbody.innerHTML = '<tr><td colspan="4" class="text-center text-muted">En attente...</td></tr>';
 
-        fetch("/messages")
+        fetch(`${window.BASE_PATH}/messages`)
             .then(r => r.json())
             .then(messages => {
                 tbody.innerHTML = "";
@@ -184,7 +193,7 @@ document.addEventListener("DOMContentLoaded", () => {
         const tbody = document.querySelector("#consTable tbody");
         tbody.innerHTML = '<tr><td colspan="4" class="text-center text-muted">En attente...</td></tr>';
 
-        fetch("/consumptions")
+        fetch(`${window.BASE_PATH}/consumptions`)
             .then(r => r.json())
             .then(consumptions => {
                 tbody.innerHTML = "";
@@ -23,7 +23,7 @@
     // If accessing a protected page without authentication, redirect to login
     if (isProtectedPage && !isDashboardEnabled) {
         // Store the intended destination
-        const redirectUrl = `/login.html?redirect=${encodeURIComponent(currentPath)}`;
+        const redirectUrl = `${window.BASE_PATH}/login.html?redirect=${encodeURIComponent(currentPath)}`;
         window.location.replace(redirectUrl);
         // Prevent further script execution
         throw new Error('Redirecting to login page');
@@ -45,20 +45,20 @@
 
         logoutBtn.addEventListener('click', async () => {
             try {
-                const response = await fetch('/dashboard/logout', {method: 'POST'});
+                const response = await fetch(`${window.BASE_PATH}/dashboard/logout`, {method: 'POST'});
                 const data = await response.json();
 
                 if (data.status === 'ok') {
                     localStorage.setItem('dashboardEnabled', 'false');
                     console.log('Dashboard disabled');
                     // Redirect to login page
-                    window.location.href = '/login.html';
+                    window.location.href = `${window.BASE_PATH}/login.html`;
                 }
             } catch (error) {
                 console.error('Logout error:', error);
                 // Still redirect to login page even on error
                 localStorage.setItem('dashboardEnabled', 'false');
-                window.location.href = '/login.html';
+                window.location.href = `${window.BASE_PATH}/login.html`;
             }
         });
     }
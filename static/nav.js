@@ -3,9 +3,9 @@ document.addEventListener('DOMContentLoaded', function () {
     if (!navContainer) return;
 
     const navItems = [
-        {href: '/control-panel.html', label: 'Control Panel'},
-        {href: '/activity-map.html', label: 'Activity Map'},
-        {href: '/circular-graph.html', label: 'Circular Graph'}
+        {href: `${window.BASE_PATH}/control-panel.html`, label: 'Control Panel'},
+        {href: `${window.BASE_PATH}/activity-map.html`, label: 'Activity Map'},
+        {href: `${window.BASE_PATH}/circular-graph.html`, label: 'Circular Graph'}
     ];
 
     const currentPath = window.location.pathname;
@@ -22,7 +22,7 @@ document.addEventListener('DOMContentLoaded', function () {
         <nav class="navbar navbar-dark fixed-top">
             <div class="container" style="padding-left: 1.5rem; padding-right: 1.5rem;">
                 <div class="d-flex align-items-center">
-                    <a class="navbar-brand" href="/control-panel.html">📊 Pub/Sub Monitor</a>
+                    <a class="navbar-brand" href="${window.BASE_PATH}/control-panel.html">📊 Pub/Sub Monitor</a>
                     <div class="ms-4 d-flex gap-2">${navLinksHTML}</div>
                 </div>
                 <button class="btn btn-sm btn-outline-danger" id="dashboardLogoutBtn" style="margin-left: auto;">Logout</button>
@@ -0,0 +1,7 @@
+// Shared Socket.IO helpers for the dashboard pages.
+
+// Batched events (EVENT_BATCH_WINDOW_MS): replay each one on the socket's usual handlers.
+function replayEventsBatch(socket) {
+    socket.on('events_batch', (events) => events.forEach(({event_type, data}) =>
+        socket.listeners(event_type).forEach((handler) => handler(data))));
+}
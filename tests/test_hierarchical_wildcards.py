@@ -0,0 +1,110 @@
+#!/usr/bin/env python3
+"""
+Test des motifs hiérarchiques de sujets sur le endpoint WebSocket brut (/ws).
+
+- `orders.*` couvre exactement un segment : `orders.eu`, `orders.` (segment vide final),
+  mais ni `orders` ni `orders.eu.paris`
+- `orders.#` couvre zéro ou plusieurs segments : `orders`, `orders.eu`, `orders.eu.paris`
+- `orders.*.eu` couvre un segment vide au milieu : `orders..eu`
+- `#` ailleurs qu'en dernier segment est refusé avec une trame d'erreur
+- publier directement sur un motif est refusé (400)
+
+Prérequis : pip3 install websocket-client requests
+"""
+import json
+import sys
+import uuid
+
+import requests
+import websocket
+
+WS_URL = "ws://localhost:5000/ws"
+PUBLISH_URL = "http://localhost:5000/publish"
+
+
+def subscribe(topics):
+    ws = websocket.create_connection(WS_URL, timeout=2)
+    welcome = json.loads(ws.recv())
+    assert welcome["event"] == "welcome", f"trame inattendue: {welcome}"
+    ws.send(json.dumps({
+        "event": "subscribe",
+        "consumer": f"wildcard-{uuid.uuid4().hex[:8]}",
+        "topics": topics,
+    }))
+    return ws
+
+
+def publish(topic):
+    message_id = str(uuid.uuid4())
+    response = requests.post(PUBLISH_URL, json={
+        "topic": topic,
+        "message_id": message_id,
+        "message": {"topic": topic},
+        "producer": "test_hierarchical_wildcards",
+    }, timeout=5)
+    return response, message_id
+
+
+def received_topics(ws):
+    """Collecte les sujets des messages reçus jusqu'au délai d'attente."""
+    topics = []
+    while True:
+        try:
+            frame = json.loads(ws.recv())
+        except websocket.WebSocketTimeoutException:
+            return topics
+        if "message_id" in frame:
+            topics.append(frame["topic"])
+
+
+def check(pattern, matching, not_matching):
+    # Préfixe unique pour ne pas recevoir les publications d'un autre test.
+    prefix = uuid.uuid4().hex[:8]
+    ws = subscribe([f"{prefix}.{pattern}"])
+    for topic in matching + not_matching:
+        response, _ = publish(f"{prefix}.{topic}")
+        assert response.status_code == 200, f"publication refusée: {response.text}"
+    topics = received_topics(ws)
+    ws.close()
+    expected = sorted(f"{prefix}.{topic}" for topic in matching)
+    assert sorted(topics) == expected, f"{pattern}: reçu {topics}, attendu {expected}"
+    print(f"✓ {pattern} -> {matching}, sans {not_matching}")
+
+
+def test_single_segment():
+    check("orders.*", ["orders.eu", "orders."], ["orders", "orders.eu.paris", "invoices.eu"])
+
+
+def test_multi_segment():
+    check("orders.#", ["orders", "orders.eu", "orders.eu.paris"], ["invoices.eu", "ordersx"])
+
+
+def test_empty_segment():
+    check("orders.*.eu", ["orders..eu", "orders.fr.eu"], ["orders.eu", "orders.fr.us"])
+
+
+def test_invalid_pattern():
+    ws = subscribe(["orders.#.eu"])
+    frame = json.loads(ws.recv())
+    ws.close()
+    assert frame["event"] == "error", f"trame inattendue: {frame}"
+    print(f"✓ Motif invalide refusé: {frame['reason']}")
+
+
+def test_publish_to_pattern():
+    response, _ = publish("orders.*")
+    assert response.status_code == 400, f"statut inattendu: {response.status_code}"
+    print("✓ Publication sur un motif refusée (400)")
+
+
+if __name__ == "__main__":
+    try:
+        test_single_segment()
+        test_multi_segment()
+        test_empty_segment()
+        test_invalid_pattern()
+        test_publish_to_pattern()
+    except AssertionError as e:
+        print(f"✗ Échec: {e}")
+        sys.exit(1)
+    print("\nTous les tests de motifs hiérarchiques sont passés")
@@ -0,0 +1,98 @@
+#!/usr/bin/env python3
+"""
+Test de la gestion des différents types de trames sur le endpoint WebSocket brut (/ws).
+
+Vérifie d'abord la trame `welcome` envoyée à la connexion, puis pour chaque type de trame :
+- Ping   -> le serveur répond par un Pong portant le même payload
+- Texte vide -> ignoré silencieusement, la connexion reste ouverte
+- Binaire -> rejeté avec une trame d'erreur JSON explicite
+- Close  -> le serveur ferme proprement la connexion
+
+Prérequis : pip3 install websocket-client
+"""
+import json
+import sys
+
+import websocket
+
+WS_URL = "ws://localhost:5000/ws"
+
+
+def connect():
+    ws = websocket.create_connection(WS_URL, timeout=5)
+    # La première trame est toujours la trame de bienvenue du serveur.
+    welcome = json.loads(ws.recv())
+    assert welcome["event"] == "welcome", f"trame inattendue: {welcome}"
+    return ws
+
+
+def test_welcome():
+    ws = websocket.create_connection(WS_URL, timeout=5)
+    welcome = json.loads(ws.recv())
+    ws.close()
+    assert welcome["event"] == "welcome", f"trame inattendue: {welcome}"
+    for field in ("server_version", "max_message_bytes", "supported_events", "server_time"):
+        assert field in welcome, f"champ manquant: {field}"
+    assert "subscribe" in welcome["supported_events"]
+    print(f"✓ Trame welcome reçue (version {welcome['server_version']})")
+
+
+def recv_frame(ws):
+    """Reçoit une trame en conservant son opcode (y compris les trames de contrôle)."""
+    opcode, data = ws.recv_data(control_frame=True)
+    return opcode, data
+
+
+def test_ping():
+    ws = connect()
+    ws.ping(b"hello")
+    opcode, data = recv_frame(ws)
+    ws.close()
+    assert opcode == websocket.ABNF.OPCODE_PONG, f"opcode inattendu: {opcode}"
+    assert data == b"hello", f"payload inattendu: {data!r}"
+    print("✓ Ping -> Pong avec le même payload")
+
+
+def test_empty_text():
+    ws = connect()
+    ws.send("")
+    ws.send("   ")
+    # La connexion doit rester utilisable : un ping reçoit toujours sa réponse.
+    ws.ping(b"still-alive")
+    opcode, data = recv_frame(ws)
+    ws.close()
+    assert opcode == websocket.ABNF.OPCODE_PONG and data == b"still-alive"
+    print("✓ Trames texte vides ignorées, connexion toujours ouverte")
+
+
+def test_binary_rejected():
+    ws = connect()
+    ws.send_binary(b"\x00\x01\x02")
+    opcode, data = recv_frame(ws)
+    ws.close()
+    assert opcode == websocket.ABNF.OPCODE_TEXT, f"opcode inattendu: {opcode}"
+    frame = json.loads(data)
+    assert frame["event"] == "error", f"trame inattendue: {frame}"
+    print(f"✓ Trame binaire rejetée: {frame['reason']}")
+
+
+def test_close():
+    ws = connect()
+    ws.send_close()
+    opcode, _ = recv_frame(ws)
+    assert opcode == websocket.ABNF.OPCODE_CLOSE, f"opcode inattendu: {opcode}"
+    ws.close()
+    print("✓ Close traité proprement")
+
+
+if __name__ == "__main__":
+    try:
+        test_welcome()
+        test_ping()
+        test_empty_text()
+        test_binary_rejected()
+        test_close()
+    except AssertionError as e:
+        print(f"✗ Échec: {e}")
+        sys.exit(1)
+    print("\nTous les tests de trames WebSocket sont passés")
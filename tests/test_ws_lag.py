@@ -0,0 +1,83 @@
+#!/usr/bin/env python3
+"""
+Test de la trame de contrôle `lag` sur le endpoint WebSocket brut (/ws).
+
+Le serveur doit être démarré avec un canal par sujet minuscule pour forcer la saturation :
+
+    TOPIC_CHANNEL_CAPACITY=1 cargo run
+
+Une rafale de publications concurrentes dépasse la capacité du canal ; l'abonné reçoit alors
+des trames `{"event": "lag", "topic", "missed"}` au lieu de perdre les messages en silence.
+Vérifie que :
+- au moins une trame `lag` est reçue, pour le bon sujet
+- elle se distingue des messages livrés (champ `event`, pas de `message_id`)
+- messages reçus + messages manqués = messages publiés
+- le total `missed` correspond au compteur `dropped` de `GET /topics/{topic}/rate`
+
+Prérequis : pip3 install websocket-client requests
+"""
+import json
+import sys
+import uuid
+from concurrent.futures import ThreadPoolExecutor
+
+import requests
+import websocket
+
+WS_URL = "ws://localhost:5000/ws"
+BASE_URL = "http://localhost:5000"
+BURST = 500
+
+
+def publish(topic, index):
+    response = requests.post(f"{BASE_URL}/publish", json={
+        "topic": topic,
+        "message_id": str(uuid.uuid4()),
+        "message": {"index": index},
+        "producer": "test_ws_lag",
+    }, timeout=5)
+    assert response.status_code == 200, f"publication refusée: {response.text}"
+
+
+def test_lag_frame():
+    topic = f"lag-{uuid.uuid4().hex[:8]}"
+    ws = websocket.create_connection(WS_URL, timeout=2)
+    welcome = json.loads(ws.recv())
+    assert welcome["event"] == "welcome", f"trame inattendue: {welcome}"
+    ws.send(json.dumps({"event": "subscribe", "consumer": "test_ws_lag", "topics": [topic]}))
+
+    with ThreadPoolExecutor(max_workers=16) as pool:
+        list(pool.map(lambda index: publish(topic, index), range(BURST)))
+
+    delivered = 0
+    missed = 0
+    lag_frames = 0
+    while True:
+        try:
+            frame = json.loads(ws.recv())
+        except websocket.WebSocketTimeoutException:
+            break
+        if frame.get("event") == "lag":
+            assert frame["topic"] == topic, f"sujet inattendu: {frame}"
+            assert "message_id" not in frame, f"trame lag ambiguë: {frame}"
+            assert frame["missed"] > 0, f"missed invalide: {frame}"
+            lag_frames += 1
+            missed += frame["missed"]
+        elif "message_id" in frame:
+            delivered += 1
+    ws.close()
+
+    assert lag_frames > 0, "aucune trame lag reçue (serveur démarré avec TOPIC_CHANNEL_CAPACITY=1 ?)"
+    assert delivered + missed == BURST, f"{delivered} reçus + {missed} manqués != {BURST} publiés"
+    rate = requests.get(f"{BASE_URL}/topics/{topic}/rate", timeout=5).json()
+    assert rate["dropped"] == missed, f"dropped={rate['dropped']}, missed={missed}"
+    print(f"✓ {lag_frames} trames lag reçues: {delivered} reçus + {missed} manqués = {BURST}")
+
+
+if __name__ == "__main__":
+    try:
+        test_lag_frame()
+    except AssertionError as e:
+        print(f"✗ Échec: {e}")
+        sys.exit(1)
+    print("\nTest de la trame lag passé")